@@ -0,0 +1,187 @@
+use std::{error, fmt};
+
+/// Identifies a line of source by its file and zero-based line index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: usize,
+    pub line: usize,
+}
+
+/// A range of byte offsets within a single source line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub location: Location,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a new span covering `start..end` of the given line.
+    pub fn new(location: Location, start: usize, end: usize) -> Self {
+        Span {
+            location,
+            start,
+            end,
+        }
+    }
+
+    /// Create a span covering both `self` and `other`, if they are on the same line.
+    pub fn to(self, other: Span) -> Span {
+        if self.location != other.location {
+            return self;
+        }
+
+        Span::new(
+            self.location,
+            self.start.min(other.start),
+            self.end.max(other.end),
+        )
+    }
+}
+
+/// An error found while assembling, before it is rendered against the source text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceError {
+    pub span: Span,
+    pub message: String,
+    pub notes: Vec<(Span, String)>,
+}
+
+impl SourceError {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        SourceError {
+            span,
+            message: message.into(),
+            notes: vec![],
+        }
+    }
+
+    /// Attach a note pointing at another place in the source.
+    pub fn with_note(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.notes.push((span, message.into()));
+        self
+    }
+}
+
+/// A single problem found while assembling, pinned to a position in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub width: usize,
+    pub message: String,
+    pub source_line: String,
+    pub notes: Vec<Diagnostic>,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic for a span on the given line of a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The name of the file the line belongs to.
+    /// * `source_line` - The full text of the line.
+    /// * `span` - The part of the line to point at.
+    /// * `message` - What went wrong.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::assembler::diagnostic::{Diagnostic, Location, Span};
+    /// let span = Span::new(Location { file: 0, line: 2 }, 4, 7);
+    /// let diagnostic = Diagnostic::new("main.s", "    LDZ #$01", span, "unknown instruction `LDZ`");
+    /// assert_eq!((diagnostic.line, diagnostic.column), (3, 5));
+    /// ```
+    pub fn new(file: &str, source_line: &str, span: Span, message: impl Into<String>) -> Self {
+        let start = span.start.min(source_line.len());
+        let end = span.end.clamp(start, source_line.len());
+
+        Diagnostic {
+            file: file.to_string(),
+            line: span.location.line + 1,
+            column: source_line[..start].chars().count() + 1,
+            width: source_line[start..end].chars().count().max(1),
+            message: message.into(),
+            source_line: source_line.to_string(),
+            notes: vec![],
+        }
+    }
+
+    fn render(&self, f: &mut fmt::Formatter, level: &str) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+
+        // Reuse the line's own whitespace so the caret lines up even with tabs
+        let indent: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "{}: {}", level, self.message)?;
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter, self.file, self.line, self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, self.source_line)?;
+        write!(f, "{} | {}{}", gutter, indent, "^".repeat(self.width))?;
+
+        for note in &self.notes {
+            writeln!(f)?;
+            note.render(f, "note")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.render(f, "error")
+    }
+}
+
+/// Every diagnostic collected while assembling a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics(vec![])
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.0.iter()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for diagnostic in &self.0 {
+            writeln!(f, "{}\n", diagnostic)?;
+        }
+
+        match self.0.len() {
+            1 => write!(f, "assembly failed due to 1 error"),
+            count => write!(f, "assembly failed due to {} errors", count),
+        }
+    }
+}
+
+impl error::Error for Diagnostics {}
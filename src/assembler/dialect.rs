@@ -0,0 +1,640 @@
+use super::diagnostic::{SourceError, Span};
+use super::lexer::{Token, TokenKind};
+use super::parser::{named_label, split_arguments, Line};
+use crate::cpu::operations::{get_addressing_modes, Variant};
+
+use clap::ValueEnum;
+use std::collections::HashSet;
+
+/// The assembler syntaxes a program can be written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Dialect {
+    /// This assembler's own syntax.
+    #[default]
+    Native,
+    /// The syntax of ca65, from the cc65 suite.
+    Ca65,
+    /// The syntax of the ACME cross-assembler.
+    Acme,
+}
+
+/// ca65 directives that only matter to its linker or listing, and are skipped.
+const CA65_IGNORED: [&str; 16] = [
+    ".autoimport",
+    ".case",
+    ".debuginfo",
+    ".export",
+    ".exportzp",
+    ".feature",
+    ".global",
+    ".globalzp",
+    ".import",
+    ".importzp",
+    ".list",
+    ".listbytes",
+    ".p02",
+    ".segment",
+    ".setcpu",
+    ".smart",
+];
+
+/// ACME pseudo-ops that only matter to its output files, and are skipped.
+const ACME_IGNORED: [&str; 6] = ["!to", "!cpu", "!zone", "!zn", "!sl", "!symbollist"];
+
+/// Rewrite the lines of a file into the native syntax.
+///
+/// For ca65 this renames directives such as `.res` and `.endmacro`, turns
+/// `.lobyte` and `.hibyte` into `<` and `>`, accepts `:=` assignments and skips
+/// linker directives, so code is placed by `.org` alone. Symbols defined in a
+/// `.proc` or `.scope` are renamed `scope@name`, as local labels are, and
+/// `scope::name` refers to them from outside. For ACME it turns
+/// `!` pseudo-ops into directives, `{` ... `}` blocks into `.endif` and `.endm`,
+/// `+name` into macro calls and `lda+2` into `lda a:`, and adds the colon after
+/// labels that lack one.
+///
+/// # Arguments
+///
+/// * `lines` - The tokenized lines of one file.
+/// * `dialect` - The syntax the file is written in.
+/// * `variant` - The processor, whose instruction names are never taken for labels.
+///
+/// # Returns
+///
+/// The rewritten lines, and any errors found along the way.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assembler::{assemble_with, dialect::Dialect, source::SourceFile, Options};
+/// let options = Options { dialect: Dialect::Ca65, ..Options::default() };
+/// let assembly = assemble_with(SourceFile::new("table.s", "table: .res 4"), &options).unwrap();
+/// assert_eq!(assembly.bytes, [0, 0, 0, 0]);
+/// ```
+pub(crate) fn translate(
+    lines: Vec<Line>,
+    dialect: Dialect,
+    variant: Variant,
+) -> (Vec<Line>, Vec<SourceError>) {
+    match dialect {
+        Dialect::Native => (lines, vec![]),
+        Dialect::Ca65 => Ca65::default().translate(lines.into_iter().map(ca65).collect()),
+        Dialect::Acme => Acme {
+            variant,
+            ..Acme::default()
+        }
+        .translate(lines),
+    }
+}
+
+fn ca65(mut line: Line) -> Line {
+    // `.lobyte(x)` and `.hibyte(x)` may appear anywhere in an expression
+    for token in &mut line.tokens {
+        match token.ident().map(str::to_lowercase).as_deref() {
+            Some(".lobyte") => token.kind = TokenKind::Less,
+            Some(".hibyte") => token.kind = TokenKind::Greater,
+            _ => (),
+        }
+    }
+
+    let tokens = &mut line.tokens;
+    let start = match tokens.as_slice() {
+        // `NAME := value` is an ordinary assignment
+        [Token {
+            kind: TokenKind::Ident(_),
+            ..
+        }, colon @ Token {
+            kind: TokenKind::Colon,
+            ..
+        }, equals @ Token {
+            kind: TokenKind::Equals,
+            ..
+        }, ..]
+            if colon.span.end == equals.span.start =>
+        {
+            tokens.remove(1);
+            return line;
+        }
+        tokens if named_label(tokens).is_some() => 2,
+        _ => 0,
+    };
+
+    let directive = match tokens.get(start).and_then(Token::ident) {
+        Some(name) => name.to_lowercase(),
+        None => return line,
+    };
+
+    let native = match directive.as_str() {
+        ".byt" | ".asciiz" => ".byte",
+        ".addr" => ".word",
+        ".res" => ".fill",
+        ".mac" => ".macro",
+        ".endmacro" | ".endmac" => ".endm",
+        name if CA65_IGNORED.contains(&name) => {
+            tokens.truncate(start);
+            return line;
+        }
+        _ => return line,
+    };
+
+    let span = tokens[start].span;
+    tokens[start].kind = TokenKind::Ident(native.to_string());
+
+    if directive == ".asciiz" {
+        tokens.push(token(TokenKind::Comma, span));
+        tokens.push(token(TokenKind::Number { value: 0 }, span));
+    }
+
+    line
+}
+
+/// A ca65 directive that opens or closes a scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScopeKind {
+    Proc,
+    Scope,
+}
+
+#[derive(Debug, Default)]
+struct Ca65 {
+    /// The full name of each open scope, innermost last, with its kind and directive.
+    open: Vec<(String, ScopeKind, Span)>,
+    /// How many unnamed `.scope` blocks have been opened, to name them apart.
+    unnamed: usize,
+    /// Whether the lines are inside a macro body, which is left alone.
+    in_macro: bool,
+    /// The full name of every scope.
+    scopes: HashSet<String>,
+    /// The full name of every symbol defined inside a scope.
+    defined: HashSet<String>,
+    errors: Vec<SourceError>,
+}
+
+impl Ca65 {
+    /// Find every scoped symbol first, so that forward references are renamed
+    /// too, then rename them and turn `.proc` into a label.
+    fn translate(mut self, lines: Vec<Line>) -> (Vec<Line>, Vec<SourceError>) {
+        for line in &lines {
+            if self.enter(line).is_none() {
+                if let (Some(name), Some(prefix)) = (defined_name(&line.tokens), self.prefix()) {
+                    self.defined.insert(format!("{}@{}", prefix, name));
+                }
+            }
+        }
+        self.errors.clear();
+        self.unnamed = 0;
+
+        let lines = lines.into_iter().map(|line| self.line(line)).collect();
+
+        for (_, kind, span) in self.open.drain(..) {
+            let directive = match kind {
+                ScopeKind::Proc => ".proc",
+                ScopeKind::Scope => ".scope",
+            };
+            self.errors.push(SourceError::new(
+                span,
+                format!("`{}` has no matching end", directive),
+            ));
+        }
+
+        (lines, self.errors)
+    }
+
+    /// The full name new symbols are placed under, or `None` outside any scope.
+    fn prefix(&self) -> Option<&str> {
+        match self.in_macro {
+            true => None,
+            false => self.open.last().map(|(name, _, _)| name.as_str()),
+        }
+    }
+
+    /// Open or close a scope or macro body at a directive.
+    ///
+    /// # Returns
+    ///
+    /// The tokens the line becomes, or `None` if it holds no such directive.
+    fn enter(&mut self, line: &Line) -> Option<Vec<Token>> {
+        let directive = line.tokens.first()?;
+        let name = directive.ident()?.to_lowercase();
+
+        match name.as_str() {
+            ".macro" => self.in_macro = true,
+            ".endm" => self.in_macro = false,
+            _ => (),
+        }
+        if self.in_macro && name != ".macro" {
+            return None;
+        }
+
+        let kind = match name.as_str() {
+            ".proc" => ScopeKind::Proc,
+            ".scope" => ScopeKind::Scope,
+            ".endproc" | ".endscope" => {
+                let closing = match name.as_str() {
+                    ".endproc" => ScopeKind::Proc,
+                    _ => ScopeKind::Scope,
+                };
+                match self.open.pop() {
+                    Some((_, kind, _)) if kind == closing => (),
+                    _ => self.errors.push(SourceError::new(
+                        directive.span,
+                        format!("`{}` without a matching opening directive", name),
+                    )),
+                }
+                return Some(vec![]);
+            }
+            _ => return None,
+        };
+
+        let written = match line.tokens.get(1) {
+            Some(Token {
+                kind: TokenKind::Ident(written),
+                ..
+            }) => Some(written.clone()),
+            _ => None,
+        };
+        let (written, label) = match (written, kind) {
+            (Some(written), _) => (written, line.tokens.get(1).map(|token| token.span)),
+            (None, ScopeKind::Scope) => {
+                self.unnamed += 1;
+                (format!("scope#{}", self.unnamed), None)
+            }
+            (None, ScopeKind::Proc) => {
+                self.errors
+                    .push(SourceError::new(line.end, "expected a name after `.proc`"));
+                return Some(vec![]);
+            }
+        };
+
+        let full = match self.prefix() {
+            Some(prefix) => format!("{}@{}", prefix, written),
+            None => written,
+        };
+        if kind == ScopeKind::Proc && self.prefix().is_some() {
+            self.defined.insert(full.clone());
+        }
+        self.scopes.insert(full.clone());
+        self.open.push((full.clone(), kind, directive.span));
+
+        // A `.proc` is a label for its first instruction
+        Some(match (kind, label) {
+            (ScopeKind::Proc, Some(span)) => vec![
+                token(TokenKind::Ident(full), span),
+                token(
+                    TokenKind::Colon,
+                    Span::new(span.location, span.end, span.end),
+                ),
+            ],
+            _ => vec![],
+        })
+    }
+
+    fn line(&mut self, line: Line) -> Line {
+        if let Some(tokens) = self.enter(&line) {
+            return line_with(line, tokens);
+        }
+        if self.in_macro {
+            return line;
+        }
+
+        let mut tokens: Vec<Token> = Vec::with_capacity(line.tokens.len());
+        let mut rest = line.tokens.iter();
+        while let Some(next) = rest.next() {
+            let written = match &next.kind {
+                TokenKind::Ident(written) if !written.starts_with(['.', '@']) => written,
+                _ => {
+                    tokens.push(next.clone());
+                    continue;
+                }
+            };
+
+            // `scope::name`, written without spaces
+            if let [first @ Token {
+                kind: TokenKind::Colon,
+                ..
+            }, second @ Token {
+                kind: TokenKind::Colon,
+                ..
+            }, name @ Token {
+                kind: TokenKind::Ident(member),
+                ..
+            }, ..] = rest.as_slice()
+            {
+                if next.span.end == first.span.start
+                    && first.span.end == second.span.start
+                    && second.span.end == name.span.start
+                {
+                    let scope = self.resolve(written, &self.scopes);
+                    let full = format!("{}@{}", scope, member);
+                    tokens.push(token(TokenKind::Ident(full), next.span.to(name.span)));
+                    rest.nth(2);
+                    continue;
+                }
+            }
+
+            let full = self.resolve(written, &self.defined);
+            tokens.push(token(TokenKind::Ident(full), next.span));
+        }
+
+        line_with(line, tokens)
+    }
+
+    /// The full name a name refers to, searching the open scopes from the
+    /// innermost out, or the name itself if no scope defines it.
+    fn resolve(&self, written: &str, names: &HashSet<String>) -> String {
+        self.open
+            .iter()
+            .rev()
+            .map(|(prefix, _, _)| format!("{}@{}", prefix, written))
+            .find(|full| names.contains(full))
+            .unwrap_or_else(|| written.to_string())
+    }
+}
+
+/// The symbol a line defines, as `name:` or `name = value`, unless it is a
+/// local label, which is scoped by the label before it instead.
+fn defined_name(tokens: &[Token]) -> Option<&str> {
+    let name = match tokens {
+        [name @ Token {
+            kind: TokenKind::Ident(_),
+            ..
+        }, Token {
+            kind: TokenKind::Equals,
+            ..
+        }, ..] => name,
+        tokens => named_label(tokens)?.0,
+    };
+    name.ident().filter(|name| !name.starts_with(['.', '@']))
+}
+
+/// A block opened with `{` in ACME source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    If,
+    Macro,
+}
+
+#[derive(Debug, Default)]
+struct Acme {
+    blocks: Vec<(Block, Span)>,
+    errors: Vec<SourceError>,
+    variant: Variant,
+}
+
+impl Acme {
+    fn translate(mut self, lines: Vec<Line>) -> (Vec<Line>, Vec<SourceError>) {
+        let lines = lines.into_iter().map(|line| self.line(line)).collect();
+
+        for (_, span) in self.blocks.drain(..) {
+            self.errors
+                .push(SourceError::new(span, "`{` has no matching `}`"));
+        }
+
+        (lines, self.errors)
+    }
+
+    fn line(&mut self, mut line: Line) -> Line {
+        let mut tokens = merge_pseudo_ops(std::mem::take(&mut line.tokens));
+
+        if let Some(first) = tokens
+            .first()
+            .filter(|token| token.kind == TokenKind::RBrace)
+        {
+            line.tokens = self.close(first.span, &tokens[1..]);
+            return line;
+        }
+
+        // A label is any name at the start of a line that is not an instruction
+        let start = match tokens.as_slice() {
+            [Token {
+                kind: TokenKind::Ident(name),
+                span,
+            }, rest @ ..]
+                if !name.starts_with('!')
+                    && get_addressing_modes(name, self.variant).is_empty() =>
+            {
+                match rest.first().map(|token| &token.kind) {
+                    Some(TokenKind::Colon) => 2,
+                    Some(TokenKind::Equals) => return line_with(line, tokens),
+                    _ => {
+                        let colon = token(
+                            TokenKind::Colon,
+                            Span::new(span.location, span.end, span.end),
+                        );
+                        tokens.insert(1, colon);
+                        2
+                    }
+                }
+            }
+            _ => 0,
+        };
+
+        // `+name` calls a macro, while a `+` on its own is an anonymous label
+        if let [plus @ Token {
+            kind: TokenKind::Plus,
+            ..
+        }, name @ Token {
+            kind: TokenKind::Ident(_),
+            ..
+        }, ..] = &tokens[start..]
+        {
+            if plus.span.end == name.span.start {
+                tokens.remove(start);
+                return line_with(line, tokens);
+            }
+        }
+
+        let pseudo_op = match tokens.get(start).and_then(Token::ident) {
+            Some(name) if name.starts_with('!') => name.to_lowercase(),
+            Some(_) => {
+                size_suffix(&mut tokens, start);
+                return line_with(line, tokens);
+            }
+            None => return line_with(line, tokens),
+        };
+        let span = tokens[start].span;
+
+        let (native, block) = match pseudo_op.as_str() {
+            "!byte" | "!by" | "!8" | "!text" | "!tx" => (".byte", None),
+            "!word" | "!wo" | "!16" => (".word", None),
+            "!fill" | "!fi" => (".fill", None),
+            "!source" | "!src" => (".include", None),
+            "!binary" | "!bin" => {
+                reorder_binary(&mut tokens, start);
+                (".incbin", None)
+            }
+            "!macro" => (".macro", Some(Block::Macro)),
+            "!if" => (".if", Some(Block::If)),
+            "!ifdef" => (".ifdef", Some(Block::If)),
+            "!ifndef" => (".ifndef", Some(Block::If)),
+            name if ACME_IGNORED.contains(&name) => {
+                tokens.truncate(start);
+                return line_with(line, tokens);
+            }
+            name => {
+                self.errors.push(SourceError::new(
+                    span,
+                    format!("unsupported ACME pseudo-op `{}`", name),
+                ));
+                return line_with(line, vec![]);
+            }
+        };
+
+        tokens[start].kind = TokenKind::Ident(native.to_string());
+
+        if let Some(block) = block {
+            match tokens.last() {
+                Some(last) if last.kind == TokenKind::LBrace => {
+                    tokens.pop();
+                    self.blocks.push((block, span));
+                }
+                _ => {
+                    self.errors.push(SourceError::new(
+                        line.end,
+                        format!("expected `{{` at the end of the `{}` line", pseudo_op),
+                    ));
+                    return line_with(line, vec![]);
+                }
+            }
+        }
+
+        line_with(line, tokens)
+    }
+
+    /// Close the innermost block at a `}`, or continue an `!if` with `} else {`.
+    fn close(&mut self, span: Span, rest: &[Token]) -> Vec<Token> {
+        let otherwise = matches!(
+            rest,
+            [Token {
+                kind: TokenKind::Ident(name),
+                ..
+            }, Token {
+                kind: TokenKind::LBrace,
+                ..
+            }] if name.eq_ignore_ascii_case("else")
+        );
+
+        if !rest.is_empty() && !otherwise {
+            self.errors.push(SourceError::new(
+                rest[0].span,
+                "expected a new line or `else {` after `}`",
+            ));
+        }
+
+        let native = match self.blocks.last() {
+            None => {
+                self.errors
+                    .push(SourceError::new(span, "`}` without a matching `{`"));
+                return vec![];
+            }
+            Some((Block::Macro, _)) if otherwise => {
+                self.errors.push(SourceError::new(
+                    span,
+                    "`else` can only follow an `!if` block",
+                ));
+                return vec![];
+            }
+            Some(_) if otherwise => ".else",
+            Some((Block::If, _)) => ".endif",
+            Some((Block::Macro, _)) => ".endm",
+        };
+
+        if !otherwise {
+            self.blocks.pop();
+        }
+
+        vec![token(TokenKind::Ident(native.to_string()), span)]
+    }
+}
+
+/// Join each `!` with the name written straight after it, as in `!byte` or `!8`.
+fn merge_pseudo_ops(tokens: Vec<Token>) -> Vec<Token> {
+    let mut merged: Vec<Token> = Vec::with_capacity(tokens.len());
+
+    for token in tokens {
+        let name = match &token.kind {
+            TokenKind::Ident(name) => name.clone(),
+            TokenKind::Number { value, .. } => value.to_string(),
+            _ => {
+                merged.push(token);
+                continue;
+            }
+        };
+
+        match merged.last_mut() {
+            Some(bang) if bang.kind == TokenKind::Bang && bang.span.end == token.span.start => {
+                bang.kind = TokenKind::Ident(format!("!{}", name));
+                bang.span = bang.span.to(token.span);
+            }
+            _ => merged.push(token),
+        }
+    }
+
+    merged
+}
+
+/// ACME's `lda+1 value` and `lda+2 value` become `lda z:value` and `lda a:value`.
+fn size_suffix(tokens: &mut Vec<Token>, start: usize) {
+    let prefix = match &tokens[start..] {
+        [mnemonic, plus @ Token {
+            kind: TokenKind::Plus,
+            ..
+        }, size @ Token {
+            kind: TokenKind::Number { value: 1 | 2 },
+            ..
+        }, ..]
+            if mnemonic.span.end == plus.span.start && plus.span.end == size.span.start =>
+        {
+            let name = if size.kind == (TokenKind::Number { value: 1 }) {
+                "z"
+            } else {
+                "a"
+            };
+            let span = plus.span.to(size.span);
+            [
+                token(TokenKind::Ident(name.to_string()), span),
+                token(
+                    TokenKind::Colon,
+                    Span::new(span.location, span.end, span.end),
+                ),
+            ]
+        }
+        _ => return,
+    };
+
+    tokens.splice(start + 1..start + 3, prefix);
+}
+
+/// ACME's `!binary "file", size, skip` becomes `.incbin "file", skip, size`.
+fn reorder_binary(tokens: &mut Vec<Token>, start: usize) {
+    let args = tokens.split_off(start + 1);
+    let parts = split_arguments(&args);
+    let comma = |tokens: &mut Vec<Token>, span| tokens.push(token(TokenKind::Comma, span));
+
+    match parts.as_slice() {
+        [file, size] => {
+            let span = size.first().map_or(tokens[start].span, |token| token.span);
+            tokens.extend(file.iter().cloned());
+            comma(tokens, span);
+            tokens.push(token(TokenKind::Number { value: 0 }, span));
+            comma(tokens, span);
+            tokens.extend(size.iter().cloned());
+        }
+        [file, size, skip] => {
+            let span = skip.first().map_or(tokens[start].span, |token| token.span);
+            tokens.extend(file.iter().cloned());
+            comma(tokens, span);
+            tokens.extend(skip.iter().cloned());
+            comma(tokens, span);
+            tokens.extend(size.iter().cloned());
+        }
+        _ => tokens.extend(args.iter().cloned()),
+    }
+}
+
+fn line_with(line: Line, tokens: Vec<Token>) -> Line {
+    Line { tokens, ..line }
+}
+
+fn token(kind: TokenKind, span: Span) -> Token {
+    Token { kind, span }
+}
@@ -0,0 +1,99 @@
+use super::diagnostic::SourceError;
+use super::lexer::Token;
+use super::parser::{Operand, Size};
+use crate::cpu::operations::{
+    get_addressing_modes, get_instruction, AddressingMode, InstructionInfo, Variant,
+};
+
+/// Choose the instruction encoding for a mnemonic and its operand.
+///
+/// Branches always use relative addressing. Otherwise a direct or indexed
+/// operand takes the form of the given size, falling back to the other size
+/// when the instruction lacks it, unless the size was forced. Only the
+/// instructions and addressing modes of the variant are allowed.
+///
+/// # Arguments
+///
+/// * `mnemonic` - The instruction name token.
+/// * `operand` - The parsed operand.
+/// * `size` - The preferred width of the operand address.
+/// * `forced` - Whether the size was written in the source, so no other is allowed.
+/// * `variant` - The processor the program is for.
+///
+/// # Returns
+///
+/// A `Result` containing the instruction to emit, or an error naming what is unsupported.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assemble;
+/// assert_eq!(assemble("LDA $10").unwrap(), [0xA5, 0x10]);
+/// assert_eq!(assemble("LDA $1000").unwrap(), [0xAD, 0x00, 0x10]);
+/// ```
+pub fn select(
+    mnemonic: &Token,
+    operand: &Operand,
+    size: Size,
+    forced: bool,
+    variant: Variant,
+) -> Result<InstructionInfo, SourceError> {
+    let name = mnemonic.ident().unwrap_or_default().to_uppercase();
+    let modes = get_addressing_modes(&name, variant);
+    if modes.is_empty() {
+        let message = if get_addressing_modes(&name, Variant::Cmos).is_empty() {
+            format!("unknown instruction `{}`", name)
+        } else {
+            format!("`{}` is only on the {}", name, Variant::Cmos)
+        };
+        return Err(SourceError::new(mnemonic.span, message));
+    }
+
+    let supports = |mode| modes.contains(&mode);
+
+    let sized = |zero_page, absolute| {
+        let (preferred, other) = match size {
+            Size::ZeroPage => (zero_page, absolute),
+            Size::Absolute => (absolute, zero_page),
+        };
+        if forced || supports(preferred) {
+            preferred
+        } else {
+            other
+        }
+    };
+
+    let addressing = match operand {
+        Operand::None if supports(AddressingMode::Implied) => AddressingMode::Implied,
+        Operand::None | Operand::Accumulator => AddressingMode::Accumulator,
+        Operand::Immediate(_) => AddressingMode::Immediate,
+        Operand::Direct(_) if supports(AddressingMode::Relative) => AddressingMode::Relative,
+        Operand::Direct(_) => sized(AddressingMode::ZeroPage, AddressingMode::Absolute),
+        Operand::IndexedX(_) => sized(AddressingMode::ZeroPageX, AddressingMode::AbsoluteX),
+        Operand::IndexedY(_) => sized(AddressingMode::ZeroPageY, AddressingMode::AbsoluteY),
+        Operand::Indirect(_) if supports(AddressingMode::Indirect) => AddressingMode::Indirect,
+        Operand::Indirect(_) if supports(AddressingMode::ZeroPageIndirect) => {
+            AddressingMode::ZeroPageIndirect
+        }
+        Operand::Indirect(_) => AddressingMode::Indirect,
+        Operand::IndexedIndirect(_) if supports(AddressingMode::AbsoluteIndexedIndirect) => {
+            AddressingMode::AbsoluteIndexedIndirect
+        }
+        Operand::IndexedIndirect(_) => AddressingMode::IndexedIndirect,
+        Operand::IndirectIndexed(_) => AddressingMode::IndirectIndexed,
+    };
+
+    match get_instruction(None, Some(&name), Some(addressing), variant) {
+        Some(instruction_info) => Ok(instruction_info),
+        None => match operand.value() {
+            None => Err(SourceError::new(
+                mnemonic.span,
+                format!("`{}` requires an operand", name),
+            )),
+            Some(value) => Err(SourceError::new(
+                value.span(),
+                format!("`{}` does not support {} addressing", name, addressing),
+            )),
+        },
+    }
+}
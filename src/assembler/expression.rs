@@ -0,0 +1,316 @@
+use super::diagnostic::{SourceError, Span};
+use super::lexer::{Token, TokenKind};
+use super::parser::{sign_run, Cursor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Complement,
+    Low,
+    High,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    ShiftLeft,
+    ShiftRight,
+    And,
+    Or,
+    Xor,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    LogicalAnd,
+    LogicalOr,
+}
+
+/// An operand expression, evaluated once symbol values are known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number {
+        value: i64,
+        span: Span,
+    },
+    Symbol {
+        name: String,
+        span: Span,
+    },
+    /// The address of the current statement, written `*`.
+    Current {
+        span: Span,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+        span: Span,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+        span: Span,
+    },
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Number { span, .. }
+            | Expr::Symbol { span, .. }
+            | Expr::Current { span }
+            | Expr::Unary { span, .. }
+            | Expr::Binary { span, .. } => *span,
+        }
+    }
+
+    /// Evaluate the expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - The value of `*`, the address of the current statement.
+    /// * `symbol` - Looks up the value of a symbol, or fails if it is undefined.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the value of the expression.
+    pub fn evaluate(
+        &self,
+        current: i64,
+        symbol: &dyn Fn(&str, Span) -> Result<i64, SourceError>,
+    ) -> Result<i64, SourceError> {
+        match self {
+            Expr::Number { value, .. } => Ok(*value),
+            Expr::Symbol { name, span } => symbol(name, *span),
+            Expr::Current { .. } => Ok(current),
+            Expr::Unary { op, operand, .. } => {
+                let value = operand.evaluate(current, symbol)?;
+                Ok(match op {
+                    UnaryOp::Negate => -value,
+                    UnaryOp::Complement => !value,
+                    UnaryOp::Low => value & 0xFF,
+                    UnaryOp::High => (value >> 8) & 0xFF,
+                    UnaryOp::Not => i64::from(value == 0),
+                })
+            }
+            Expr::Binary {
+                op,
+                left,
+                right,
+                span,
+            } => {
+                let left = left.evaluate(current, symbol)?;
+
+                // Logical operators stop early, so `.if` can guard the right side
+                match (op, left) {
+                    (BinaryOp::LogicalAnd, 0) => return Ok(0),
+                    (BinaryOp::LogicalOr, left) if left != 0 => return Ok(1),
+                    _ => (),
+                }
+
+                let right = right.evaluate(current, symbol)?;
+                Ok(match op {
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Subtract => left.wrapping_sub(right),
+                    BinaryOp::Multiply => left.wrapping_mul(right),
+                    BinaryOp::Divide if right == 0 => {
+                        return Err(SourceError::new(*span, "division by zero"))
+                    }
+                    BinaryOp::Divide => left / right,
+                    BinaryOp::ShiftLeft | BinaryOp::ShiftRight if !(0..64).contains(&right) => {
+                        return Err(SourceError::new(
+                            *span,
+                            format!("cannot shift by {}, only by 0 to 63", right),
+                        ))
+                    }
+                    BinaryOp::ShiftLeft => left << right,
+                    BinaryOp::ShiftRight => left >> right,
+                    BinaryOp::And => left & right,
+                    BinaryOp::Or => left | right,
+                    BinaryOp::Xor => left ^ right,
+                    BinaryOp::Equal => i64::from(left == right),
+                    BinaryOp::NotEqual => i64::from(left != right),
+                    BinaryOp::Less => i64::from(left < right),
+                    BinaryOp::Greater => i64::from(left > right),
+                    BinaryOp::LessEqual => i64::from(left <= right),
+                    BinaryOp::GreaterEqual => i64::from(left >= right),
+                    BinaryOp::LogicalAnd => i64::from(left != 0 && right != 0),
+                    BinaryOp::LogicalOr => i64::from(left != 0 || right != 0),
+                })
+            }
+        }
+    }
+}
+
+/// Parse a sequence of tokens that must form exactly one expression.
+///
+/// # Arguments
+///
+/// * `tokens` - The tokens of the expression.
+/// * `end` - A span just past the tokens, used when the expression is cut short.
+///
+/// # Returns
+///
+/// A `Result` containing the expression.
+pub fn parse_expression(tokens: &[Token], end: Span) -> Result<Expr, SourceError> {
+    let mut cursor = Cursor::new(tokens, end);
+    let expr = expression(&mut cursor)?;
+    cursor.expect_end()?;
+    Ok(expr)
+}
+
+/// Parse an expression from the cursor, leaving any tokens that follow it.
+pub fn expression(cursor: &mut Cursor) -> Result<Expr, SourceError> {
+    binary(cursor, 0)
+}
+
+// Binary operators from loosest to tightest binding. Comparisons yield 1 or 0,
+// and a lone `=` compares too, as in conditions such as `.if SIZE = 8`.
+const LEVELS: [&[(TokenKind, BinaryOp)]; 9] = [
+    &[(TokenKind::OrOr, BinaryOp::LogicalOr)],
+    &[(TokenKind::AndAnd, BinaryOp::LogicalAnd)],
+    &[
+        (TokenKind::Equals, BinaryOp::Equal),
+        (TokenKind::EqualEqual, BinaryOp::Equal),
+        (TokenKind::NotEqual, BinaryOp::NotEqual),
+        (TokenKind::Less, BinaryOp::Less),
+        (TokenKind::Greater, BinaryOp::Greater),
+        (TokenKind::LessEqual, BinaryOp::LessEqual),
+        (TokenKind::GreaterEqual, BinaryOp::GreaterEqual),
+    ],
+    &[(TokenKind::Pipe, BinaryOp::Or)],
+    &[(TokenKind::Caret, BinaryOp::Xor)],
+    &[(TokenKind::Ampersand, BinaryOp::And)],
+    &[
+        (TokenKind::ShiftLeft, BinaryOp::ShiftLeft),
+        (TokenKind::ShiftRight, BinaryOp::ShiftRight),
+    ],
+    &[
+        (TokenKind::Plus, BinaryOp::Add),
+        (TokenKind::Minus, BinaryOp::Subtract),
+    ],
+    &[
+        (TokenKind::Star, BinaryOp::Multiply),
+        (TokenKind::Slash, BinaryOp::Divide),
+    ],
+];
+
+fn binary(cursor: &mut Cursor, level: usize) -> Result<Expr, SourceError> {
+    if level == LEVELS.len() {
+        return unary(cursor);
+    }
+
+    let mut left = binary(cursor, level + 1)?;
+
+    while let Some(op) = cursor
+        .peek()
+        .and_then(|kind| LEVELS[level].iter().find(|(token, _)| token == kind))
+        .map(|(_, op)| *op)
+    {
+        cursor.advance();
+        let right = binary(cursor, level + 1)?;
+        let span = left.span().to(right.span());
+        left = Expr::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+            span,
+        };
+    }
+
+    Ok(left)
+}
+
+fn unary(cursor: &mut Cursor) -> Result<Expr, SourceError> {
+    if let Some(reference) = anonymous(cursor) {
+        return Ok(reference);
+    }
+
+    let op = match cursor.peek() {
+        Some(TokenKind::Minus) => UnaryOp::Negate,
+        Some(TokenKind::Tilde) => UnaryOp::Complement,
+        Some(TokenKind::Less) => UnaryOp::Low,
+        Some(TokenKind::Greater) => UnaryOp::High,
+        Some(TokenKind::Bang) => UnaryOp::Not,
+        _ => return primary(cursor),
+    };
+
+    let start = cursor.peek_span();
+    cursor.advance();
+    let operand = unary(cursor)?;
+
+    Ok(Expr::Unary {
+        op,
+        span: start.to(operand.span()),
+        operand: Box::new(operand),
+    })
+}
+
+/// Parse a reference to an anonymous label, such as `-` or `++`, if one is next.
+///
+/// A run of signs only names a label when nothing follows it in the operand,
+/// so `-1` is still a negative number. Unnamed labels are referred to with a
+/// colon before the signs, as in `:+` or `:--`.
+fn anonymous(cursor: &mut Cursor) -> Option<Expr> {
+    let tokens = cursor.rest();
+    let first = tokens.first()?;
+    let colon = first.kind == TokenKind::Colon;
+    let signs = &tokens[usize::from(colon)..];
+
+    match signs.first() {
+        Some(sign) if matches!(sign.kind, TokenKind::Plus | TokenKind::Minus) => {
+            if colon && sign.span.start != first.span.end {
+                return None;
+            }
+        }
+        _ => return None,
+    }
+
+    let length = sign_run(signs);
+    match signs.get(length).map(|token| &token.kind) {
+        None | Some(TokenKind::Comma | TokenKind::RParen) => (),
+        Some(_) => return None,
+    }
+
+    let prefix = if colon { ":" } else { "" };
+    let name = format!("{}{}", prefix, signs[0].kind.to_string().repeat(length));
+    let span = first.span.to(signs[length - 1].span);
+    for _ in 0..length + usize::from(colon) {
+        cursor.advance();
+    }
+
+    Some(Expr::Symbol { name, span })
+}
+
+fn primary(cursor: &mut Cursor) -> Result<Expr, SourceError> {
+    let span = cursor.peek_span();
+
+    match cursor.advance().map(|token| &token.kind) {
+        Some(TokenKind::Number { value }) => Ok(Expr::Number {
+            value: i64::from(*value),
+            span,
+        }),
+        Some(TokenKind::Ident(name)) => Ok(Expr::Symbol {
+            name: name.clone(),
+            span,
+        }),
+        Some(TokenKind::Star) => Ok(Expr::Current { span }),
+        Some(TokenKind::LParen) => {
+            let expr = expression(cursor)?;
+            cursor.expect(&TokenKind::RParen, "`)`")?;
+            Ok(expr)
+        }
+        Some(kind) => Err(SourceError::new(
+            span,
+            format!("expected an expression, found `{}`", kind),
+        )),
+        None => Err(SourceError::new(span, "expected an expression")),
+    }
+}
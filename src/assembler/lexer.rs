@@ -0,0 +1,265 @@
+use super::diagnostic::{Location, SourceError, Span};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident(String),
+    Number { value: u32 },
+    Str(String),
+    Hash,
+    Comma,
+    Colon,
+    Equals,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Less,
+    Greater,
+    ShiftLeft,
+    ShiftRight,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    Bang,
+    EqualEqual,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    AndAnd,
+    OrOr,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Ident(name) => write!(f, "{}", name),
+            TokenKind::Number { value, .. } => write!(f, "${:X}", value),
+            TokenKind::Str(text) => write!(f, "{:?}", text),
+            TokenKind::Hash => write!(f, "#"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::Equals => write!(f, "="),
+            TokenKind::LParen => write!(f, "("),
+            TokenKind::RParen => write!(f, ")"),
+            TokenKind::LBrace => write!(f, "{{"),
+            TokenKind::RBrace => write!(f, "}}"),
+            TokenKind::Plus => write!(f, "+"),
+            TokenKind::Minus => write!(f, "-"),
+            TokenKind::Star => write!(f, "*"),
+            TokenKind::Slash => write!(f, "/"),
+            TokenKind::Less => write!(f, "<"),
+            TokenKind::Greater => write!(f, ">"),
+            TokenKind::ShiftLeft => write!(f, "<<"),
+            TokenKind::ShiftRight => write!(f, ">>"),
+            TokenKind::Ampersand => write!(f, "&"),
+            TokenKind::Pipe => write!(f, "|"),
+            TokenKind::Caret => write!(f, "^"),
+            TokenKind::Tilde => write!(f, "~"),
+            TokenKind::Bang => write!(f, "!"),
+            TokenKind::EqualEqual => write!(f, "=="),
+            TokenKind::NotEqual => write!(f, "!="),
+            TokenKind::LessEqual => write!(f, "<="),
+            TokenKind::GreaterEqual => write!(f, ">="),
+            TokenKind::AndAnd => write!(f, "&&"),
+            TokenKind::OrOr => write!(f, "||"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    /// Get the name of an identifier token.
+    pub fn ident(&self) -> Option<&str> {
+        match &self.kind {
+            TokenKind::Ident(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Split a single line of assembly into tokens, stopping at a `;` comment.
+///
+/// # Arguments
+///
+/// * `line` - The line to tokenize.
+/// * `location` - Where the line lives, recorded in every token's span.
+///
+/// # Returns
+///
+/// A `Result` containing the tokens of the line, or an error pointing at the offending character.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assemble;
+/// assert_eq!(assemble("LDA #$01 ; load one").unwrap(), [0xA9, 0x01]);
+/// ```
+pub fn tokenize(line: &str, location: Location) -> Result<Vec<Token>, SourceError> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let end_of = |index: usize| chars.get(index).map_or(line.len(), |(offset, _)| *offset);
+    let span = |from: usize, to: usize| Span::new(location, end_of(from), end_of(to));
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i].1;
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+
+        let (kind, length) = match c {
+            ';' => break,
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '#' => (TokenKind::Hash, 1),
+            ',' => (TokenKind::Comma, 1),
+            ':' => (TokenKind::Colon, 1),
+            '=' if next == Some('=') => (TokenKind::EqualEqual, 2),
+            '=' => (TokenKind::Equals, 1),
+            '!' if next == Some('=') => (TokenKind::NotEqual, 2),
+            '!' => (TokenKind::Bang, 1),
+            '(' => (TokenKind::LParen, 1),
+            ')' => (TokenKind::RParen, 1),
+            '{' => (TokenKind::LBrace, 1),
+            '}' => (TokenKind::RBrace, 1),
+            '+' => (TokenKind::Plus, 1),
+            '-' => (TokenKind::Minus, 1),
+            '*' => (TokenKind::Star, 1),
+            '/' => (TokenKind::Slash, 1),
+            '<' if next == Some('<') => (TokenKind::ShiftLeft, 2),
+            '>' if next == Some('>') => (TokenKind::ShiftRight, 2),
+            '<' if next == Some('=') => (TokenKind::LessEqual, 2),
+            '>' if next == Some('=') => (TokenKind::GreaterEqual, 2),
+            '<' if next == Some('>') => (TokenKind::NotEqual, 2),
+            '<' => (TokenKind::Less, 1),
+            '>' => (TokenKind::Greater, 1),
+            '&' if next == Some('&') => (TokenKind::AndAnd, 2),
+            '&' => (TokenKind::Ampersand, 1),
+            '|' if next == Some('|') => (TokenKind::OrOr, 2),
+            '|' => (TokenKind::Pipe, 1),
+            '^' => (TokenKind::Caret, 1),
+            '~' => (TokenKind::Tilde, 1),
+            '$' | '%' | '0'..='9' => {
+                let (radix, first) = match c {
+                    '$' => (16, i + 1),
+                    '%' => (2, i + 1),
+                    _ => (10, i),
+                };
+
+                let mut j = first;
+                while j < chars.len() && chars[j].1.is_ascii_alphanumeric() {
+                    j += 1;
+                }
+
+                let digits = &line[end_of(first)..end_of(j)];
+                if digits.is_empty() {
+                    return Err(SourceError::new(
+                        span(i, j.max(i + 1)),
+                        format!("expected digits after `{}`", c),
+                    ));
+                }
+
+                let value = match u32::from_str_radix(digits, radix) {
+                    Ok(value) if value <= 0xFFFF => value,
+                    Ok(_) => {
+                        return Err(SourceError::new(
+                            span(i, j),
+                            "number does not fit in 16 bits",
+                        ))
+                    }
+                    Err(_) => {
+                        return Err(SourceError::new(
+                            span(i, j),
+                            format!("invalid digit in base {} number `{}`", radix, digits),
+                        ))
+                    }
+                };
+
+                (TokenKind::Number { value }, j - i)
+            }
+            '\'' => match next {
+                Some(character) if character.is_ascii() => {
+                    // The closing quote is optional, as in most 6502 assemblers
+                    let closed = chars.get(i + 2).map(|(_, c)| *c) == Some('\'');
+                    let value = TokenKind::Number {
+                        value: character as u32,
+                    };
+                    (value, if closed { 3 } else { 2 })
+                }
+                _ => {
+                    return Err(SourceError::new(
+                        span(i, i + 2),
+                        "expected an ASCII character after `'`",
+                    ))
+                }
+            },
+            '"' => {
+                let mut text = String::new();
+                let mut j = i + 1;
+
+                loop {
+                    match chars.get(j).map(|(_, c)| *c) {
+                        None => return Err(SourceError::new(span(i, j), "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => {
+                            let escaped = match chars.get(j + 1).map(|(_, c)| *c) {
+                                Some('n') => '\n',
+                                Some('r') => '\r',
+                                Some('0') => '\0',
+                                Some(c @ ('\\' | '"')) => c,
+                                _ => {
+                                    return Err(SourceError::new(
+                                        span(j, j + 2),
+                                        "unknown escape sequence",
+                                    ))
+                                }
+                            };
+                            text.push(escaped);
+                            j += 2;
+                        }
+                        Some(c) => {
+                            text.push(c);
+                            j += 1;
+                        }
+                    }
+                }
+
+                (TokenKind::Str(text), j + 1 - i)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '@' => {
+                let mut j = i + 1;
+                while j < chars.len() && (chars[j].1.is_ascii_alphanumeric() || chars[j].1 == '_') {
+                    j += 1;
+                }
+
+                let name = line[end_of(i)..end_of(j)].to_string();
+                (TokenKind::Ident(name), j - i)
+            }
+            c => {
+                return Err(SourceError::new(
+                    span(i, i + 1),
+                    format!("unexpected character `{}`", c),
+                ))
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            span: span(i, i + length),
+        });
+        i += length;
+    }
+
+    Ok(tokens)
+}
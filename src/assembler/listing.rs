@@ -0,0 +1,104 @@
+use super::pass::describe;
+use super::Assembly;
+use std::fmt::Write;
+
+/// How many bytes are shown on each row of a listing.
+const BYTES_PER_ROW: usize = 3;
+
+/// What the assembler did with one line of source, in the order lines were assembled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingLine {
+    /// The name of the file the line came from.
+    pub file: String,
+    /// The one-based line number within the file.
+    pub line: usize,
+    /// The source text, as written in the file or macro definition.
+    pub text: String,
+    /// The address of the line, or `None` when nothing was assembled from it.
+    pub address: Option<u16>,
+    pub bytes: Vec<u8>,
+    /// The base cycle count of an instruction, and whether it can take longer.
+    pub cycles: Option<(u8, bool)>,
+    /// Whether the line came from a macro expansion.
+    pub expanded: bool,
+}
+
+/// Render a listing of an assembled program, followed by its symbol table.
+///
+/// Every row shows the address, up to three bytes, the cycle count of an
+/// instruction, the source line number and the source text. Cycle counts that
+/// can grow when a page boundary is crossed or a branch is taken are marked
+/// with a `+`, and lines from macro expansions are marked the same way after
+/// their line number. A comment row names the file whenever it changes.
+///
+/// # Arguments
+///
+/// * `assembly` - The assembled program.
+///
+/// # Returns
+///
+/// The listing as text.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assembler::{self, listing::render};
+/// let assembly = assembler::assemble_source("main.s", "LDA #$01").unwrap();
+/// assert!(render(&assembly).contains("0800  A9 01       2       1  LDA #$01"));
+/// ```
+pub fn render(assembly: &Assembly) -> String {
+    let mut output = String::new();
+    let mut file = None;
+
+    for line in &assembly.listing {
+        if file != Some(&line.file) {
+            file = Some(&line.file);
+            let _ = writeln!(output, "{:26}; {}", "", line.file);
+        }
+
+        let address = line
+            .address
+            .map_or(String::new(), |address| format!("{:04X}", address));
+        let cycles = match line.cycles {
+            Some((cycles, true)) => format!("{}+", cycles),
+            Some((cycles, false)) => format!("{} ", cycles),
+            None => String::new(),
+        };
+        let marker = if line.expanded { '+' } else { ' ' };
+
+        let mut rows = line.bytes.chunks(BYTES_PER_ROW);
+        let first = rows.next().map_or(String::new(), hex);
+        let row = format!(
+            "{:4}  {:8}  {:>4} {:>6}{} {}",
+            address, first, cycles, line.line, marker, line.text
+        );
+        let _ = writeln!(output, "{}", row.trim_end());
+
+        // Bytes that do not fit on the first row continue below it
+        let mut next = line.address.unwrap_or_default();
+        for row in rows {
+            next = next.wrapping_add(BYTES_PER_ROW as u16);
+            let _ = writeln!(output, "{:04X}  {}", next, hex(row));
+        }
+    }
+
+    let _ = writeln!(output);
+    let _ = writeln!(output, "Symbols:");
+    for (name, value) in &assembly.symbols {
+        let value = match value {
+            0..=0xFFFF => format!("${:04X}", value),
+            _ => describe(*value),
+        };
+        let _ = writeln!(output, "  {:24} {}", name, value);
+    }
+
+    output
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,116 @@
+use super::diagnostic::{SourceError, Span};
+use super::lexer::{Token, TokenKind};
+use super::parser::{named_label, split_arguments, Line};
+
+use std::collections::HashSet;
+
+/// A macro recorded from a `.macro` ... `.endm` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    pub name: String,
+    pub params: Vec<String>,
+    /// Labels defined in the body, renamed in every expansion so each gets its own copy.
+    pub locals: HashSet<String>,
+    pub body: Vec<Line>,
+    /// Where the macro was defined, for notes in diagnostics.
+    pub span: Span,
+}
+
+impl Macro {
+    /// Create a macro from the arguments of its `.macro` directive and its body.
+    ///
+    /// # Arguments
+    ///
+    /// * `directive` - The `.macro` token itself, used when the name is missing.
+    /// * `args` - The tokens after `.macro`: the name, then comma-separated parameters.
+    /// * `body` - The lines between `.macro` and `.endm`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the macro.
+    pub fn new(directive: &Token, args: &[Token], body: Vec<Line>) -> Result<Macro, SourceError> {
+        let (name, span) = match args.first() {
+            Some(token) => match token.ident() {
+                Some(name) => (name.to_string(), token.span),
+                None => return Err(SourceError::new(token.span, "expected a macro name")),
+            },
+            None => return Err(SourceError::new(directive.span, "expected a macro name")),
+        };
+
+        let mut params = vec![];
+        for param in split_arguments(&args[1..]) {
+            match param {
+                [Token {
+                    kind: TokenKind::Ident(param),
+                    span,
+                }] => {
+                    if params.contains(param) {
+                        return Err(SourceError::new(
+                            *span,
+                            format!("duplicate parameter `{}`", param),
+                        ));
+                    }
+                    params.push(param.clone());
+                }
+                [token, ..] => {
+                    return Err(SourceError::new(token.span, "expected a parameter name"))
+                }
+                [] => return Err(SourceError::new(span, "expected a parameter name")),
+            }
+        }
+
+        let locals = body
+            .iter()
+            .filter_map(|line| named_label(&line.tokens).and_then(|(label, _)| label.ident()))
+            .filter(|label| !params.iter().any(|param| param == label))
+            .map(String::from)
+            .collect();
+
+        Ok(Macro {
+            name,
+            params,
+            locals,
+            body,
+            span,
+        })
+    }
+
+    /// Produce the lines of one expansion of the macro.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The argument tokens of the call, one slice per parameter.
+    /// * `expansion` - A number unique to this expansion, used to rename local labels.
+    ///
+    /// # Returns
+    ///
+    /// The body with parameters replaced by the arguments and local labels renamed.
+    pub fn expand(&self, args: &[&[Token]], expansion: usize) -> Vec<Line> {
+        self.body
+            .iter()
+            .map(|line| {
+                let mut tokens = vec![];
+
+                for token in &line.tokens {
+                    let name = token.ident().unwrap_or_default();
+
+                    if let Some(index) = self.params.iter().position(|param| param == name) {
+                        tokens.extend(args[index].iter().cloned());
+                    } else if self.locals.contains(name) {
+                        tokens.push(Token {
+                            kind: TokenKind::Ident(format!("{}#{}", name, expansion)),
+                            span: token.span,
+                        });
+                    } else {
+                        tokens.push(token.clone());
+                    }
+                }
+
+                Line {
+                    tokens,
+                    ..line.clone()
+                }
+            })
+            .collect()
+    }
+}
@@ -1,6 +1,61 @@
-use super::cpu::operations::{get_instruction, AddressingMode};
-use anyhow::{bail, Result};
+use crate::cpu::operations::Variant;
+use diagnostic::{Diagnostic, Diagnostics, Location, SourceError, Span};
+use dialect::Dialect;
+use expression::{parse_expression, Expr};
+use lexer::{tokenize, TokenKind};
+use listing::ListingLine;
+use pass::{Layout, Pass};
+use source::{SourceFile, Sources};
+
+use anyhow::{bail, Context, Result};
 use log::info;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+pub mod diagnostic;
+pub mod dialect;
+pub(crate) mod encoder;
+pub(crate) mod expression;
+pub(crate) mod lexer;
+pub mod listing;
+pub(crate) mod macros;
+pub(crate) mod parser;
+pub(crate) mod pass;
+pub mod source;
+
+/// The address programs are assembled for, matching where `main` loads them.
+pub const DEFAULT_ORIGIN: u16 = 0x0800;
+
+/// How many passes may run before symbol values are expected to have settled.
+const MAX_PASSES: usize = 16;
+
+/// Settings that change how a program is assembled.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Directories searched for `.include` and `.incbin` files, after the including file's own directory.
+    pub include_paths: Vec<PathBuf>,
+    /// Symbols defined before the first line is assembled, as with `-D NAME=value`.
+    pub defines: BTreeMap<String, i64>,
+    /// The syntax the program is written in.
+    pub dialect: Dialect,
+    /// The processor the program is for, which decides the instructions allowed.
+    pub variant: Variant,
+}
+
+/// An assembled program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    /// The address of the first byte.
+    pub origin: u16,
+    /// The assembled bytes, with any gaps between `.org` sections filled with zeros.
+    pub bytes: Vec<u8>,
+    /// The final value of every label and constant.
+    pub symbols: BTreeMap<String, i64>,
+    /// What was assembled from each line, for `listing::render`.
+    pub listing: Vec<ListingLine>,
+}
 
 /// Assemble a program string into a vector of bytes.
 ///
@@ -15,92 +70,246 @@ use log::info;
 /// # Examples
 ///
 /// ```
+/// # use apple_ie::assembler;
 /// let program = "LDA #$01\nBRK";
 /// let bytes = assembler::assemble(program).unwrap();
 /// assert_eq!(bytes, vec![0xA9, 0x01, 0x00]);
 /// ```
 pub fn assemble(program: &str) -> Result<Vec<u8>> {
-    // Split the program string into lines and trim whitespace
-    let lines = program.lines().map(|x| x.trim()).collect::<Vec<&str>>();
-    let mut bytes = vec![];
+    Ok(assemble_source("<input>", program)?.bytes)
+}
 
-    // Iterate over each line of the program
-    for line in lines {
-        let line = line.trim();
-        if !line.is_empty() {
-            // Parse the line into an operation and its arguments
-            let (operation, mut args) = parse(line)?;
-            bytes.push(operation);
-            bytes.append(&mut args);
-        }
-    }
+/// Assemble a program, naming it `file` in any diagnostics.
+///
+/// Every line is assembled even after an error is found, so the returned
+/// `Diagnostics` describes all problems in the program at once.
+///
+/// # Arguments
+///
+/// * `file` - The name of the program, usually its path.
+/// * `program` - A string containing the program to assemble.
+///
+/// # Returns
+///
+/// A `Result` containing the assembled program, or a `Diagnostics` error.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assembler::{self, diagnostic::Diagnostics};
+/// let error = assembler::assemble_source("main.s", "LDZ #$01").unwrap_err();
+/// let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+/// assert_eq!(diagnostics.iter().next().unwrap().line, 1);
+/// ```
+pub fn assemble_source(file: &str, program: &str) -> Result<Assembly> {
+    assemble_with(SourceFile::new(file, program), &Options::default())
+}
 
-    // Log the assembled program as a vector of bytes
-    info!("The following is in bytes: {:#04X?}", bytes);
+/// Read and assemble a program from disk.
+///
+/// # Arguments
+///
+/// * `path` - The path of the program.
+/// * `options` - How to assemble the program.
+///
+/// # Returns
+///
+/// A `Result` containing the assembled program, or a `Diagnostics` error.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use apple_ie::assembler::{self, Options};
+/// # use std::path::{Path, PathBuf};
+/// let options = Options {
+///     include_paths: vec![PathBuf::from("lib")],
+///     ..Options::default()
+/// };
+/// let assembly = assembler::assemble_file(Path::new("main.s"), &options).unwrap();
+/// ```
+pub fn assemble_file(path: &Path, options: &Options) -> Result<Assembly> {
+    let file = SourceFile::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    assemble_with(file, options)
+}
+
+/// Parse a symbol definition given on the command line.
+///
+/// The value may be any constant expression, and defaults to 1 when left out.
+///
+/// # Arguments
+///
+/// * `text` - The definition, written `NAME=value` or `NAME`.
+///
+/// # Returns
+///
+/// A `Result` containing the name and value of the symbol, or a description of the problem.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assembler;
+/// assert_eq!(assembler::parse_define("RAM=$1000"), Ok(("RAM".to_string(), 0x1000)));
+/// assert_eq!(assembler::parse_define("DEBUG"), Ok(("DEBUG".to_string(), 1)));
+/// ```
+pub fn parse_define(text: &str) -> Result<(String, i64), String> {
+    let (name, value) = text.split_once('=').unwrap_or((text, "1"));
+
+    let name = match tokenize(name, Location::default()).as_deref() {
+        Ok(
+            [lexer::Token {
+                kind: TokenKind::Ident(name),
+                ..
+            }],
+        ) if !name.starts_with(['.', '@']) => name.clone(),
+        _ => return Err(format!("`{}` is not a valid symbol name", name.trim())),
+    };
 
-    Ok(bytes)
+    evaluate(value, |_| None)
+        .map(|value| (name, value))
+        .map_err(|error| format!("invalid value for `{}`: {}", text, error))
 }
 
-/// Parse a line of assembly code into an operation and its arguments.
+/// Evaluate an expression written outside a program, such as on the command line.
 ///
 /// # Arguments
 ///
-/// * `line` - A string containing the line of assembly code to parse.
+/// * `text` - The expression, in the same syntax as operands.
+/// * `symbol` - Looks up the value of a symbol, or gives `None` if it is unknown.
 ///
 /// # Returns
 ///
-/// A `Result` containing a tuple of the operation code and its arguments.
+/// A `Result` containing the value, or a description of the problem.
 ///
 /// # Examples
 ///
 /// ```
-/// let line = "LDA #$01";
-/// let (operation, args) = assembler::parse(line).unwrap();
-/// assert_eq!(operation, 0xA9);
-/// assert_eq!(args, vec![0x01]);
+/// # use apple_ie::assembler;
+/// assert_eq!(assembler::evaluate("$10 * 2", |_| None), Ok(0x20));
+/// assert_eq!(assembler::evaluate("start+1", |_| Some(0x0800)), Ok(0x0801));
 /// ```
-fn parse(line: &str) -> Result<(u8, Vec<u8>)> {
-    // Split the line into the operation name and its arguments
-    let split: Vec<&str> = line.splitn(2, ' ').collect();
-
-    let mut args = vec![];
-    let mut addressing = AddressingMode::Implied;
-    let operation_name = split[0];
-
-    // If the line has arguments, parse them based on their addressing mode
-    if split.len() != 1 {
-        match split[1] {
-            arg if arg.starts_with("#$") => {
-                addressing = AddressingMode::Immediate;
-                let arg = arg.trim_start_matches("#$");
-                args.push(u8::from_str_radix(arg, 16)?);
-            }
-            arg if arg.starts_with('$') => {
-                let arg = arg.trim_start_matches('$');
-
-                // If the argument is two characters, use zero page addressing
-                // If the argument is four characters, use absolute addressing
-                match arg.len() {
-                    2 => {
-                        addressing = AddressingMode::ZeroPage;
-                        args.push(u8::from_str_radix(arg, 16)?);
-                    }
-                    4 => {
-                        addressing = AddressingMode::Absolute;
-                        let arg = u16::from_str_radix(arg, 16)?;
-                        args.push(arg as u8);
-                        args.push((arg >> 8) as u8);
-                    }
-                    _ => bail!("Can't parse argument"),
-                }
-            }
-            _ => bail!("Unknown operand."),
-        };
+pub fn evaluate(text: &str, symbol: impl Fn(&str) -> Option<i64>) -> Result<i64, String> {
+    let lookup = |name: &str, span| {
+        symbol(name)
+            .ok_or_else(|| SourceError::new(span, format!("`{}` is not a known symbol", name)))
+    };
+
+    parse(text)?
+        .evaluate(0, &lookup)
+        .map_err(|error| error.message)
+}
+
+/// Parse an expression written outside a program, to evaluate many times over.
+///
+/// # Returns
+///
+/// A `Result` containing the expression, or a description of the problem.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assembler;
+/// let expr = assembler::parse("X * 2").unwrap();
+/// assert_eq!(expr.evaluate(0, &|_, _| Ok(3)), Ok(6));
+/// ```
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let end = Span::new(Location::default(), text.len(), text.len() + 1);
+    tokenize(text, Location::default())
+        .and_then(|tokens| parse_expression(&tokens, end))
+        .map_err(|error| error.message)
+}
+
+/// Parse a 16-bit address given on the command line, such as `$F800`.
+pub fn parse_address(text: &str) -> Result<u16, String> {
+    let value = evaluate(text, |_| None)?;
+    u16::try_from(value).map_err(|_| format!("{} is not a 16-bit address", text))
+}
+
+/// Assemble a program made of a root file and anything it includes.
+pub fn assemble_with(file: SourceFile, options: &Options) -> Result<Assembly> {
+    let mut sources = Sources::new();
+    let root = sources.add(file);
+
+    // Keep assembling until forward references and operand sizes stop changing
+    let mut previous = Layout::default();
+    for _ in 0..MAX_PASSES {
+        let mut pass = Pass::new(&mut sources, options, &previous, false, DEFAULT_ORIGIN);
+        pass.run(root);
+
+        let layout = pass.layout();
+        if layout == previous {
+            break;
+        }
+        previous = layout;
+    }
+
+    let mut pass = Pass::new(&mut sources, options, &previous, true, DEFAULT_ORIGIN);
+    pass.run(root);
+
+    let settled = pass.layout() == previous;
+    let mut errors = std::mem::take(&mut pass.errors);
+    if errors.is_empty() && !settled {
+        errors = pass.unsettled(MAX_PASSES);
     }
+    let chunks = std::mem::take(&mut pass.chunks);
+    let listing = std::mem::take(&mut pass.listing);
+    let symbols = pass.values();
+
+    if !errors.is_empty() {
+        let diagnostics = errors
+            .iter()
+            .map(|error| diagnostic(&sources, error))
+            .collect();
+        return Err(Diagnostics(diagnostics).into());
+    }
+
+    // Only operand sizes can still be changing here
+    if !settled {
+        bail!("Operand sizes did not settle after {} passes", MAX_PASSES);
+    }
+
+    let origin = chunks
+        .iter()
+        .map(|(start, _)| *start)
+        .min()
+        .unwrap_or(DEFAULT_ORIGIN);
 
-    // Look up the instruction based on the operation name and addressing mode
-    match get_instruction(None, Some(operation_name), Some(addressing)) {
-        Some(opcode) => Ok((opcode.opcode_value, args)),
-        None => bail!("Instruction cannot be found."),
+    let mut bytes = vec![];
+    for (start, chunk) in &chunks {
+        let offset = usize::from(start - origin);
+        if bytes.len() < offset + chunk.len() {
+            bytes.resize(offset + chunk.len(), 0);
+        }
+        bytes[offset..offset + chunk.len()].copy_from_slice(chunk);
     }
-}
\ No newline at end of file
+
+    // Log the assembled program as a vector of bytes
+    info!("The following is in bytes: {:#04X?}", bytes);
+
+    Ok(Assembly {
+        origin,
+        bytes,
+        symbols,
+        listing,
+    })
+}
+
+/// Render an error against the source it points into.
+fn diagnostic(sources: &Sources, error: &SourceError) -> Diagnostic {
+    let at = |span: Span, message: &str| {
+        let file = &sources.files[span.location.file];
+        let line = file
+            .lines
+            .get(span.location.line)
+            .map_or("", String::as_str);
+        Diagnostic::new(&file.name, line, span, message)
+    };
+
+    let mut diagnostic = at(error.span, &error.message);
+    diagnostic.notes = error
+        .notes
+        .iter()
+        .map(|(span, message)| at(*span, message))
+        .collect();
+
+    diagnostic
+}
@@ -0,0 +1,589 @@
+use super::diagnostic::{Location, SourceError, Span};
+use super::expression::{expression, parse_expression, Expr};
+use super::lexer::{Token, TokenKind};
+
+/// A tokenized line of source, ready to be parsed into a statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub location: Location,
+    pub tokens: Vec<Token>,
+    /// A span just past the end of the line, used for "expected ..." errors.
+    pub end: Span,
+}
+
+impl Line {
+    pub fn new(location: Location, tokens: Vec<Token>, length: usize) -> Self {
+        Line {
+            location,
+            tokens,
+            end: Span::new(location, length, length + 1),
+        }
+    }
+}
+
+/// The syntactic shape of an instruction operand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    Accumulator,
+    Immediate(Expr),
+    Direct(Expr),
+    IndexedX(Expr),
+    IndexedY(Expr),
+    Indirect(Expr),
+    IndexedIndirect(Expr),
+    IndirectIndexed(Expr),
+}
+
+impl Operand {
+    /// The expression of the operand, if it has one.
+    pub fn value(&self) -> Option<&Expr> {
+        match self {
+            Operand::None | Operand::Accumulator => None,
+            Operand::Immediate(value)
+            | Operand::Direct(value)
+            | Operand::IndexedX(value)
+            | Operand::IndexedY(value)
+            | Operand::Indirect(value)
+            | Operand::IndexedIndirect(value)
+            | Operand::IndirectIndexed(value) => Some(value),
+        }
+    }
+}
+
+/// The width of the address in a direct or indexed operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    /// A one-byte address in the zero page, forced with a `z:` prefix.
+    ZeroPage,
+    /// A two-byte address, forced with an `a:` prefix.
+    Absolute,
+}
+
+/// The assembler directives, written with a leading `.`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive {
+    Org,
+    Byte,
+    Word,
+    Fill,
+    Macro,
+    EndMacro,
+    Include,
+    Incbin,
+    If,
+    IfDef,
+    IfNDef,
+    ElseIf,
+    Else,
+    EndIf,
+}
+
+impl Directive {
+    /// Look up a directive by name, ignoring case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::assemble;
+    /// assert_eq!(assemble(".BYTE 1").unwrap(), [0x01]);
+    /// assert!(assemble(".nope 1").is_err());
+    /// ```
+    pub fn from_name(name: &str) -> Option<Directive> {
+        match name.to_lowercase().as_str() {
+            ".org" => Some(Directive::Org),
+            ".byte" => Some(Directive::Byte),
+            ".word" => Some(Directive::Word),
+            ".fill" => Some(Directive::Fill),
+            ".macro" => Some(Directive::Macro),
+            ".endm" => Some(Directive::EndMacro),
+            ".include" => Some(Directive::Include),
+            ".incbin" => Some(Directive::Incbin),
+            ".if" => Some(Directive::If),
+            ".ifdef" => Some(Directive::IfDef),
+            ".ifndef" => Some(Directive::IfNDef),
+            ".elseif" => Some(Directive::ElseIf),
+            ".else" => Some(Directive::Else),
+            ".endif" => Some(Directive::EndIf),
+            _ => None,
+        }
+    }
+
+    /// Whether the directive opens, continues or closes a conditional block.
+    pub fn is_conditional(self) -> bool {
+        matches!(
+            self,
+            Directive::If
+                | Directive::IfDef
+                | Directive::IfNDef
+                | Directive::ElseIf
+                | Directive::Else
+                | Directive::EndIf
+        )
+    }
+
+    /// Find the directive a line starts with, skipping any label.
+    pub fn of_line(line: &Line) -> Option<Directive> {
+        let tokens = match named_label(&line.tokens) {
+            Some((_, rest)) => rest,
+            None => &line.tokens,
+        };
+
+        tokens
+            .first()
+            .and_then(|token| token.ident())
+            .and_then(Directive::from_name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatementKind {
+    Empty,
+    /// `NAME = expression`
+    Assignment {
+        name: Token,
+        value: Expr,
+    },
+    /// `* = expression`
+    Org(Expr),
+    /// A directive such as `.byte`, with its raw argument tokens.
+    Directive {
+        directive: Directive,
+        name: Token,
+        args: Vec<Token>,
+    },
+    /// An instruction or macro call, with its raw argument tokens.
+    Instruction {
+        mnemonic: Token,
+        args: Vec<Token>,
+    },
+}
+
+/// A single line of assembly, optionally labelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub label: Option<Token>,
+    pub kind: StatementKind,
+}
+
+/// Parse a tokenized line into a statement.
+///
+/// # Arguments
+///
+/// * `line` - The tokenized line.
+///
+/// # Returns
+///
+/// A `Result` containing the statement, or an error pointing at the part of the line that could not be parsed.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assemble;
+/// let bytes = assemble("loop: LDA #$01\n      JMP loop").unwrap();
+/// assert_eq!(bytes, [0xA9, 0x01, 0x4C, 0x00, 0x08]);
+/// ```
+pub fn parse_statement(line: &Line) -> Result<Statement, SourceError> {
+    let mut tokens: &[Token] = &line.tokens;
+    let mut label = None;
+
+    match tokens {
+        [name @ Token {
+            kind: TokenKind::Ident(_),
+            ..
+        }, Token {
+            kind: TokenKind::Equals,
+            ..
+        }, rest @ ..] => {
+            let value = parse_expression(rest, line.end)?;
+            return Ok(Statement {
+                label: None,
+                kind: StatementKind::Assignment {
+                    name: name.clone(),
+                    value,
+                },
+            });
+        }
+        [Token {
+            kind: TokenKind::Star,
+            ..
+        }, Token {
+            kind: TokenKind::Equals,
+            ..
+        }, rest @ ..] => {
+            let value = parse_expression(rest, line.end)?;
+            return Ok(Statement {
+                label: None,
+                kind: StatementKind::Org(value),
+            });
+        }
+        _ => {
+            if let Some((name, rest)) = named_label(tokens) {
+                label = Some(name.clone());
+                tokens = rest;
+            } else if let Some((name, rest)) = anonymous_label(tokens) {
+                label = Some(name);
+                tokens = rest;
+            }
+        }
+    }
+
+    let kind = match tokens {
+        [] => StatementKind::Empty,
+        [name, args @ ..] => match name.ident() {
+            Some(ident) if ident.starts_with('.') => match Directive::from_name(ident) {
+                Some(directive) => StatementKind::Directive {
+                    directive,
+                    name: name.clone(),
+                    args: args.to_vec(),
+                },
+                None => {
+                    return Err(SourceError::new(
+                        name.span,
+                        format!("unknown directive `{}`", ident),
+                    ))
+                }
+            },
+            Some(_) => StatementKind::Instruction {
+                mnemonic: name.clone(),
+                args: args.to_vec(),
+            },
+            None => {
+                return Err(SourceError::new(
+                    name.span,
+                    format!("expected an instruction, found `{}`", name.kind),
+                ))
+            }
+        },
+    };
+
+    Ok(Statement { label, kind })
+}
+
+/// Split a label written `name:` from the start of a line.
+///
+/// A colon written straight before a sign is a reference to an unnamed label
+/// instead, so `BNE :-` is a branch rather than a label called `BNE`.
+pub fn named_label(tokens: &[Token]) -> Option<(&Token, &[Token])> {
+    match tokens {
+        [name @ Token {
+            kind: TokenKind::Ident(_),
+            ..
+        }, colon @ Token {
+            kind: TokenKind::Colon,
+            ..
+        }, rest @ ..] => match rest.first() {
+            Some(sign)
+                if matches!(sign.kind, TokenKind::Plus | TokenKind::Minus)
+                    && sign.span.start == colon.span.end =>
+            {
+                None
+            }
+            _ => Some((name, rest)),
+        },
+        _ => None,
+    }
+}
+
+/// Split an anonymous label such as `-` or `++` from the start of a line.
+///
+/// The label is a run of `+` or `-` signs written without spaces, optionally
+/// followed by a colon, and is returned as a single identifier token. A lone
+/// colon is an unnamed label in the style of ca65, named `:`.
+fn anonymous_label(tokens: &[Token]) -> Option<(Token, &[Token])> {
+    let first = tokens.first()?;
+    if first.kind == TokenKind::Colon {
+        let name = Token {
+            kind: TokenKind::Ident(":".to_string()),
+            span: first.span,
+        };
+        return Some((name, &tokens[1..]));
+    }
+
+    if !matches!(first.kind, TokenKind::Plus | TokenKind::Minus) {
+        return None;
+    }
+
+    let length = sign_run(tokens);
+    let span = first.span.to(tokens[length - 1].span);
+    let name = Token {
+        kind: TokenKind::Ident(first.kind.to_string().repeat(length)),
+        span,
+    };
+
+    match &tokens[length..] {
+        [Token {
+            kind: TokenKind::Colon,
+            ..
+        }, rest @ ..] => Some((name, rest)),
+        rest @ ([]
+        | [Token {
+            kind: TokenKind::Ident(_),
+            ..
+        }, ..]) => Some((name, rest)),
+        _ => None,
+    }
+}
+
+/// Count the `+` or `-` signs at the start of the tokens that are written without spaces.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assemble;
+/// let bytes = assemble("--  DEX\n    BNE --").unwrap();
+/// assert_eq!(bytes, [0xCA, 0xD0, 0xFD]);
+/// ```
+pub fn sign_run(tokens: &[Token]) -> usize {
+    let Some(first) = tokens.first() else {
+        return 0;
+    };
+
+    1 + tokens
+        .windows(2)
+        .take_while(|pair| pair[1].kind == first.kind && pair[1].span.start == pair[0].span.end)
+        .count()
+}
+
+/// Parse the argument tokens of an instruction into an operand.
+///
+/// A direct or indexed operand may start with `z:` or `a:`, as in ca65, to
+/// force a zero page or absolute address instead of letting the assembler
+/// choose from its value.
+///
+/// # Arguments
+///
+/// * `tokens` - The tokens following the mnemonic.
+/// * `end` - A span just past the end of the line.
+///
+/// # Returns
+///
+/// A `Result` containing the operand, and the size forced by a prefix, if any.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assemble;
+/// assert_eq!(assemble("LDA a:$10,X").unwrap(), [0xBD, 0x10, 0x00]);
+/// ```
+pub fn parse_operand(tokens: &[Token], end: Span) -> Result<(Operand, Option<Size>), SourceError> {
+    if let Some((size, rest)) = size_prefix(tokens) {
+        let mut cursor = Cursor::new(rest, end);
+        let operand = parse_indexed(&mut cursor)?;
+        cursor.expect_end()?;
+        return Ok((operand, Some(size)));
+    }
+
+    let mut cursor = Cursor::new(tokens, end);
+
+    let operand = match cursor.peek() {
+        None => Operand::None,
+        Some(TokenKind::Ident(name)) if name.eq_ignore_ascii_case("A") && tokens.len() == 1 => {
+            cursor.advance();
+            Operand::Accumulator
+        }
+        Some(TokenKind::Hash) => {
+            cursor.advance();
+            Operand::Immediate(expression(&mut cursor)?)
+        }
+        Some(TokenKind::LParen) => match parse_indirect(&mut cursor)? {
+            Some(operand) => operand,
+            // A parenthesised expression such as `(BASE+1)*2`, so start over
+            None => {
+                cursor = Cursor::new(tokens, end);
+                parse_indexed(&mut cursor)?
+            }
+        },
+        Some(_) => parse_indexed(&mut cursor)?,
+    };
+
+    cursor.expect_end()?;
+    Ok((operand, None))
+}
+
+/// Split a `z:` or `a:` prefix, written without spaces, from the start of an operand.
+fn size_prefix(tokens: &[Token]) -> Option<(Size, &[Token])> {
+    match tokens {
+        [prefix @ Token {
+            kind: TokenKind::Ident(name),
+            ..
+        }, colon @ Token {
+            kind: TokenKind::Colon,
+            ..
+        }, rest @ ..]
+            if prefix.span.end == colon.span.start =>
+        {
+            let size = match name.to_lowercase().as_str() {
+                "z" => Size::ZeroPage,
+                "a" => Size::Absolute,
+                _ => return None,
+            };
+            Some((size, rest))
+        }
+        _ => None,
+    }
+}
+
+fn parse_indirect(cursor: &mut Cursor) -> Result<Option<Operand>, SourceError> {
+    cursor.advance();
+    let value = expression(cursor)?;
+
+    if cursor.eat(&TokenKind::Comma) {
+        cursor.expect_register("X")?;
+        cursor.expect(&TokenKind::RParen, "`)`")?;
+        return Ok(Some(Operand::IndexedIndirect(value)));
+    }
+
+    cursor.expect(&TokenKind::RParen, "`)`")?;
+    match cursor.peek() {
+        None => Ok(Some(Operand::Indirect(value))),
+        Some(TokenKind::Comma) => {
+            cursor.advance();
+            cursor.expect_register("Y")?;
+            Ok(Some(Operand::IndirectIndexed(value)))
+        }
+        Some(_) => Ok(None),
+    }
+}
+
+fn parse_indexed(cursor: &mut Cursor) -> Result<Operand, SourceError> {
+    let value = expression(cursor)?;
+
+    if !cursor.eat(&TokenKind::Comma) {
+        return Ok(Operand::Direct(value));
+    }
+
+    let span = cursor.peek_span();
+    match cursor.advance().and_then(|token| token.ident()) {
+        Some(name) if name.eq_ignore_ascii_case("X") => Ok(Operand::IndexedX(value)),
+        Some(name) if name.eq_ignore_ascii_case("Y") => Ok(Operand::IndexedY(value)),
+        _ => Err(SourceError::new(span, "expected `X` or `Y`")),
+    }
+}
+
+/// Split argument tokens on commas that are not inside parentheses.
+///
+/// # Arguments
+///
+/// * `tokens` - The tokens to split.
+///
+/// # Returns
+///
+/// A vector of arguments, empty when there are no tokens at all.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::assemble;
+/// let bytes = assemble(".macro pair first, second\n.byte first, second\n.endm\npair (1 + 2), 3").unwrap();
+/// assert_eq!(bytes, [0x03, 0x03]);
+/// ```
+pub fn split_arguments(tokens: &[Token]) -> Vec<&[Token]> {
+    if tokens.is_empty() {
+        return vec![];
+    }
+
+    let mut arguments = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::LParen => depth += 1,
+            TokenKind::RParen => depth -= 1,
+            TokenKind::Comma if depth == 0 => {
+                arguments.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    arguments.push(&tokens[start..]);
+    arguments
+}
+
+/// Walks a slice of tokens, producing errors that point at the right place.
+pub struct Cursor<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    end: Span,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(tokens: &'a [Token], end: Span) -> Self {
+        Cursor {
+            tokens,
+            position: 0,
+            end,
+        }
+    }
+
+    pub fn peek(&self) -> Option<&'a TokenKind> {
+        self.tokens.get(self.position).map(|token| &token.kind)
+    }
+
+    /// The span of the next token, or of the end of the line.
+    pub fn peek_span(&self) -> Span {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |token| token.span)
+    }
+
+    /// The tokens that have not been consumed yet.
+    pub fn rest(&self) -> &'a [Token] {
+        &self.tokens[self.position..]
+    }
+
+    pub fn advance(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position);
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    pub fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek() == Some(kind) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn expect(&mut self, kind: &TokenKind, description: &str) -> Result<(), SourceError> {
+        if self.eat(kind) {
+            return Ok(());
+        }
+
+        Err(SourceError::new(
+            self.peek_span(),
+            format!("expected {}", description),
+        ))
+    }
+
+    pub fn expect_register(&mut self, register: &str) -> Result<(), SourceError> {
+        match self.peek() {
+            Some(TokenKind::Ident(name)) if name.eq_ignore_ascii_case(register) => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(SourceError::new(
+                self.peek_span(),
+                format!("expected `{}`", register),
+            )),
+        }
+    }
+
+    /// Fail unless every token has been consumed.
+    pub fn expect_end(&self) -> Result<(), SourceError> {
+        match self.tokens.get(self.position) {
+            None => Ok(()),
+            Some(token) => Err(SourceError::new(
+                token.span,
+                format!("unexpected `{}`", token.kind),
+            )),
+        }
+    }
+}
@@ -0,0 +1,1112 @@
+use super::diagnostic::{Location, SourceError, Span};
+use super::dialect::translate;
+use super::encoder::select;
+use super::expression::{parse_expression, Expr};
+use super::lexer::{tokenize, Token, TokenKind};
+use super::listing::ListingLine;
+use super::macros::Macro;
+use super::parser::{
+    parse_operand, parse_statement, split_arguments, Directive, Line, Operand, Size, StatementKind,
+};
+use super::source::Sources;
+use super::Options;
+use crate::cpu::operations::AddressingMode;
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// How deeply macros and includes may nest before assembly gives up.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// A symbol defined during a pass, with where it was defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbol {
+    pub value: i64,
+    /// The definition in source, or `None` for symbols defined on the command line.
+    pub span: Option<Span>,
+}
+
+/// What a pass found out about the program, which the next pass builds on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layout {
+    /// The value of every symbol.
+    pub symbols: BTreeMap<String, i64>,
+    /// The instructions, numbered in the order they were assembled, whose
+    /// operand has needed an absolute address.
+    pub wide: BTreeSet<usize>,
+}
+
+/// One open `.if` block.
+#[derive(Debug, Clone, Copy)]
+struct Condition {
+    /// Whether the code around the block is being assembled.
+    outer: bool,
+    /// Whether the current branch is being assembled.
+    active: bool,
+    /// Whether any branch so far has been assembled.
+    taken: bool,
+    /// Whether the `.else` branch has been reached.
+    otherwise: bool,
+    /// The opening directive.
+    span: Span,
+}
+
+/// Something currently being expanded, reported as notes on errors inside it.
+#[derive(Debug, Clone)]
+enum Frame {
+    Macro {
+        name: String,
+        call: Span,
+        definition: Span,
+    },
+    Include {
+        file: usize,
+        site: Span,
+    },
+}
+
+/// A single walk over the whole program.
+///
+/// Every pass assembles everything, using the symbol values from the previous
+/// pass for forward references. Passes are repeated until the values settle,
+/// and only the errors of the final, strict pass are reported.
+///
+/// Operands known to fit in a byte get the zero page form. Once an instruction
+/// has needed an absolute operand it keeps it in every later pass, so code
+/// only ever grows and the passes are sure to settle.
+pub struct Pass<'a> {
+    sources: &'a mut Sources,
+    options: &'a Options,
+    previous: &'a Layout,
+    strict: bool,
+    pub symbols: BTreeMap<String, Symbol>,
+    pub chunks: Vec<(u16, Vec<u8>)>,
+    pub errors: Vec<SourceError>,
+    pub listing: Vec<ListingLine>,
+    macros: HashMap<String, Rc<Macro>>,
+    address: u32,
+    frames: Vec<Frame>,
+    conditions: Vec<Condition>,
+    /// The listing entry of the line being assembled.
+    current: Option<usize>,
+    /// The last global label, which local labels belong to.
+    scope: String,
+    /// How many of each anonymous label, such as `-` or `++`, have been defined.
+    anonymous: HashMap<String, usize>,
+    /// The instructions that must keep an absolute operand.
+    wide: BTreeSet<usize>,
+    /// How many instructions have been assembled.
+    instructions: usize,
+    expansions: usize,
+    root: usize,
+}
+
+impl<'a> Pass<'a> {
+    /// Create a new pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - The source files of the program, which includes are added to.
+    /// * `options` - How to assemble the program.
+    /// * `previous` - What the previous pass found out.
+    /// * `strict` - Whether undefined symbols are errors rather than placeholders.
+    /// * `origin` - The address assembly starts at.
+    pub fn new(
+        sources: &'a mut Sources,
+        options: &'a Options,
+        previous: &'a Layout,
+        strict: bool,
+        origin: u16,
+    ) -> Self {
+        let symbols = options
+            .defines
+            .iter()
+            .map(|(name, value)| {
+                let symbol = Symbol {
+                    value: *value,
+                    span: None,
+                };
+                (name.clone(), symbol)
+            })
+            .collect();
+
+        Pass {
+            sources,
+            options,
+            previous,
+            strict,
+            symbols,
+            chunks: vec![],
+            errors: vec![],
+            listing: vec![],
+            macros: HashMap::new(),
+            address: u32::from(origin),
+            frames: vec![],
+            conditions: vec![],
+            current: None,
+            scope: String::new(),
+            anonymous: HashMap::new(),
+            wide: previous.wide.clone(),
+            instructions: 0,
+            expansions: 0,
+            root: 0,
+        }
+    }
+
+    /// Assemble every line of a file.
+    pub fn run(&mut self, file: usize) {
+        self.root = file;
+        let lines = self.lex(file);
+        self.assemble_lines(&lines);
+    }
+
+    /// The value of every symbol defined in this pass.
+    pub fn values(&self) -> BTreeMap<String, i64> {
+        self.symbols
+            .iter()
+            .map(|(name, symbol)| (name.clone(), symbol.value))
+            .collect()
+    }
+
+    /// The symbols whose value differs from the one in the previous pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `passes` - How many passes have run, for the message.
+    ///
+    /// # Returns
+    ///
+    /// An error at the definition of each symbol still changing.
+    pub fn unsettled(&self, passes: usize) -> Vec<SourceError> {
+        self.symbols
+            .iter()
+            .filter_map(|(name, symbol)| {
+                let previous = *self.previous.symbols.get(name)?;
+                let span = symbol.span?;
+                (previous != symbol.value).then(|| {
+                    SourceError::new(
+                        span,
+                        format!(
+                            "the value of `{}` did not settle after {} passes, going from {} to {}",
+                            name, passes, previous, symbol.value
+                        ),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// What this pass found out, for the next pass.
+    pub fn layout(&self) -> Layout {
+        Layout {
+            symbols: self.values(),
+            wide: self.wide.clone(),
+        }
+    }
+
+    fn lex(&mut self, file: usize) -> Vec<Line> {
+        let lines = self.sources.files[file].lines.clone();
+
+        let lines = lines
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let location = Location { file, line: index };
+                let tokens = tokenize(text, location).unwrap_or_else(|error| {
+                    self.error(error);
+                    vec![]
+                });
+                Line::new(location, tokens, text.len())
+            })
+            .collect();
+
+        let (lines, errors) = translate(lines, self.options.dialect, self.options.variant);
+        for error in errors {
+            self.error(error);
+        }
+
+        lines
+    }
+
+    fn assemble_lines(&mut self, lines: &[Line]) {
+        // Blocks opened outside these lines cannot be continued or closed by them
+        let depth = self.conditions.len();
+        let outer = self.current;
+        let mut index = 0;
+
+        while index < lines.len() {
+            let line = &lines[index];
+            index += 1;
+
+            // Conditionals are followed even in skipped code, to keep nesting straight
+            if Directive::of_line(line).is_some_and(Directive::is_conditional) {
+                self.record(line, false);
+                self.conditional(line, depth);
+                continue;
+            }
+
+            if !self.assembling() {
+                self.record(line, false);
+                continue;
+            }
+
+            self.record(line, !line.tokens.is_empty());
+
+            let statement = match parse_statement(line) {
+                Ok(statement) => statement,
+                Err(error) => {
+                    self.error(error);
+                    continue;
+                }
+            };
+
+            if let Some(label) = &statement.label {
+                self.label(label);
+            }
+
+            match statement.kind {
+                StatementKind::Empty => (),
+                StatementKind::Assignment { name, value } => {
+                    let value = self.evaluate_or_zero(&value);
+                    self.define(&name, value);
+                }
+                StatementKind::Org(value) => self.org(&value),
+                StatementKind::Directive {
+                    directive: Directive::Macro,
+                    name,
+                    args,
+                } => {
+                    let (body, consumed, closed) = macro_body(&lines[index..]);
+                    for line in &lines[index..index + consumed] {
+                        self.record(line, false);
+                    }
+                    index += consumed;
+
+                    if !closed {
+                        self.error(SourceError::new(
+                            name.span,
+                            "`.macro` has no matching `.endm`",
+                        ));
+                    }
+
+                    match Macro::new(&name, &args, body) {
+                        Ok(definition) => self.define_macro(definition),
+                        Err(error) => self.error(error),
+                    }
+                }
+                StatementKind::Directive {
+                    directive,
+                    name,
+                    args,
+                } => self.directive(directive, &name, &args, line),
+                StatementKind::Instruction { mnemonic, args } => {
+                    self.instruction(&mnemonic, &args, line)
+                }
+            }
+        }
+
+        self.current = outer;
+
+        for condition in self.conditions.split_off(depth) {
+            self.error(SourceError::new(
+                condition.span,
+                "`.if` has no matching `.endif`",
+            ));
+        }
+    }
+
+    /// Start the listing entry of a line, which emitted bytes are added to.
+    fn record(&mut self, line: &Line, assembled: bool) {
+        let file = &self.sources.files[line.location.file];
+        let text = file.lines.get(line.location.line).cloned();
+
+        self.current = Some(self.listing.len());
+        self.listing.push(ListingLine {
+            file: file.name.clone(),
+            line: line.location.line + 1,
+            text: text.unwrap_or_default(),
+            address: assembled.then_some(self.address as u16),
+            bytes: vec![],
+            cycles: None,
+            expanded: self
+                .frames
+                .iter()
+                .any(|frame| matches!(frame, Frame::Macro { .. })),
+        });
+    }
+
+    /// Whether the current line is outside every skipped conditional branch.
+    fn assembling(&self) -> bool {
+        self.conditions
+            .last()
+            .is_none_or(|condition| condition.active)
+    }
+
+    /// Handle `.if`, `.ifdef`, `.ifndef`, `.elseif`, `.else` and `.endif`.
+    ///
+    /// Conditions are only evaluated when their branch could be taken, so code
+    /// in skipped branches may refer to symbols that are never defined.
+    fn conditional(&mut self, line: &Line, depth: usize) {
+        let statement = match parse_statement(line) {
+            Ok(statement) => statement,
+            Err(error) => return self.error(error),
+        };
+
+        let (directive, name, args) = match statement.kind {
+            StatementKind::Directive {
+                directive,
+                name,
+                args,
+            } => (directive, name, args),
+            _ => return,
+        };
+
+        let assembling = self.assembling();
+        if let (Some(label), true) = (&statement.label, assembling) {
+            self.label(label);
+        }
+
+        let keyword = name.ident().unwrap_or_default().to_lowercase();
+        if matches!(directive, Directive::Else | Directive::EndIf) {
+            if let Some(extra) = args.first() {
+                self.error(SourceError::new(
+                    extra.span,
+                    format!("`{}` takes no arguments", keyword),
+                ));
+            }
+        }
+
+        if directive == Directive::If
+            || directive == Directive::IfDef
+            || directive == Directive::IfNDef
+        {
+            let taken = assembling && self.condition(directive, &args, line);
+            self.conditions.push(Condition {
+                outer: assembling,
+                active: taken,
+                taken,
+                otherwise: false,
+                span: name.span,
+            });
+            return;
+        }
+
+        let condition = match self.conditions.last() {
+            Some(condition) if self.conditions.len() > depth => *condition,
+            _ => {
+                return self.error(SourceError::new(
+                    name.span,
+                    format!("`{}` without a matching `.if`", keyword),
+                ))
+            }
+        };
+
+        match directive {
+            Directive::EndIf => {
+                self.conditions.pop();
+            }
+            _ if condition.otherwise => self.error(
+                SourceError::new(name.span, format!("`{}` after `.else`", keyword))
+                    .with_note(condition.span, "in this conditional block"),
+            ),
+            _ => {
+                let active = condition.outer
+                    && !condition.taken
+                    && (directive == Directive::Else || self.condition(Directive::If, &args, line));
+
+                if let Some(top) = self.conditions.last_mut() {
+                    top.active = active;
+                    top.taken |= active;
+                    top.otherwise = directive == Directive::Else;
+                }
+            }
+        }
+    }
+
+    /// Decide whether the branch of an `.if`, `.ifdef` or `.ifndef` is taken.
+    fn condition(&mut self, directive: Directive, args: &[Token], line: &Line) -> bool {
+        if directive == Directive::If {
+            return match parse_expression(args, line.end) {
+                Ok(value) => self.evaluate_or_zero(&value) != 0,
+                Err(error) => {
+                    self.error(error);
+                    false
+                }
+            };
+        }
+
+        match args {
+            [token] if token.ident().is_some() => {
+                let name = self.qualify(token.ident().unwrap_or_default());
+                let defined = self.symbols.contains_key(&name);
+                defined == (directive == Directive::IfDef)
+            }
+            _ => {
+                let span = args.first().map_or(line.end, |token| token.span);
+                self.error(SourceError::new(span, "expected a symbol name"));
+                false
+            }
+        }
+    }
+
+    fn directive(&mut self, directive: Directive, name: &Token, args: &[Token], line: &Line) {
+        match directive {
+            Directive::Org => match parse_expression(args, line.end) {
+                Ok(value) => self.org(&value),
+                Err(error) => self.error(error),
+            },
+            Directive::Byte | Directive::Word => {
+                let mut bytes = vec![];
+
+                if args.is_empty() {
+                    return self.error(SourceError::new(line.end, "expected an expression"));
+                }
+
+                for arg in split_arguments(args) {
+                    if let [Token {
+                        kind: TokenKind::Str(text),
+                        span,
+                    }] = arg
+                    {
+                        if directive == Directive::Word {
+                            self.error(SourceError::new(*span, "`.word` does not take strings"));
+                        }
+                        bytes.extend(text.bytes());
+                        continue;
+                    }
+
+                    let value = match parse_expression(arg, line.end) {
+                        Ok(value) => value,
+                        Err(error) => {
+                            self.error(error);
+                            continue;
+                        }
+                    };
+
+                    match directive {
+                        Directive::Byte => bytes.push(self.byte(&value, -128)),
+                        _ => bytes.extend(self.word(&value).to_le_bytes()),
+                    }
+                }
+
+                self.emit(&bytes, name.span);
+            }
+            // Handled as the lines are walked, so they never reach here
+            Directive::Macro
+            | Directive::If
+            | Directive::IfDef
+            | Directive::IfNDef
+            | Directive::ElseIf
+            | Directive::Else
+            | Directive::EndIf => (),
+            Directive::EndMacro => self.error(SourceError::new(
+                name.span,
+                "`.endm` without a matching `.macro`",
+            )),
+            Directive::Include => {
+                if let Some(extra) = split_arguments(args).get(1) {
+                    let span = extra.first().map_or(line.end, |token| token.span);
+                    return self.error(SourceError::new(span, "`.include` takes only a file name"));
+                }
+
+                if let Some((path, site)) = self.find_file(args, line) {
+                    self.include(&path, site);
+                }
+            }
+            Directive::Incbin => self.incbin(name, args, line),
+            Directive::Fill => self.fill(name, args, line),
+        }
+    }
+
+    /// Find the file named by the first argument of `.include` or `.incbin`.
+    fn find_file(&mut self, args: &[Token], line: &Line) -> Option<(PathBuf, Span)> {
+        let (name, span) = match split_arguments(args).first() {
+            Some(
+                [Token {
+                    kind: TokenKind::Str(name),
+                    span,
+                }],
+            ) => (name.clone(), *span),
+            Some([token, ..]) => {
+                self.error(SourceError::new(
+                    token.span,
+                    "expected a file name in quotes",
+                ));
+                return None;
+            }
+            _ => {
+                self.error(SourceError::new(line.end, "expected a file name in quotes"));
+                return None;
+            }
+        };
+
+        let found = self
+            .sources
+            .resolve(&name, line.location.file, &self.options.include_paths);
+
+        match found {
+            Ok(path) => Some((path, span)),
+            Err(searched) => {
+                let searched: Vec<String> = searched
+                    .iter()
+                    .map(|path| format!("`{}`", path.display()))
+                    .collect();
+                self.error(SourceError::new(
+                    span,
+                    format!("cannot find `{}`, tried {}", name, searched.join(", ")),
+                ));
+                None
+            }
+        }
+    }
+
+    fn include(&mut self, path: &Path, site: Span) {
+        let file = match self.sources.load(path) {
+            Ok(file) => file,
+            Err(error) => {
+                return self.error(SourceError::new(
+                    site,
+                    format!("cannot read `{}`: {}", path.display(), error),
+                ))
+            }
+        };
+
+        // The files currently being assembled, outermost first
+        let chain: Vec<usize> = std::iter::once(self.root)
+            .chain(self.frames.iter().filter_map(|frame| match frame {
+                Frame::Include { file, .. } => Some(*file),
+                Frame::Macro { .. } => None,
+            }))
+            .collect();
+
+        if let Some(start) = chain.iter().position(|open| *open == file) {
+            let names: Vec<&str> = chain[start..]
+                .iter()
+                .chain(std::iter::once(&file))
+                .map(|open| self.sources.files[*open].name.as_str())
+                .collect();
+            let message = format!("include cycle: {}", names.join(" -> "));
+            return self.error(SourceError::new(site, message));
+        }
+
+        if self.frames.len() >= MAX_EXPANSION_DEPTH {
+            return self.error(SourceError::new(
+                site,
+                format!("includes nested more than {} deep", MAX_EXPANSION_DEPTH),
+            ));
+        }
+
+        let lines = self.lex(file);
+        self.frames.push(Frame::Include { file, site });
+        self.assemble_lines(&lines);
+        self.frames.pop();
+    }
+
+    fn incbin(&mut self, directive: &Token, args: &[Token], line: &Line) {
+        let (path, site) = match self.find_file(args, line) {
+            Some(found) => found,
+            None => return,
+        };
+
+        // An optional offset into the file, then an optional length
+        let mut bounds = vec![];
+        for arg in split_arguments(args).iter().skip(1) {
+            match parse_expression(arg, line.end) {
+                Ok(value) => bounds.push((self.evaluate_or_zero(&value), value.span())),
+                Err(error) => return self.error(error),
+            }
+        }
+
+        if let Some((_, span)) = bounds.get(2) {
+            return self.error(SourceError::new(
+                *span,
+                "`.incbin` takes a file name, an offset and a length",
+            ));
+        }
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(error) => {
+                return self.error(SourceError::new(
+                    site,
+                    format!("cannot read `{}`: {}", path.display(), error),
+                ))
+            }
+        };
+
+        let size = data.len() as i64;
+        let (offset, offset_span) = bounds.first().copied().unwrap_or((0, site));
+        let (length, length_span) = bounds.get(1).copied().unwrap_or((size - offset, site));
+
+        if !(0..=size).contains(&offset) {
+            return self.error(SourceError::new(
+                offset_span,
+                format!("offset {} is outside the {} byte file", offset, size),
+            ));
+        }
+
+        if length < 0 || offset + length > size {
+            return self.error(SourceError::new(
+                length_span,
+                format!(
+                    "length {} runs past the end of the {} byte file",
+                    length, size
+                ),
+            ));
+        }
+
+        let (start, end) = (offset as usize, (offset + length) as usize);
+        self.emit(&data[start..end], directive.span);
+    }
+
+    /// Emit a number of copies of a byte, zero unless given.
+    fn fill(&mut self, directive: &Token, args: &[Token], line: &Line) {
+        let args = split_arguments(args);
+
+        let (count, value) = match args.as_slice() {
+            [count] => (count, None),
+            [count, value] => (count, Some(value)),
+            _ => {
+                let span = args.get(2).and_then(|arg| arg.first());
+                return self.error(SourceError::new(
+                    span.map_or(line.end, |token| token.span),
+                    "`.fill` takes a count and an optional value",
+                ));
+            }
+        };
+
+        let count = match parse_expression(count, line.end) {
+            Ok(count) => count,
+            Err(error) => return self.error(error),
+        };
+        let size = self.evaluate_or_zero(&count);
+        if !(0..=0x10000).contains(&size) {
+            return self.error(SourceError::new(
+                count.span(),
+                format!("cannot fill {} bytes", size),
+            ));
+        }
+
+        let byte = match value.map(|value| parse_expression(value, line.end)) {
+            Some(Ok(value)) => self.byte(&value, -128),
+            Some(Err(error)) => return self.error(error),
+            None => 0,
+        };
+
+        self.emit(&vec![byte; size as usize], directive.span);
+    }
+
+    fn instruction(&mut self, mnemonic: &Token, args: &[Token], line: &Line) {
+        let name = mnemonic.ident().unwrap_or_default();
+        if let Some(definition) = self.macros.get(name).cloned() {
+            return self.expand(&definition, mnemonic, args);
+        }
+
+        let (operand, forced) = match parse_operand(args, line.end) {
+            Ok(operand) => operand,
+            Err(error) => return self.error(error),
+        };
+
+        let index = self.instructions;
+        self.instructions += 1;
+        let size = forced.unwrap_or_else(|| self.size(&operand, index));
+
+        let instruction_info = match select(
+            mnemonic,
+            &operand,
+            size,
+            forced.is_some(),
+            self.options.variant,
+        ) {
+            Ok(instruction_info) => instruction_info,
+            Err(error) => return self.error(error),
+        };
+
+        if let Some(entry) = self.current {
+            self.listing[entry].cycles =
+                Some((instruction_info.cycles, instruction_info.page_cycle));
+        }
+
+        let mut bytes = vec![instruction_info.opcode_value];
+
+        if let Some(value) = operand.value() {
+            match instruction_info.addressing_mode {
+                AddressingMode::Relative => bytes.push(self.branch(value)),
+                AddressingMode::Absolute
+                | AddressingMode::AbsoluteX
+                | AddressingMode::AbsoluteY
+                | AddressingMode::AbsoluteIndexedIndirect
+                | AddressingMode::Indirect => bytes.extend(self.word(value).to_le_bytes()),
+                AddressingMode::Immediate => bytes.push(self.byte(value, -128)),
+                _ => bytes.push(self.byte(value, 0)),
+            }
+        }
+
+        self.emit(&bytes, mnemonic.span);
+    }
+
+    /// Choose the zero page when the operand is known to fit in a byte, and
+    /// the absolute form when it is unknown, too large, or was in an earlier pass.
+    fn size(&mut self, operand: &Operand, index: usize) -> Size {
+        match operand.value().and_then(|value| self.known(value)) {
+            _ if self.wide.contains(&index) => Size::Absolute,
+            Some(0..=0xFF) => Size::ZeroPage,
+            Some(_) => {
+                self.wide.insert(index);
+                Size::Absolute
+            }
+            None => Size::Absolute,
+        }
+    }
+
+    fn expand(&mut self, definition: &Macro, call: &Token, args: &[Token]) {
+        let args = split_arguments(args);
+
+        if args.len() != definition.params.len() {
+            return self.error(
+                SourceError::new(
+                    call.span,
+                    format!(
+                        "macro `{}` takes {} argument(s) but {} were given",
+                        definition.name,
+                        definition.params.len(),
+                        args.len()
+                    ),
+                )
+                .with_note(
+                    definition.span,
+                    format!("macro `{}` defined here", definition.name),
+                ),
+            );
+        }
+
+        if self.frames.len() >= MAX_EXPANSION_DEPTH {
+            return self.error(SourceError::new(
+                call.span,
+                format!(
+                    "macro expansion nested more than {} deep",
+                    MAX_EXPANSION_DEPTH
+                ),
+            ));
+        }
+
+        self.expansions += 1;
+        let lines = definition.expand(&args, self.expansions);
+
+        self.frames.push(Frame::Macro {
+            name: definition.name.clone(),
+            call: call.span,
+            definition: definition.span,
+        });
+        self.assemble_lines(&lines);
+        self.frames.pop();
+    }
+
+    fn define_macro(&mut self, definition: Macro) {
+        if let Some(existing) = self.macros.get(&definition.name) {
+            let error = SourceError::new(
+                definition.span,
+                format!("macro `{}` is already defined", definition.name),
+            )
+            .with_note(existing.span, "previously defined here");
+            return self.error(error);
+        }
+
+        self.macros
+            .insert(definition.name.clone(), Rc::new(definition));
+    }
+
+    /// Define a label at the current address.
+    ///
+    /// Anonymous labels are numbered in order of definition, and any other label
+    /// outside a macro starts a new scope for the local labels that follow it.
+    fn label(&mut self, token: &Token) {
+        let name = token.ident().unwrap_or_default();
+        let address = i64::from(self.address);
+
+        if is_anonymous(name) {
+            let count = self.anonymous.entry(name.to_string()).or_default();
+            let numbered = Token {
+                kind: TokenKind::Ident(format!("{}#{}", name, count)),
+                span: token.span,
+            };
+            *count += 1;
+            return self.define(&numbered, address);
+        }
+
+        if !is_local(name) && !name.contains('#') {
+            self.scope = name.to_string();
+        }
+
+        self.define(token, address);
+    }
+
+    /// The full name of the symbol a name refers to at this point in the program.
+    ///
+    /// Local labels are prefixed with their scope, `-` refers to the most recent
+    /// `-` label and `+` to the next `+` label.
+    fn qualify(&self, name: &str) -> String {
+        if is_local(name) {
+            return format!("{}{}", self.scope, name);
+        }
+
+        if !is_anonymous(name) {
+            return name.to_string();
+        }
+
+        // Unnamed labels count `:`s in either direction, so `:--` is two back
+        if let Some(signs) = name.strip_prefix(':') {
+            let count = self.anonymous.get(":").copied().unwrap_or_default();
+            return match (signs.starts_with('+'), count.checked_sub(signs.len())) {
+                (true, _) => format!(":#{}", count + signs.len() - 1),
+                (false, Some(index)) => format!(":#{}", index),
+                (false, None) => name.to_string(),
+            };
+        }
+
+        let count = self.anonymous.get(name).copied().unwrap_or_default();
+        match (name.starts_with('+'), count) {
+            (true, _) => format!("{}#{}", name, count),
+            (false, 0) => name.to_string(),
+            (false, _) => format!("{}#{}", name, count - 1),
+        }
+    }
+
+    fn define(&mut self, token: &Token, value: i64) {
+        let written = token.ident().unwrap_or_default();
+        let qualified = self.qualify(written);
+        let name = match is_anonymous(written) {
+            true => written,
+            false => qualified.as_str(),
+        };
+
+        if let Some(existing) = self.symbols.get(name) {
+            let error = match existing.span {
+                Some(span) => SourceError::new(
+                    token.span,
+                    format!("symbol `{}` is already defined", written),
+                )
+                .with_note(span, "previously defined here"),
+                None => SourceError::new(
+                    token.span,
+                    format!(
+                        "symbol `{}` is already defined on the command line",
+                        written
+                    ),
+                ),
+            };
+            return self.error(error);
+        }
+
+        self.symbols.insert(
+            name.to_string(),
+            Symbol {
+                value,
+                span: Some(token.span),
+            },
+        );
+    }
+
+    fn org(&mut self, value: &Expr) {
+        let address = self.evaluate_or_zero(value);
+
+        match u16::try_from(address) {
+            Ok(address) => {
+                self.address = u32::from(address);
+                if let Some(entry) = self.current {
+                    self.listing[entry].address = Some(address);
+                }
+            }
+            Err(_) => self.error(SourceError::new(
+                value.span(),
+                format!("origin {} is outside the address space", address),
+            )),
+        }
+    }
+
+    fn emit(&mut self, bytes: &[u8], span: Span) {
+        if self.address + bytes.len() as u32 > 0x10000 {
+            return self.error(SourceError::new(span, "program runs past $FFFF"));
+        }
+
+        match self.chunks.last_mut() {
+            Some((start, chunk)) if u32::from(*start) + chunk.len() as u32 == self.address => {
+                chunk.extend_from_slice(bytes)
+            }
+            _ => self.chunks.push((self.address as u16, bytes.to_vec())),
+        }
+
+        if let Some(entry) = self.current {
+            let entry = &mut self.listing[entry];
+            if entry.bytes.is_empty() {
+                entry.address = Some(self.address as u16);
+            }
+            entry.bytes.extend_from_slice(bytes);
+        }
+
+        self.address += bytes.len() as u32;
+    }
+
+    fn evaluate(&self, value: &Expr) -> Result<i64, SourceError> {
+        let symbol = |name: &str, span: Span| match self.lookup(name) {
+            Some(value) => Ok(value),
+            None if self.strict => {
+                let message = match (name.starts_with(':'), name.chars().last()) {
+                    (true, Some('-')) => "no `:` label before this line".to_string(),
+                    (true, _) => "no `:` label after this line".to_string(),
+                    (false, Some('-')) => format!("no `{}` label before this line", name),
+                    (false, Some('+')) => format!("no `{}` label after this line", name),
+                    _ => format!("undefined symbol `{}`", name),
+                };
+                Err(SourceError::new(span, message))
+            }
+            None => Ok(0),
+        };
+
+        value.evaluate(i64::from(self.address), &symbol)
+    }
+
+    /// Evaluate an expression, or give `None` while any of its symbols is unknown.
+    fn known(&self, value: &Expr) -> Option<i64> {
+        let symbol = |name: &str, span: Span| {
+            self.lookup(name)
+                .ok_or_else(|| SourceError::new(span, "unknown symbol"))
+        };
+
+        value.evaluate(i64::from(self.address), &symbol).ok()
+    }
+
+    /// The value of a symbol from this pass, or else from the previous one.
+    fn lookup(&self, name: &str) -> Option<i64> {
+        let qualified = self.qualify(name);
+        self.symbols
+            .get(&qualified)
+            .map(|symbol| symbol.value)
+            .or_else(|| self.previous.symbols.get(&qualified).copied())
+    }
+
+    /// Evaluate an expression, reporting any error and carrying on with zero.
+    fn evaluate_or_zero(&mut self, value: &Expr) -> i64 {
+        self.evaluate(value).unwrap_or_else(|error| {
+            self.error(error);
+            0
+        })
+    }
+
+    fn byte(&mut self, value: &Expr, minimum: i64) -> u8 {
+        let result = self.evaluate_or_zero(value);
+
+        if !(minimum..=0xFF).contains(&result) {
+            self.error(SourceError::new(
+                value.span(),
+                format!("value {} does not fit in a byte", describe(result)),
+            ));
+        }
+
+        result as u8
+    }
+
+    fn word(&mut self, value: &Expr) -> u16 {
+        let result = self.evaluate_or_zero(value);
+
+        if !(-0x8000..=0xFFFF).contains(&result) {
+            self.error(SourceError::new(
+                value.span(),
+                format!("value {} does not fit in 16 bits", describe(result)),
+            ));
+        }
+
+        result as u16
+    }
+
+    fn branch(&mut self, value: &Expr) -> u8 {
+        let target = match self.evaluate(value) {
+            Ok(target) => target,
+            Err(error) => {
+                self.error(error);
+                return 0;
+            }
+        };
+        let offset = target - (i64::from(self.address) + 2);
+
+        if !(-128..=127).contains(&offset) {
+            self.error(SourceError::new(
+                value.span(),
+                format!("branch target is {} bytes away, out of range", offset),
+            ));
+        }
+
+        offset as u8
+    }
+
+    fn error(&mut self, mut error: SourceError) {
+        for frame in self.frames.iter().rev() {
+            match frame {
+                Frame::Macro {
+                    name,
+                    call,
+                    definition,
+                } => {
+                    error
+                        .notes
+                        .push((*call, format!("in this expansion of macro `{}`", name)));
+                    error
+                        .notes
+                        .push((*definition, format!("macro `{}` defined here", name)));
+                }
+                Frame::Include { file, site } => {
+                    let name = &self.sources.files[*file].name;
+                    error
+                        .notes
+                        .push((*site, format!("in `{}`, included from here", name)));
+                }
+            }
+        }
+
+        self.errors.push(error);
+    }
+}
+
+/// Whether a name is a local label, such as `@loop` or `.loop`.
+fn is_local(name: &str) -> bool {
+    name.starts_with(['@', '.'])
+}
+
+/// Whether a name is an anonymous label, such as `-`, `++` or `:`.
+fn is_anonymous(name: &str) -> bool {
+    name.starts_with(['+', '-', ':'])
+}
+
+/// Format a value the way it would usually be written in source.
+pub fn describe(value: i64) -> String {
+    match value {
+        0.. => format!("${:X}", value),
+        _ => value.to_string(),
+    }
+}
+
+/// Collect the lines of a macro body, up to the `.endm` matching its `.macro`.
+///
+/// # Returns
+///
+/// The body, the number of lines consumed including the `.endm`, and whether it was found.
+fn macro_body(lines: &[Line]) -> (Vec<Line>, usize, bool) {
+    let mut depth = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        match Directive::of_line(line) {
+            Some(Directive::Macro) => depth += 1,
+            Some(Directive::EndMacro) if depth == 0 => {
+                return (lines[..index].to_vec(), index + 1, true)
+            }
+            Some(Directive::EndMacro) => depth -= 1,
+            _ => (),
+        }
+    }
+
+    (lines.to_vec(), lines.len(), false)
+}
@@ -0,0 +1,122 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A file of assembly source, split into lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// The name shown in diagnostics.
+    pub name: String,
+    /// Where the file was read from, if it came from disk.
+    pub path: Option<PathBuf>,
+    pub lines: Vec<String>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, text: &str) -> Self {
+        SourceFile {
+            name: name.to_string(),
+            path: None,
+            lines: text.lines().map(String::from).collect(),
+        }
+    }
+
+    /// Read a source file from disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file to read.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the file, named by the path as given.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(SourceFile {
+            path: Some(fs::canonicalize(path)?),
+            ..SourceFile::new(&path.display().to_string(), &text)
+        })
+    }
+}
+
+/// Every source file taking part in an assembly, indexed by `Location::file`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sources {
+    pub files: Vec<SourceFile>,
+}
+
+impl Sources {
+    pub fn new() -> Self {
+        Sources { files: vec![] }
+    }
+
+    /// Add a file, returning its index.
+    pub fn add(&mut self, file: SourceFile) -> usize {
+        self.files.push(file);
+        self.files.len() - 1
+    }
+
+    /// Load a file from disk, reusing it if it has been loaded before.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, as found by `resolve`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index of the file.
+    pub fn load(&mut self, path: &Path) -> io::Result<usize> {
+        let canonical = fs::canonicalize(path)?;
+        let existing = self
+            .files
+            .iter()
+            .position(|file| file.path.as_ref() == Some(&canonical));
+
+        match existing {
+            Some(index) => Ok(index),
+            None => Ok(self.add(SourceFile::read(path)?)),
+        }
+    }
+
+    /// Find a file named by an `.include` or `.incbin` directive.
+    ///
+    /// The directory of the including file is searched first, then each of the
+    /// include paths in order. Files included from source that did not come from
+    /// disk are looked up relative to the current directory instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The file name as written in the directive.
+    /// * `from` - The index of the including file.
+    /// * `include_paths` - Extra directories to search.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the path of the file, or the list of places searched.
+    pub fn resolve(
+        &self,
+        name: &str,
+        from: usize,
+        include_paths: &[PathBuf],
+    ) -> Result<PathBuf, Vec<PathBuf>> {
+        // Use the path as it was found rather than the canonical one, to keep names short
+        let file = &self.files[from];
+        let directory = match file.path {
+            Some(_) => Path::new(&file.name).parent().map(Path::to_path_buf),
+            None => None,
+        }
+        .unwrap_or_default();
+
+        let candidates: Vec<PathBuf> = std::iter::once(directory)
+            .chain(include_paths.iter().cloned())
+            .map(|directory| directory.join(name))
+            .collect();
+
+        candidates
+            .iter()
+            .find(|candidate| candidate.is_file())
+            .cloned()
+            .ok_or(candidates)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::memory::Memory;
+use crate::pia::Pia;
+
+/// Everything the CPU reaches through its address pins: RAM, with the PIA's
+/// registers mapped over it at `$D010` to `$D013`.
+///
+/// # Examples
+///
+/// ```
+/// use apple_ie::Bus;
+///
+/// let mut bus = Bus::new();
+/// bus.write(0x0300, 0x42);
+/// assert_eq!(bus.read(0x0300), Some(0x42));
+///
+/// bus.pia.press(b'a');
+/// assert_eq!(bus.read(0xD010), Some(0xC1));
+/// ```
+#[derive(Default)]
+pub struct Bus {
+    pub memory: Memory,
+    /// The keyboard and display.
+    pub pia: Pia,
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a byte without side effects, as a debugger looks at memory.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the byte, or `None` if nothing answers at the address.
+    pub fn peek(&self, address: u16) -> Option<u8> {
+        if Pia::contains(address) {
+            return Some(self.pia.peek(address));
+        }
+        self.memory.read(address)
+    }
+
+    /// Read a byte as the CPU does, so that reading the keyboard takes the waiting key.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the byte, or `None` if nothing answers at the address.
+    pub fn read(&mut self, address: u16) -> Option<u8> {
+        if Pia::contains(address) {
+            return Some(self.pia.read(address));
+        }
+        self.memory.read(address)
+    }
+
+    /// Write a byte as the CPU does, so that writing the display prints it.
+    pub fn write(&mut self, address: u16, value: u8) {
+        if Pia::contains(address) {
+            self.pia.write(address, value);
+            return;
+        }
+        self.memory.write(address, value);
+    }
+
+    /// Copy bytes into RAM, leaving the PIA alone.
+    ///
+    /// Bytes past the top of memory wrap around to address zero.
+    pub fn load(&mut self, address: u16, buffer: &[u8]) {
+        self.memory.load(address, buffer);
+    }
+}
@@ -0,0 +1,602 @@
+use super::script::unescape;
+use super::snapshot;
+use apple_ie::assembler::evaluate;
+use apple_ie::cpu::breakpoints::{Access, Breakpoint, Condition, Point, Stop, Watchpoint};
+use apple_ie::cpu::{Cpu, CpuError};
+use apple_ie::disassembler::Instruction;
+
+use anyhow::Result;
+use std::{
+    io::{BufRead, Write},
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// The opcode of `JSR`, which `next` runs over as one step.
+const JSR_OPCODE: u8 = 0x20;
+
+/// How many instructions `dis` shows when no count is given.
+const DISASSEMBLE_COUNT: usize = 10;
+
+/// How many bytes `mem` shows when no length is given.
+const EXAMINE_LENGTH: usize = 64;
+
+/// How many bytes each row of a memory dump shows.
+const BYTES_PER_ROW: usize = 16;
+
+/// How many instructions can be stepped back over, unless the CPU already keeps a history.
+const HISTORY_LENGTH: usize = 10_000;
+
+/// How many instructions run between checks for an interrupt.
+const RUN_CHUNK: u64 = 10_000;
+
+/// Every command name, including the short forms.
+const COMMANDS: [&str; 38] = [
+    "s", "step", "bs", "back", "rw", "rewind", "n", "next", "u", "until", "c", "continue", "r",
+    "regs", "m", "mem", "w", "write", "d", "dis", "b", "break", "wa", "watch", "ignore", "del",
+    "delete", "bl", "breaks", "k", "keys", "save", "load", "h", "help", "?", "q", "quit",
+];
+
+const HELP: &str = "\
+  s, step [N]           Execute N instructions, or one
+  bs, back [N]          Undo the last N instructions, or one
+  rw, rewind CYC        Undo instructions back to a cycle count
+  n, next               Execute one instruction, running a JSR through to its return
+  u, until ADDR         Run until the PC reaches an address
+  c, continue           Run until a BRK, breakpoint or watchpoint
+  r, regs               Show the registers and flags
+  m, mem ADDR [LEN]     Show memory as hex and ASCII
+  w, write ADDR BYTE..  Write bytes to memory
+  d, dis [ADDR] [N]     Disassemble N instructions, from the PC by default
+  b, break ADDR [if C]  Stop before the instruction at an address runs
+  wa, watch ADDR[..END] [r|w|rw] [if C]
+                        Stop after memory is read or written, both by default
+  ignore ID N           Let a breakpoint or watchpoint pass its next N hits
+  del, delete ID        Remove a breakpoint or watchpoint
+  bl, breaks            List the breakpoints and watchpoints
+  k, keys TEXT          Type keys for the program to read, with \\r for Return
+  save FILE             Save a snapshot of the machine
+  load FILE             Restore the machine from a snapshot
+  h, help               Show this list
+  q, quit               Leave the debugger
+
+Addresses and values are expressions, such as $0800, start+2 or pc.
+Conditions are expressions too, such as A == $FF or X > 3 && C, using the
+registers A, X, Y, SP, PC and P, the flags N, V, D, I, Z and C, and for
+watchpoints the ADDRESS and VALUE accessed.
+An empty line repeats the last command, and Ctrl-C interrupts a running program.";
+
+/// A debugger command, parsed from a line of input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Back(usize),
+    Rewind(u64),
+    Next,
+    Until(u16),
+    Continue,
+    Registers,
+    Examine {
+        address: u16,
+        length: usize,
+    },
+    Write {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+    Disassemble {
+        address: Option<u16>,
+        count: usize,
+    },
+    Break {
+        address: u16,
+        condition: Option<Condition>,
+    },
+    Watch {
+        range: RangeInclusive<u16>,
+        access: Access,
+        condition: Option<Condition>,
+    },
+    Ignore {
+        id: usize,
+        count: u64,
+    },
+    Delete(usize),
+    Breakpoints,
+    /// Keys to queue on the keyboard.
+    Keys(Vec<u8>),
+    Save(PathBuf),
+    Load(PathBuf),
+    Help,
+    Quit,
+}
+
+impl Command {
+    /// Parse a command line, evaluating its arguments against the CPU's symbols.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The command and its arguments, separated by spaces.
+    /// * `cpu` - The CPU, whose symbols and program counter arguments may use.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the command, or a message explaining what is wrong.
+    pub fn parse(line: &str, cpu: &Cpu) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default().to_lowercase();
+        let mut args: Vec<&str> = words.collect();
+
+        // Keys are typed as written, spaces and all
+        if matches!(name.as_str(), "k" | "keys") {
+            return match line.trim_start().split_once(char::is_whitespace) {
+                Some((_, text)) if !text.trim().is_empty() => {
+                    Ok(Command::Keys(unescape(text.trim_start())))
+                }
+                _ => Err(format!("wrong arguments for `{}`, see `help`", name)),
+            };
+        }
+
+        // Everything after `if` is a condition, which may contain spaces
+        let condition = match args.iter().position(|arg| arg.eq_ignore_ascii_case("if")) {
+            Some(index) => {
+                let text = args.split_off(index)[1..].join(" ");
+                Some(Condition::new(&text)?)
+            }
+            None => None,
+        };
+
+        let value = |text: &str| {
+            evaluate(text, |name| {
+                if name.eq_ignore_ascii_case("pc") {
+                    return Some(i64::from(cpu.registers.program_counter));
+                }
+                cpu.symbols.address_of(name).map(i64::from)
+            })
+        };
+        let address = |text: &str| {
+            let value = value(text)?;
+            u16::try_from(value).map_err(|_| format!("{} is not a 16-bit address", text))
+        };
+        let count = |text: &str| {
+            let value = value(text)?;
+            usize::try_from(value).map_err(|_| format!("{} is not a count", text))
+        };
+
+        let command = match (name.as_str(), args.as_slice()) {
+            ("s" | "step", []) => Command::Step(1),
+            ("s" | "step", [n]) => Command::Step(count(n)?),
+            ("bs" | "back", []) => Command::Back(1),
+            ("bs" | "back", [n]) => Command::Back(count(n)?),
+            ("rw" | "rewind", [cycles]) => Command::Rewind(count(cycles)? as u64),
+            ("n" | "next", []) => Command::Next,
+            ("u" | "until", [target]) => Command::Until(address(target)?),
+            ("c" | "continue", []) => Command::Continue,
+            ("r" | "regs", []) => Command::Registers,
+            ("m" | "mem", [start]) => Command::Examine {
+                address: address(start)?,
+                length: EXAMINE_LENGTH,
+            },
+            ("m" | "mem", [start, length]) => Command::Examine {
+                address: address(start)?,
+                length: count(length)?,
+            },
+            ("w" | "write", [start, bytes @ ..]) if !bytes.is_empty() => {
+                let bytes = bytes
+                    .iter()
+                    .map(|text| {
+                        let value = value(text)?;
+                        u8::try_from(value).map_err(|_| format!("{} does not fit in a byte", text))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Command::Write {
+                    address: address(start)?,
+                    bytes,
+                }
+            }
+            ("d" | "dis", []) => Command::Disassemble {
+                address: None,
+                count: DISASSEMBLE_COUNT,
+            },
+            ("d" | "dis", [start]) => Command::Disassemble {
+                address: Some(address(start)?),
+                count: DISASSEMBLE_COUNT,
+            },
+            ("d" | "dis", [start, n]) => Command::Disassemble {
+                address: Some(address(start)?),
+                count: count(n)?,
+            },
+            ("b" | "break", [target]) => Command::Break {
+                address: address(target)?,
+                condition,
+            },
+            ("wa" | "watch", [range, kind @ ..]) if kind.len() <= 1 => {
+                let range = match range.split_once("..") {
+                    Some((start, end)) => address(start)?..=address(end)?,
+                    None => address(range)?..=address(range)?,
+                };
+                let access = match kind.first().map(|kind| kind.to_lowercase()).as_deref() {
+                    None | Some("rw") => Access::Any,
+                    Some("r") => Access::Read,
+                    Some("w") => Access::Write,
+                    Some(kind) => return Err(format!("`{}` is not r, w or rw", kind)),
+                };
+                Command::Watch {
+                    range,
+                    access,
+                    condition,
+                }
+            }
+            ("ignore", [id, n]) => Command::Ignore {
+                id: count(id)?,
+                count: count(n)? as u64,
+            },
+            ("del" | "delete", [id]) => Command::Delete(count(id)?),
+            ("bl" | "breaks", []) => Command::Breakpoints,
+            ("save", [path]) => Command::Save(PathBuf::from(path)),
+            ("load", [path]) => Command::Load(PathBuf::from(path)),
+            ("h" | "help" | "?", []) => Command::Help,
+            ("q" | "quit", []) => Command::Quit,
+            (name, _) if condition.is_some() && COMMANDS.contains(&name) => {
+                return Err(format!("`{}` does not take a condition", name))
+            }
+            (name, _) if COMMANDS.contains(&name) => {
+                return Err(format!("wrong arguments for `{}`, see `help`", name))
+            }
+            _ => return Err(format!("unknown command `{}`, see `help`", name)),
+        };
+
+        Ok(command)
+    }
+}
+
+/// An interactive machine-language monitor driving a `Cpu`.
+pub struct Debugger<'a> {
+    cpu: &'a mut Cpu,
+    /// The last command entered, repeated by an empty line.
+    last: String,
+    /// Set to stop a running program, as a Ctrl-C handler does.
+    interrupt: Arc<AtomicBool>,
+}
+
+impl<'a> Debugger<'a> {
+    /// Take charge of a CPU, starting to record its history if it is not already.
+    pub fn new(cpu: &'a mut Cpu) -> Self {
+        if cpu.history.capacity() == 0 {
+            cpu.history.set_capacity(HISTORY_LENGTH);
+        }
+        Debugger {
+            cpu,
+            last: String::new(),
+            interrupt: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// The flag that stops a running program once set, for a signal handler to set.
+    pub fn interrupt(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupt)
+    }
+
+    /// Read commands until `quit` or the end of the input, writing what they show.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Where commands are read from, one per line.
+    /// * `output` - Where prompts and results are written.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that fails only when the input or output does.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> Result<()> {
+        writeln!(output, "Type `help` for a list of commands.")?;
+        self.show_position(&mut output)?;
+
+        loop {
+            write!(output, "(dbg) ")?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            let line = match line.trim() {
+                "" => self.last.clone(),
+                line => line.to_string(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            self.last = line.clone();
+
+            match Command::parse(&line, self.cpu) {
+                Ok(Command::Quit) => break,
+                Ok(command) => self.execute(command, &mut output)?,
+                Err(message) => writeln!(output, "{}", message)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Carry out a command, writing what it shows.
+    pub fn execute(&mut self, command: Command, output: &mut impl Write) -> Result<()> {
+        match command {
+            Command::Step(count) => {
+                self.resume(count as u64, None, output)?;
+                self.show_position(output)?;
+            }
+            Command::Back(count) => {
+                let undone = (0..count).take_while(|_| self.cpu.step_back()).count();
+                if undone < count {
+                    writeln!(output, "History ends after {} instructions", undone)?;
+                }
+                self.show_position(output)?;
+            }
+            Command::Rewind(cycles) => match self.cpu.rewind(cycles) {
+                Some(_) => self.show_position(output)?,
+                None => writeln!(
+                    output,
+                    "History only goes back to cycle {}",
+                    self.cpu.history.earliest().unwrap_or(self.cpu.cycles)
+                )?,
+            },
+            Command::Next => {
+                let pc = self.cpu.registers.program_counter;
+                if self.cpu.read_addr(pc)? == JSR_OPCODE {
+                    self.resume(u64::MAX, Some(pc.wrapping_add(3)), output)?;
+                } else {
+                    self.resume(1, None, output)?;
+                }
+                self.show_position(output)?;
+            }
+            Command::Until(address) => {
+                self.resume(u64::MAX, Some(address), output)?;
+                self.show_position(output)?;
+            }
+            Command::Continue => {
+                self.resume(u64::MAX, None, output)?;
+                self.show_position(output)?;
+            }
+            Command::Break { address, condition } => {
+                let point = Point::Breakpoint(Breakpoint {
+                    condition,
+                    ..Breakpoint::at(address)
+                });
+                self.add_point(point, output)?;
+            }
+            Command::Watch {
+                range,
+                access,
+                condition,
+            } => {
+                let point = Point::Watchpoint(Watchpoint {
+                    condition,
+                    ..Watchpoint::on(range, access)
+                });
+                self.add_point(point, output)?;
+            }
+            Command::Ignore { id, count } => match self.cpu.breakpoints.get_mut(id) {
+                Some(Point::Breakpoint(Breakpoint { ignore, hits, .. }))
+                | Some(Point::Watchpoint(Watchpoint { ignore, hits, .. })) => {
+                    *ignore = *hits + count;
+                    writeln!(output, "Ignoring the next {} hits of {}", count, id)?;
+                }
+                None => writeln!(output, "No breakpoint or watchpoint {}", id)?,
+            },
+            Command::Delete(id) => match self.cpu.breakpoints.remove(id) {
+                Some(point) => writeln!(output, "Deleted {}: {}", id, point)?,
+                None => writeln!(output, "No breakpoint or watchpoint {}", id)?,
+            },
+            Command::Keys(keys) => {
+                for key in keys {
+                    self.cpu.bus.pia.press(key);
+                }
+            }
+            Command::Breakpoints => {
+                if self.cpu.breakpoints.is_empty() {
+                    writeln!(output, "No breakpoints or watchpoints")?;
+                }
+                for (id, point) in self.cpu.breakpoints.iter() {
+                    writeln!(output, "{:3}  {}", id, point)?;
+                }
+            }
+            Command::Registers => {
+                writeln!(output, "{}  CYC={}", self.cpu.registers, self.cpu.cycles)?;
+            }
+            Command::Examine { address, length } => self.examine(address, length, output)?,
+            Command::Write { address, bytes } => {
+                for (offset, byte) in bytes.iter().enumerate() {
+                    self.cpu
+                        .load_addr(address.wrapping_add(offset as u16), *byte);
+                }
+            }
+            Command::Disassemble { address, count } => {
+                let pc = self.cpu.registers.program_counter;
+                for instruction in self.cpu.disassemble(address.unwrap_or(pc), count) {
+                    self.show_instruction(&instruction, output)?;
+                }
+            }
+            Command::Save(path) => match snapshot::write(self.cpu, &path) {
+                Ok(()) => writeln!(output, "Saved {}", path.display())?,
+                Err(error) => writeln!(output, "{:#}", error)?,
+            },
+            Command::Load(path) => match snapshot::read(self.cpu, &path) {
+                Ok(()) => self.show_position(output)?,
+                Err(error) => writeln!(output, "{:#}", error)?,
+            },
+            Command::Help => writeln!(output, "{}", HELP)?,
+            Command::Quit => (),
+        }
+
+        Ok(())
+    }
+
+    /// Run the CPU, saying what stopped it unless it ran as far as asked.
+    ///
+    /// It runs in chunks, showing what the display prints after each, until it
+    /// is done or `interrupt` is set.
+    fn resume(&mut self, limit: u64, until: Option<u16>, output: &mut impl Write) -> Result<()> {
+        self.interrupt.store(false, Ordering::Relaxed);
+        let mut remaining = limit;
+        let mut ends_line = true;
+
+        let stop = loop {
+            let chunk = remaining.min(RUN_CHUNK);
+            remaining -= chunk;
+            let stop = self.cpu.resume(chunk, until);
+
+            let printed = self.cpu.bus.pia.display.take_output();
+            if !printed.is_empty() {
+                write!(output, "{}", printed)?;
+                output.flush()?;
+                ends_line = printed.ends_with('\n');
+            }
+
+            if !matches!(stop, Ok(Stop::Limit)) || remaining == 0 {
+                break stop.map(Some);
+            }
+            if self.interrupt.swap(false, Ordering::Relaxed) {
+                break Ok(None);
+            }
+
+            // Carrying on passes over what is at the PC, so check it here
+            let address = self.cpu.registers.program_counter;
+            if until == Some(address) {
+                break Ok(Some(Stop::Reached { address }));
+            }
+            match self.cpu.pending_stop() {
+                Ok(None) => (),
+                stop => break stop,
+            }
+        };
+
+        if !ends_line {
+            writeln!(output)?;
+        }
+
+        let symbols = &self.cpu.symbols;
+
+        match stop {
+            Ok(None) => writeln!(
+                output,
+                "Interrupted at {}",
+                symbols.describe(self.cpu.registers.program_counter)
+            )?,
+            Ok(Some(Stop::Limit | Stop::Reached { .. })) => (),
+            Ok(Some(Stop::Condition {
+                id,
+                address,
+                message,
+            })) => writeln!(
+                output,
+                "Condition of {} failed at {}: {}",
+                id,
+                symbols.describe(address),
+                message
+            )?,
+            Ok(Some(Stop::Brk { address })) => {
+                writeln!(output, "BRK at {}", symbols.describe(address))?
+            }
+            Ok(Some(Stop::Breakpoint { id, address })) => {
+                writeln!(output, "Breakpoint {} at {}", id, symbols.describe(address))?
+            }
+            Ok(Some(Stop::Watchpoint {
+                id,
+                address,
+                value,
+                access,
+            })) => writeln!(
+                output,
+                "Watchpoint {}: {} of ${:02X} at {}",
+                id,
+                access,
+                value,
+                symbols.describe(address)
+            )?,
+            Err(error) => writeln!(
+                output,
+                "Stopped at {}: {}",
+                symbols.describe(self.cpu.registers.program_counter),
+                error
+            )?,
+        }
+
+        Ok(())
+    }
+
+    fn add_point(&mut self, point: Point, output: &mut impl Write) -> Result<()> {
+        let text = point.to_string();
+        let id = self.cpu.breakpoints.add(point);
+        writeln!(output, "{:3}  {}", id, text)?;
+        Ok(())
+    }
+
+    fn show_position(&self, output: &mut impl Write) -> Result<()> {
+        let pc = self.cpu.registers.program_counter;
+        if let Some(instruction) = self.cpu.disassemble(pc, 1).first() {
+            self.show_instruction(instruction, output)?;
+        }
+        writeln!(output, "{}  CYC={}", self.cpu.registers, self.cpu.cycles)?;
+        Ok(())
+    }
+
+    fn show_instruction(&self, instruction: &Instruction, output: &mut impl Write) -> Result<()> {
+        let symbols = &self.cpu.symbols;
+        let marker = if instruction.address == self.cpu.registers.program_counter {
+            '>'
+        } else {
+            ' '
+        };
+
+        if let Some(name) = symbols.name_of(instruction.address) {
+            writeln!(output, "{:18}{}:", "", name)?;
+        }
+
+        let bytes: Vec<String> = instruction
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        writeln!(
+            output,
+            "{} {:04X}  {:8}  {}",
+            marker,
+            instruction.address,
+            bytes.join(" "),
+            instruction.text(symbols)
+        )?;
+        Ok(())
+    }
+
+    fn examine(&self, address: u16, length: usize, output: &mut impl Write) -> Result<()> {
+        let mut offset = 0;
+
+        while offset < length {
+            let row = address.wrapping_add(offset as u16);
+            let count = BYTES_PER_ROW.min(length - offset);
+            let bytes = (0..count as u16)
+                .map(|index| self.cpu.read_addr(row.wrapping_add(index)))
+                .collect::<Result<Vec<u8>, CpuError>>()?;
+
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let text: String = bytes
+                .iter()
+                .map(|byte| match byte & 0x7F {
+                    // The Apple I sets the high bit of characters, so show them either way
+                    character @ 0x20..=0x7E => character as char,
+                    _ => '.',
+                })
+                .collect();
+
+            writeln!(output, "{:04X}  {:47}  |{}|", row, hex.join(" "), text)?;
+            offset += count;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,463 @@
+use apple_ie::cpu::breakpoints::{Access, Breakpoint, Point, Stop, Watchpoint};
+use apple_ie::cpu::{Cpu, CpuError, StatusFlags};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::{
+    collections::BTreeMap,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+/// Where the server listens when no address is given.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:6502";
+
+/// The byte a client sends to interrupt a running program.
+const INTERRUPT: u8 = 0x03;
+
+/// How many instructions run between checks for an interrupt.
+const RUN_CHUNK: u64 = 10_000;
+
+/// The largest packet accepted, as given to the client in hex.
+const PACKET_SIZE: usize = 0x4000;
+
+/// The signals reported when the CPU stops, numbered as GDB expects.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+/// The registers as the client sees them, in the order of the `g` packet.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.m6502.core">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="x" bitsize="8"/>
+    <reg name="y" bitsize="8"/>
+    <reg name="p" bitsize="8"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// A GDB remote serial protocol server driving a `Cpu`.
+///
+/// Registers are sent as A, X, Y, P, SP and PC, with the PC little-endian.
+/// Software and hardware breakpoints are both kept by the CPU, as are write,
+/// read and access watchpoints.
+pub struct Server<'a> {
+    cpu: &'a mut Cpu,
+    /// The CPU breakpoint made for each `Z` packet, by type, address and length.
+    points: BTreeMap<(u8, u16, u16), usize>,
+}
+
+/// What a client sent.
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+impl<'a> Server<'a> {
+    pub fn new(cpu: &'a mut Cpu) -> Self {
+        Server {
+            cpu,
+            points: BTreeMap::new(),
+        }
+    }
+
+    /// Wait for a client to connect to an address, then serve it until it leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to listen on, such as `127.0.0.1:6502`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that fails if the address cannot be listened on or the connection breaks.
+    pub fn listen(&mut self, address: &str) -> Result<()> {
+        let listener =
+            TcpListener::bind(address).with_context(|| format!("Cannot listen on {}", address))?;
+        info!("Waiting for a GDB client on {}.", listener.local_addr()?);
+
+        let (stream, client) = listener.accept()?;
+        info!("GDB client connected from {}.", client);
+        self.serve(stream)
+    }
+
+    /// Answer packets from a connected client until it detaches, kills the program or leaves.
+    pub fn serve(&mut self, stream: TcpStream) -> Result<()> {
+        // Packets are small and answered one at a time, so send them straight away
+        stream.set_nodelay(true)?;
+        let mut connection = Connection { stream };
+
+        while let Some(incoming) = connection.receive()? {
+            let packet = match incoming {
+                Incoming::Packet(packet) => packet,
+                Incoming::Interrupt => {
+                    connection.send(&format!("S{:02x}", SIGINT))?;
+                    continue;
+                }
+            };
+
+            match packet.as_bytes().first() {
+                Some(b'k') => break,
+                Some(b'D') => {
+                    connection.send("OK")?;
+                    break;
+                }
+                _ => {
+                    let reply = self.answer(&packet, &mut connection)?;
+                    connection.send(&reply)?;
+                }
+            }
+        }
+
+        info!("GDB client left.");
+        Ok(())
+    }
+
+    /// Work out the reply to a packet. Packets that are not supported get an empty reply.
+    fn answer(&mut self, packet: &str, connection: &mut Connection) -> Result<String> {
+        let Some(command) = packet.get(..1) else {
+            return Ok(String::new());
+        };
+        let rest = &packet[1..];
+
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => {
+                let registers = &self.cpu.registers;
+                let [low, high] = registers.program_counter.to_le_bytes();
+                encode(&[
+                    registers.accumulator,
+                    registers.x,
+                    registers.y,
+                    registers.status.bits(),
+                    registers.stack_pointer,
+                    low,
+                    high,
+                ])
+            }
+            "G" => match decode(rest).as_deref() {
+                Some(&[a, x, y, p, sp, low, high]) => {
+                    for (number, value) in [a, x, y, p, sp].into_iter().enumerate() {
+                        self.set_register(number, u16::from(value));
+                    }
+                    self.set_register(5, u16::from_le_bytes([low, high]));
+                    "OK".to_string()
+                }
+                _ => error(),
+            },
+            "p" => match usize::from_str_radix(rest, 16) {
+                Ok(number @ 0..=4) => encode(&[self.register(number) as u8]),
+                Ok(5) => encode(&self.register(5).to_le_bytes()),
+                _ => error(),
+            },
+            "P" => {
+                let value = rest.split_once('=').and_then(|(number, value)| {
+                    let number = usize::from_str_radix(number, 16).ok()?;
+                    let bytes = decode(value)?;
+                    let value = bytes
+                        .iter()
+                        .rev()
+                        .fold(0, |value, byte| value << 8 | u16::from(*byte));
+                    (number <= 5).then_some((number, value))
+                });
+                match value {
+                    Some((number, value)) => {
+                        self.set_register(number, value);
+                        "OK".to_string()
+                    }
+                    None => error(),
+                }
+            }
+            "m" => match address_and_length(rest) {
+                Some((address, length)) => {
+                    let bytes: Result<Vec<u8>, CpuError> = (0..length.min(PACKET_SIZE / 2) as u16)
+                        .map(|offset| self.cpu.read_addr(address.wrapping_add(offset)))
+                        .collect();
+                    bytes.map_or_else(|_| error(), |bytes| encode(&bytes))
+                }
+                None => error(),
+            },
+            "M" => {
+                let write = rest.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = address_and_length(range)?;
+                    let bytes = decode(data)?;
+                    (bytes.len() == length).then_some((address, bytes))
+                });
+                match write {
+                    Some((address, bytes)) => {
+                        for (offset, byte) in bytes.iter().enumerate() {
+                            self.cpu
+                                .load_addr(address.wrapping_add(offset as u16), *byte);
+                        }
+                        "OK".to_string()
+                    }
+                    None => error(),
+                }
+            }
+            "s" | "c" => {
+                if !rest.is_empty() {
+                    match u16::from_str_radix(rest, 16) {
+                        Ok(address) => self.cpu.registers.program_counter = address,
+                        Err(_) => return Ok(error()),
+                    }
+                }
+                match command {
+                    "s" => {
+                        let stop = self.cpu.resume(1, None);
+                        self.stop_reply(stop)
+                    }
+                    _ => self.run(connection)?,
+                }
+            }
+            "Z" | "z" => self.change_point(command == "Z", rest),
+            "H" | "T" => "OK".to_string(),
+            "q" => match rest {
+                _ if rest.starts_with("Supported") => {
+                    format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE)
+                }
+                "Attached" => "1".to_string(),
+                "C" => "QC1".to_string(),
+                "fThreadInfo" => "m1".to_string(),
+                "sThreadInfo" => "l".to_string(),
+                _ => match rest.strip_prefix("Xfer:features:read:target.xml:") {
+                    Some(range) => match address_and_length(range) {
+                        Some((offset, length)) => chunk(TARGET_XML, offset.into(), length),
+                        None => error(),
+                    },
+                    None => String::new(),
+                },
+            },
+            _ => String::new(),
+        };
+
+        Ok(reply)
+    }
+
+    /// Continue until something stops the CPU or the client interrupts it.
+    fn run(&mut self, connection: &mut Connection) -> Result<String> {
+        let mut stop = self.cpu.resume(RUN_CHUNK, None);
+
+        while let Ok(Stop::Limit) = stop {
+            if connection.interrupted()? {
+                return Ok(format!("S{:02x}", SIGINT));
+            }
+
+            // Carrying on passes over a breakpoint or BRK at the PC, so check for one here
+            stop = match self.cpu.pending_stop() {
+                Ok(Some(stop)) => Ok(stop),
+                Ok(None) => self.cpu.resume(RUN_CHUNK, None),
+                Err(error) => Err(error),
+            };
+        }
+
+        Ok(self.stop_reply(stop))
+    }
+
+    fn stop_reply(&self, stop: Result<Stop, CpuError>) -> String {
+        match stop {
+            Ok(Stop::Watchpoint { id, address, .. }) => {
+                let kind = match self.cpu.breakpoints.iter().find(|(point, _)| *point == id) {
+                    Some((_, Point::Watchpoint(watchpoint))) => watchpoint.access,
+                    _ => Access::Any,
+                };
+                let name = match kind {
+                    Access::Write => "watch",
+                    Access::Read => "rwatch",
+                    Access::Any => "awatch",
+                };
+                format!("T{:02x}{}:{:04x};", SIGTRAP, name, address)
+            }
+            Ok(_) => format!("S{:02x}", SIGTRAP),
+            Err(error) => {
+                warn!(
+                    "Stopped at ${:04X}: {}",
+                    self.cpu.registers.program_counter, error
+                );
+                format!("S{:02x}", SIGILL)
+            }
+        }
+    }
+
+    /// Insert or remove a breakpoint or watchpoint, from a `Z` or `z` packet.
+    fn change_point(&mut self, insert: bool, text: &str) -> String {
+        let mut fields = text.split(',');
+        let (Some(kind), Some(address), Some(length)) = (
+            fields.next().and_then(|kind| kind.parse::<u8>().ok()),
+            fields
+                .next()
+                .and_then(|address| u16::from_str_radix(address, 16).ok()),
+            fields
+                .next()
+                .and_then(|length| u16::from_str_radix(length, 16).ok()),
+        ) else {
+            return error();
+        };
+
+        let key = (kind, address, length);
+        if !insert {
+            if let Some(id) = self.points.remove(&key) {
+                self.cpu.breakpoints.remove(id);
+            }
+            return "OK".to_string();
+        }
+        if self.points.contains_key(&key) {
+            return "OK".to_string();
+        }
+
+        let end = address.saturating_add(length.max(1) - 1);
+        let point = match kind {
+            0 | 1 => Point::Breakpoint(Breakpoint::at(address)),
+            2 => Point::Watchpoint(Watchpoint::on(address..=end, Access::Write)),
+            3 => Point::Watchpoint(Watchpoint::on(address..=end, Access::Read)),
+            4 => Point::Watchpoint(Watchpoint::on(address..=end, Access::Any)),
+            _ => return String::new(),
+        };
+        self.points.insert(key, self.cpu.breakpoints.add(point));
+        "OK".to_string()
+    }
+
+    fn register(&self, number: usize) -> u16 {
+        let registers = &self.cpu.registers;
+        match number {
+            0 => registers.accumulator.into(),
+            1 => registers.x.into(),
+            2 => registers.y.into(),
+            3 => registers.status.bits().into(),
+            4 => registers.stack_pointer.into(),
+            _ => registers.program_counter,
+        }
+    }
+
+    fn set_register(&mut self, number: usize, value: u16) {
+        let registers = &mut self.cpu.registers;
+        let byte = value as u8;
+        match number {
+            0 => registers.accumulator = byte,
+            1 => registers.x = byte,
+            2 => registers.y = byte,
+            3 => registers.status = StatusFlags::from_bits_retain(byte) | StatusFlags::UNUSED,
+            4 => registers.stack_pointer = byte,
+            _ => registers.program_counter = value,
+        }
+    }
+}
+
+/// A client connection, reading and writing packets.
+struct Connection {
+    stream: TcpStream,
+}
+
+impl Connection {
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Read the next packet or interrupt, acknowledging packets as they arrive.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing what was read, or `None` once the client disconnects.
+    fn receive(&mut self) -> Result<Option<Incoming>> {
+        loop {
+            match self.byte()? {
+                None => return Ok(None),
+                Some(INTERRUPT) => return Ok(Some(Incoming::Interrupt)),
+                Some(b'$') => (),
+                // Acknowledgements and noise between packets
+                Some(_) => continue,
+            }
+
+            let mut data = vec![];
+            loop {
+                match self.byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|text| u8::from_str_radix(text, 16).ok());
+
+            if expected == Some(sum(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(Incoming::Packet(
+                    String::from_utf8_lossy(&data).into_owned(),
+                )));
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn send(&mut self, data: &str) -> Result<()> {
+        let packet = format!("${}#{:02x}", data, sum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Whether the client has asked for the running program to stop, without waiting.
+    fn interrupted(&mut self) -> Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let byte = self.byte();
+        self.stream.set_nonblocking(false)?;
+
+        match byte {
+            Ok(byte) => Ok(byte == Some(INTERRUPT)),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+fn sum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parse the `ADDR,LENGTH` of memory and transfer packets.
+fn address_and_length(text: &str) -> Option<(u16, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((
+        u16::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+/// Reply to a `qXfer` read with part of a document, marking the last part with `l`.
+fn chunk(document: &str, offset: usize, length: usize) -> String {
+    let rest = document.get(offset..).unwrap_or_default();
+    if rest.len() <= length {
+        format!("l{}", rest)
+    } else {
+        format!("m{}", &rest[..length])
+    }
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
@@ -0,0 +1,175 @@
+use super::script::Script;
+use apple_ie::assembler::evaluate;
+use apple_ie::cpu::{breakpoints::Stop, Cpu, BRK_OPCODE};
+use apple_ie::symbols::SymbolTable;
+
+use anyhow::Result;
+use std::{fmt, io::Write};
+
+/// Something that ends a headless run successfully once it happens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// The PC reaches an address, before the instruction there runs.
+    Pc(u16),
+    /// A memory address holds a value.
+    Memory { address: u16, value: u8 },
+    /// The display has shown some text, anywhere in what was printed.
+    Output(String),
+}
+
+impl Condition {
+    /// Parse the address of a `Pc` condition, which may name a symbol.
+    pub fn pc(text: &str, symbols: &SymbolTable) -> Result<Self, String> {
+        Ok(Condition::Pc(value(text, symbols)?))
+    }
+
+    /// Parse a `Memory` condition, written `ADDR=VALUE`.
+    pub fn memory(text: &str, symbols: &SymbolTable) -> Result<Self, String> {
+        let (address, expected) = text
+            .split_once('=')
+            .ok_or_else(|| format!("{} is not written ADDR=VALUE", text))?;
+        Ok(Condition::Memory {
+            address: value(address, symbols)?,
+            value: value(expected, symbols)?,
+        })
+    }
+
+    /// Whether the condition holds, looking at the output only when `printed` has grown.
+    fn met(&self, cpu: &Cpu, printed: &str, grown: bool) -> bool {
+        match self {
+            Condition::Pc(address) => cpu.registers.program_counter == *address,
+            Condition::Memory { address, value } => cpu.read_addr(*address).ok() == Some(*value),
+            Condition::Output(text) => grown && printed.contains(text.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Pc(address) => write!(f, "PC reached ${:04X}", address),
+            Condition::Memory { address, value } => {
+                write!(f, "${:04X} holds ${:02X}", address, value)
+            }
+            Condition::Output(text) => write!(f, "{:?} was printed", text),
+        }
+    }
+}
+
+/// How a headless run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// A condition was met.
+    Met(Condition),
+    /// A `BRK` was reached before any condition was met.
+    Brk(u16),
+    /// The cycle limit was reached before any condition was met.
+    CycleLimit(u64),
+    /// An instruction failed.
+    Error(String),
+}
+
+impl Outcome {
+    /// The process exit status for the outcome.
+    ///
+    /// 0 when a condition was met, or when a `BRK` ends a run with no
+    /// conditions; 1 when an instruction failed; 2 when the cycle limit was
+    /// reached; 3 when a `BRK` came before any condition was met.
+    pub fn exit_code(&self, conditions: bool) -> u8 {
+        match self {
+            Outcome::Met(_) => 0,
+            Outcome::Brk(_) if !conditions => 0,
+            Outcome::Error(_) => 1,
+            Outcome::CycleLimit(_) => 2,
+            Outcome::Brk(_) => 3,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Met(condition) => write!(f, "Stopped: {}", condition),
+            Outcome::Brk(address) => write!(f, "Stopped: BRK at ${:04X}", address),
+            Outcome::CycleLimit(cycles) => write!(f, "Stopped: cycle limit of {} reached", cycles),
+            Outcome::Error(error) => write!(f, "Stopped: {}", error),
+        }
+    }
+}
+
+/// Run a program at full speed until a condition is met, a `BRK` is reached,
+/// the cycle limit passes or an instruction fails.
+///
+/// What the display prints is written to `output` as it happens, and the
+/// script types on the keyboard between instructions. Conditions are checked
+/// before each instruction, so one already true stops the run straight away.
+///
+/// # Arguments
+///
+/// * `cpu` - The CPU, with the program loaded.
+/// * `conditions` - What ends the run successfully.
+/// * `max_cycles` - The most cycles to run, if limited.
+/// * `script` - Keys to type as the program runs, which may have no steps.
+/// * `output` - Where display output goes.
+///
+/// # Returns
+///
+/// A `Result` containing how the run ended, failing only if `output` does.
+pub fn run(
+    cpu: &mut Cpu,
+    conditions: &[Condition],
+    max_cycles: Option<u64>,
+    script: &mut Script,
+    output: &mut impl Write,
+) -> Result<Outcome> {
+    let mut printed = String::new();
+    let mut grown = false;
+    let mut text = String::new();
+
+    let outcome = loop {
+        script.advance(cpu, &text);
+
+        if let Some(condition) = conditions
+            .iter()
+            .find(|condition| condition.met(cpu, &printed, grown))
+        {
+            break Outcome::Met(condition.clone());
+        }
+        if let Some(limit) = max_cycles.filter(|limit| cpu.cycles >= *limit) {
+            break Outcome::CycleLimit(limit);
+        }
+
+        // Stepping passes over a BRK at the PC, so stop at one here
+        let stop = match cpu.read_addr(cpu.registers.program_counter) {
+            Ok(BRK_OPCODE) => Ok(Stop::Brk {
+                address: cpu.registers.program_counter,
+            }),
+            Ok(_) => cpu.resume(1, None),
+            Err(error) => Err(error),
+        };
+
+        text = cpu.bus.pia.display.take_output();
+        grown = !text.is_empty();
+        if grown {
+            output.write_all(text.as_bytes())?;
+            printed.push_str(&text);
+        }
+
+        match stop {
+            Ok(Stop::Brk { address }) => break Outcome::Brk(address),
+            Ok(_) => (),
+            Err(error) => {
+                let address = cpu.registers.program_counter;
+                break Outcome::Error(format!("{} near ${:04X}", error, address));
+            }
+        }
+    };
+
+    output.flush()?;
+    Ok(outcome)
+}
+
+fn value<T: TryFrom<i64>>(text: &str, symbols: &SymbolTable) -> Result<T, String> {
+    let value = evaluate(text, |name| symbols.address_of(name).map(i64::from))?;
+    T::try_from(value).map_err(|_| format!("{} is out of range", text))
+}
@@ -0,0 +1,11 @@
+//! The command line's tools for running programs: the debugger, the GDB
+//! server, headless runs, keyboard scripts and snapshots.
+
+pub mod debugger;
+pub mod gdb;
+pub mod headless;
+pub mod script;
+pub mod snapshot;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,199 @@
+use apple_ie::cpu::Cpu;
+
+use anyhow::{bail, Context, Result};
+use std::{collections::VecDeque, fs, path::Path};
+
+/// Cycles in a millisecond, at the Apple I's 1 MHz.
+const CYCLES_PER_MILLISECOND: u64 = 1_000;
+
+/// One step of a keyboard script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Press keys, in order.
+    Type(Vec<u8>),
+    /// Let some cycles pass.
+    Delay(u64),
+    /// Wait until the display prints some text.
+    Wait(String),
+}
+
+/// Keystrokes typed into the PIA as a program runs, following a script.
+///
+/// Scripts have a step on each line. Blank lines and lines starting with `#`
+/// are skipped.
+///
+/// ```text
+/// wait \
+/// line E000R
+/// delay 50ms
+/// type PRINT 2+2\r
+/// wait 4
+/// ```
+///
+/// * `type TEXT` types the text.
+/// * `line TEXT` types the text, then Return.
+/// * `delay N` waits N cycles, or N milliseconds when written `Nms`.
+/// * `wait TEXT` waits until the display prints the text, after the last key typed.
+///
+/// Text may use `\r` or `\n` for Return, `\e` for Escape, `\xHH` for any key
+/// and `\\` for a backslash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    steps: VecDeque<Step>,
+    /// The cycle count a delay in progress ends at.
+    until: Option<u64>,
+    /// What the display has printed since the last key typed or text waited for.
+    seen: String,
+}
+
+impl Script {
+    /// Parse a script.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the script, or an error naming the line that is wrong.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut steps = VecDeque::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let step = match name {
+                "type" => Step::Type(unescape(argument)),
+                "line" => Step::Type([unescape(argument), vec![b'\r']].concat()),
+                "delay" => Step::Delay(delay(argument.trim())?),
+                "wait" if !argument.is_empty() => {
+                    // The display ends lines with newlines, however Return was written
+                    let text = String::from_utf8_lossy(&unescape(argument)).replace('\r', "\n");
+                    Step::Wait(text)
+                }
+                _ => bail!("line {}: cannot understand `{}`", number + 1, line),
+            };
+            steps.push_back(step);
+        }
+
+        Ok(Script {
+            steps,
+            ..Script::default()
+        })
+    }
+
+    /// Parse a script written on one line, with steps separated by `;`.
+    ///
+    /// A `;` to type is written `\;`.
+    pub fn parse_inline(text: &str) -> Result<Self> {
+        let mut lines = String::new();
+        let mut chars = text.chars();
+
+        // Escapes are kept for `unescape`, so `\;` reaches it whole
+        while let Some(char) = chars.next() {
+            match char {
+                '\\' => {
+                    lines.push(char);
+                    lines.extend(chars.next());
+                }
+                ';' => lines.push('\n'),
+                char => lines.push(char),
+            }
+        }
+
+        Script::parse(&lines)
+    }
+
+    /// Read a script from a file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        Script::parse(&text).with_context(|| format!("Cannot load {}", path.display()))
+    }
+
+    /// The steps still to come.
+    #[cfg(test)]
+    pub fn steps(&self) -> &VecDeque<Step> {
+        &self.steps
+    }
+
+    /// Carry out every step that is ready, between instructions.
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - The CPU whose keyboard is typed on.
+    /// * `printed` - What the display printed since the last call.
+    pub fn advance(&mut self, cpu: &mut Cpu, printed: &str) {
+        self.seen.push_str(printed);
+
+        while let Some(step) = self.steps.front() {
+            match step {
+                Step::Type(keys) => {
+                    for key in keys {
+                        cpu.bus.pia.press(*key);
+                    }
+                    self.seen.clear();
+                }
+                Step::Delay(cycles) => {
+                    let until = *self.until.get_or_insert(cpu.cycles.saturating_add(*cycles));
+                    if cpu.cycles < until {
+                        return;
+                    }
+                    self.until = None;
+                }
+                Step::Wait(text) => match self.seen.find(text.as_str()) {
+                    Some(index) => {
+                        self.seen.drain(..index + text.len());
+                    }
+                    None => return,
+                },
+            }
+            self.steps.pop_front();
+        }
+    }
+}
+
+fn delay(text: &str) -> Result<u64> {
+    let (number, scale) = match text.strip_suffix("ms") {
+        Some(number) => (number, CYCLES_PER_MILLISECOND),
+        None => (text, 1),
+    };
+    let number: u64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("`{}` is not a delay", text))?;
+    number
+        .checked_mul(scale)
+        .with_context(|| format!("`{}` is too long a delay", text))
+}
+
+/// Turn the escapes in script text into the keys they stand for.
+pub fn unescape(text: &str) -> Vec<u8> {
+    let mut keys = vec![];
+    let mut bytes = text.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            keys.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'r' | b'n') => keys.push(b'\r'),
+            Some(b'e') => keys.push(0x1B),
+            Some(b'x') => {
+                let digits: Vec<u8> = bytes.by_ref().take(2).collect();
+                let key = std::str::from_utf8(&digits)
+                    .ok()
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+                match key {
+                    Some(key) => keys.push(key),
+                    None => keys.extend([b'\\', b'x'].iter().chain(&digits)),
+                }
+            }
+            Some(other) => keys.push(other),
+            None => keys.push(b'\\'),
+        }
+    }
+
+    keys
+}
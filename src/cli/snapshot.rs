@@ -0,0 +1,188 @@
+use apple_ie::cpu::{operations::Variant, Cpu, IllegalOpcodePolicy, Registers, StatusFlags};
+use apple_ie::display::Display;
+use apple_ie::memory::MEMORY_SIZE;
+use apple_ie::pia::Pia;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use std::{fs, path::Path};
+
+/// Marks a file as a snapshot, so other JSON is not mistaken for one.
+pub const FORMAT: &str = "apple-ie snapshot";
+
+/// The layout version written, raised whenever what is saved changes.
+pub const VERSION: u64 = 2;
+
+/// Capture the whole machine: the processor emulated and what it does with
+/// undocumented opcodes, registers, cycle count, memory and the PIA with its display.
+///
+/// Breakpoints, history, tracing and symbols are debugging aids, not machine
+/// state, so they are left out.
+///
+/// # Returns
+///
+/// The snapshot, as JSON text.
+pub fn save(cpu: &Cpu) -> String {
+    let registers = &cpu.registers;
+    let pia = &cpu.bus.pia;
+
+    let snapshot = json!({
+        "format": FORMAT,
+        "version": VERSION,
+        "variant": name(cpu.variant),
+        "illegal_opcodes": name(cpu.illegal_opcodes),
+        "registers": {
+            "a": registers.accumulator,
+            "x": registers.x,
+            "y": registers.y,
+            "p": registers.status.bits(),
+            "sp": registers.stack_pointer,
+            "pc": registers.program_counter,
+        },
+        "cycles": cpu.cycles,
+        "memory": hex(&cpu.memory().ram),
+        "pia": {
+            "key": pia.key,
+            "keys": hex(&pia.keys.iter().copied().collect::<Vec<u8>>()),
+            "keyboard_control": pia.keyboard_control,
+            "display_control": pia.display_control,
+        },
+        "display": pia.display.rows(),
+    });
+
+    serde_json::to_string_pretty(&snapshot).unwrap_or_default()
+}
+
+/// Put the machine back as a snapshot describes it.
+///
+/// Nothing changes unless the whole snapshot can be read. The history is
+/// cleared, since the instructions in it led somewhere else.
+///
+/// # Arguments
+///
+/// * `cpu` - The CPU to restore.
+/// * `text` - A snapshot made by `save`.
+///
+/// # Returns
+///
+/// A `Result` that fails if the text is not a snapshot of this version.
+pub fn restore(cpu: &mut Cpu, text: &str) -> Result<()> {
+    let snapshot: Value = serde_json::from_str(text).context("Invalid JSON snapshot")?;
+
+    if snapshot["format"] != FORMAT {
+        bail!("Not an apple-ie snapshot");
+    }
+    match snapshot["version"].as_u64() {
+        Some(VERSION) => (),
+        Some(version) => bail!(
+            "Snapshot version {} is not supported, only version {}",
+            version,
+            VERSION
+        ),
+        None => bail!("Snapshot has no version"),
+    }
+
+    let variant: Variant = choice(&snapshot, "variant")?;
+    let illegal_opcodes: IllegalOpcodePolicy = choice(&snapshot, "illegal_opcodes")?;
+
+    let registers = &snapshot["registers"];
+    let registers = Registers {
+        accumulator: number(registers, "a")?,
+        x: number(registers, "x")?,
+        y: number(registers, "y")?,
+        status: StatusFlags::from_bits_retain(number(registers, "p")?),
+        stack_pointer: number(registers, "sp")?,
+        program_counter: number(registers, "pc")?,
+    };
+    let cycles: u64 = number(&snapshot, "cycles")?;
+
+    let memory = bytes(&snapshot, "memory")?;
+    if memory.len() != MEMORY_SIZE {
+        bail!(
+            "Snapshot memory is {} bytes, not {}",
+            memory.len(),
+            MEMORY_SIZE
+        );
+    }
+
+    let state = &snapshot["pia"];
+    let mut display = Display::new();
+    let rows = snapshot["display"]
+        .as_array()
+        .context("Snapshot has no display")?
+        .iter()
+        .map(|row| row.as_str().map(str::to_string))
+        .collect::<Option<Vec<String>>>()
+        .context("Snapshot display lines must be text")?;
+    display.set_rows(rows);
+    let pia = Pia {
+        keys: bytes(state, "keys")?.into(),
+        key: number(state, "key")?,
+        keyboard_control: number(state, "keyboard_control")?,
+        display_control: number(state, "display_control")?,
+        display,
+    };
+
+    cpu.variant = variant;
+    cpu.illegal_opcodes = illegal_opcodes;
+    cpu.registers = registers;
+    cpu.cycles = cycles;
+    cpu.memory_mut().ram = memory;
+    cpu.bus.pia = pia;
+    cpu.history.clear();
+    Ok(())
+}
+
+/// Save a snapshot of the machine to a file.
+pub fn write(cpu: &Cpu, path: &Path) -> Result<()> {
+    fs::write(path, save(cpu)).with_context(|| format!("Cannot write {}", path.display()))
+}
+
+/// Restore the machine from a snapshot file.
+pub fn read(cpu: &mut Cpu, path: &Path) -> Result<()> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    restore(cpu, &text).with_context(|| format!("Cannot restore {}", path.display()))
+}
+
+fn number<T: TryFrom<u64>>(object: &Value, key: &str) -> Result<T> {
+    object[key]
+        .as_u64()
+        .and_then(|value| T::try_from(value).ok())
+        .with_context(|| format!("Snapshot `{}` is missing or out of range", key))
+}
+
+/// The name a setting is given on the command line, which snapshots use too.
+fn name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn choice<T: ValueEnum>(object: &Value, key: &str) -> Result<T> {
+    let text = object[key]
+        .as_str()
+        .with_context(|| format!("Snapshot has no `{}`", key))?;
+    T::from_str(text, true).map_err(|_| anyhow!("Snapshot `{}` of `{}` is unknown", key, text))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+fn bytes(object: &Value, key: &str) -> Result<Vec<u8>> {
+    let text = object[key]
+        .as_str()
+        .with_context(|| format!("Snapshot has no `{}`", key))?;
+
+    (0..text.len())
+        .step_by(2)
+        .map(|index| {
+            text.get(index..index + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .with_context(|| format!("Snapshot `{}` is not hex bytes", key))
+}
@@ -0,0 +1,326 @@
+use super::debugger::{Command, Debugger};
+use super::gdb::Server;
+use super::headless::{self, Outcome};
+use super::script::{Script, Step};
+use super::snapshot;
+use apple_ie::assembler::assemble_source;
+use apple_ie::cpu::{breakpoints::Stop, operations::Variant, Cpu, IllegalOpcodePolicy};
+use apple_ie::symbols::SymbolTable;
+
+use anyhow::Result;
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+#[test]
+fn debugger_steps_over_subroutines_and_edits_memory() -> Result<()> {
+    let program: &str = "start:  LDX #$03
+        JSR sub
+loop:   DEX
+        BNE loop
+        STA $0300
+        BRK
+sub:    LDA #$41
+        RTS";
+
+    let assembly = assemble_source("debug.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+    cpu.symbols = SymbolTable::from_values(&assembly.symbols);
+
+    let input = "s\nn\nu loop+3\nw $0300 $C1 $C2\nm $0300 2\nc\nbogus\nq\n";
+    let mut output = vec![];
+    Debugger::new(&mut cpu).run(input.as_bytes(), &mut output)?;
+    let output = String::from_utf8(output)?;
+
+    assert!(output.contains("PC=0805 A=41 X=03"));
+    assert!(output.contains("0300  C1 C2"));
+    assert!(output.contains("BRK at $080B"));
+    assert!(output.contains("unknown command `bogus`"));
+    assert_eq!(cpu.registers.x, 0x00);
+    assert_eq!(cpu.read_addr(0x0300)?, 0x41);
+    Ok(())
+}
+
+#[test]
+fn debugger_interrupts_runs_and_types_keys() -> Result<()> {
+    let program: &str = "wait:   BIT $D011
+        BPL wait
+        LDA $D010
+        BRK";
+
+    let assembly = assemble_source("keys.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+
+    // Nothing has been typed, so only an interrupt stops the first run
+    let input = "c\nk a\nc\nc\nq\n";
+    let mut output = vec![];
+    let mut debugger = Debugger::new(&mut cpu);
+    let interrupt = debugger.interrupt();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(std::time::Duration::from_millis(100));
+        interrupt.store(true, std::sync::atomic::Ordering::Relaxed);
+    });
+    debugger.run(input.as_bytes(), &mut output)?;
+    interrupter.join().unwrap();
+    let output = String::from_utf8(output)?;
+
+    assert!(output.contains("Interrupted at $080"));
+    assert!(output.contains("BRK at $0808"));
+    assert_eq!(cpu.registers.accumulator, 0xC1);
+
+    // The second continue ran the BRK, through the vector at $FFFE to the BRK at $0000
+    assert!(output.contains("BRK at $0000"));
+    assert_eq!(
+        Command::parse("k 10 PRINT\\r", &cpu),
+        Ok(Command::Keys(b"10 PRINT\r".to_vec()))
+    );
+    assert_eq!(
+        Command::parse("m $0800 16", &cpu),
+        Ok(Command::Examine {
+            address: 0x0800,
+            length: 16
+        })
+    );
+    Ok(())
+}
+
+/// Send a GDB packet and read the reply, as a client would.
+fn gdb_exchange(stream: &mut TcpStream, packet: &str) -> String {
+    let sum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    let packet_text = format!("${}#{:02x}", packet, sum);
+    stream.write_all(packet_text.as_bytes()).unwrap();
+    if packet == "k" {
+        return String::new();
+    }
+
+    let mut reply = vec![];
+    let mut byte = [0];
+    while byte[0] != b'#' {
+        stream.read_exact(&mut byte).unwrap();
+        if byte[0] != b'+' && byte[0] != b'$' {
+            reply.push(byte[0]);
+        }
+    }
+    stream.read_exact(&mut [0; 2]).unwrap();
+    stream.write_all(b"+").unwrap();
+
+    reply.pop();
+    String::from_utf8(reply).unwrap()
+}
+
+#[test]
+fn gdb_server_drives_the_cpu() -> Result<()> {
+    let program: &str = "start:  LDX #$00
+loop:   INX
+        STX $10
+        CPX #$05
+        BNE loop
+        BRK";
+
+    let assembly = assemble_source("gdb.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_nodelay(true).unwrap();
+        [
+            "g",
+            "Z0,0805,1",
+            "c",
+            "p5",
+            "z0,0805,1",
+            "Z2,0010,1",
+            "c",
+            "g",
+            "M0300,2:c1c2",
+            "m0300,3",
+            "s",
+            "p1",
+            "k",
+        ]
+        .iter()
+        .map(|packet| gdb_exchange(&mut stream, packet))
+        .collect::<Vec<String>>()
+    });
+
+    let (stream, _) = listener.accept()?;
+    Server::new(&mut cpu).serve(stream)?;
+    let replies = client.join().unwrap();
+
+    assert_eq!(
+        replies,
+        [
+            "00000024fd0008",
+            "OK",
+            "S05",
+            "0508",
+            "OK",
+            "OK",
+            "T05watch:0010;",
+            "00020024fd0508",
+            "OK",
+            "c1c200",
+            "S05",
+            "02",
+            "",
+        ]
+    );
+    assert_eq!(cpu.registers.program_counter, 0x0807);
+    Ok(())
+}
+
+#[test]
+fn snapshots_restore_the_whole_machine() -> Result<()> {
+    let program: &str = "start:  LDX #$00
+print:  LDA msg,X
+        BEQ wait
+        ORA #$80
+        STA $D012
+        INX
+        BNE print
+wait:   LDA $D011
+        BPL wait
+        LDA $D010
+        STA $D012
+        CMP #$D1
+        BNE wait
+        BRK
+msg:    .byte \"HELLO\", $0D, 0";
+
+    let assembly = assemble_source("echo.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.variant = Variant::Cmos;
+    cpu.illegal_opcodes = IllegalOpcodePolicy::Nop;
+    cpu.load(assembly.origin, &assembly.bytes);
+    for key in b"abq" {
+        cpu.bus.pia.press(*key);
+    }
+    assert_eq!(cpu.resume(30, None)?, Stop::Limit);
+
+    let saved = snapshot::save(&cpu);
+    assert_eq!(cpu.resume(u64::MAX, None)?, Stop::Brk { address: 0x081E });
+    let finished = snapshot::save(&cpu);
+    assert!(saved != finished);
+    assert_eq!(cpu.bus.pia.display.text(), "HELLO\nABQ");
+
+    let mut restored = Cpu::new();
+    snapshot::restore(&mut restored, &saved)?;
+    assert_eq!(snapshot::save(&restored), saved);
+    assert_eq!(
+        (restored.variant, restored.illegal_opcodes),
+        (Variant::Cmos, IllegalOpcodePolicy::Nop)
+    );
+    assert_eq!(
+        restored.resume(u64::MAX, None)?,
+        Stop::Brk { address: 0x081E }
+    );
+    assert_eq!(snapshot::save(&restored), finished);
+
+    // Other versions are refused, including the first, which left out the processor
+    for version in [1, 99] {
+        let other = saved.replace("\"version\": 2", &format!("\"version\": {}", version));
+        assert!(other != saved);
+        assert!(snapshot::restore(&mut restored, &other).is_err());
+    }
+    assert_eq!(snapshot::save(&restored), finished);
+    Ok(())
+}
+
+#[test]
+fn headless_runs_stop_on_conditions() -> Result<()> {
+    let program: &str = "start:  LDX #$00
+print:  LDA msg,X
+        BEQ done
+        ORA #$80
+        STA $D012
+        INX
+        BNE print
+done:   STX $10
+        BRK
+msg:    .byte \"HELLO\", $0D, \"WORLD\", 0";
+
+    let assembly = assemble_source("headless.s", program)?;
+    let symbols = SymbolTable::from_values(&assembly.symbols);
+    let run = |conditions: &[headless::Condition], max_cycles| -> Result<(Outcome, String)> {
+        let mut cpu = Cpu::new();
+        cpu.load(assembly.origin, &assembly.bytes);
+        let mut output = vec![];
+        let outcome = headless::run(
+            &mut cpu,
+            conditions,
+            max_cycles,
+            &mut Script::default(),
+            &mut output,
+        )?;
+        Ok((outcome, String::from_utf8(output)?))
+    };
+
+    let (outcome, output) = run(&[], None)?;
+    assert_eq!(outcome, Outcome::Brk(0x0811));
+    assert_eq!(output, "HELLO\nWORLD");
+    assert_eq!(outcome.exit_code(false), 0);
+    assert_eq!(outcome.exit_code(true), 3);
+
+    let printed = headless::Condition::Output("LO\nWO".to_string());
+    let (outcome, output) = run(std::slice::from_ref(&printed), None)?;
+    assert_eq!(outcome, Outcome::Met(printed));
+    assert_eq!(output, "HELLO\nWO");
+
+    let stored = headless::Condition::memory("$10=11", &symbols).map_err(anyhow::Error::msg)?;
+    let reached = headless::Condition::pc("done", &symbols).map_err(anyhow::Error::msg)?;
+    assert_eq!(
+        run(&[stored.clone(), reached.clone()], None)?.0,
+        Outcome::Met(reached)
+    );
+    assert_eq!(run(&[stored], Some(20))?.0, Outcome::CycleLimit(20));
+    assert!(headless::Condition::pc("nowhere", &symbols).is_err());
+    Ok(())
+}
+
+#[test]
+fn scripts_type_on_the_keyboard() -> Result<()> {
+    let program: &str = "start:  LDA #$BF
+        STA $D012
+wait:   LDA $D011
+        BPL wait
+        LDA $D010
+        CMP #$D1
+        BEQ done
+        STA $D012
+        JMP wait
+done:   BRK";
+
+    let assembly = assemble_source("script.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+
+    let mut script =
+        Script::parse("# echo a line\nwait ?\ndelay 1ms\nline ab\nwait AB\\r\ntype q")?;
+    assert_eq!(script.steps()[2], Step::Type(b"ab\r".to_vec()));
+    assert_eq!(script.steps()[1], Step::Delay(1_000));
+    assert_eq!(script.steps()[3], Step::Wait("AB\n".to_string()));
+
+    let mut output = vec![];
+    let outcome = headless::run(&mut cpu, &[], Some(100_000), &mut script, &mut output)?;
+    assert!(matches!(outcome, Outcome::Brk(_)));
+    assert!(script.steps().is_empty());
+    assert!(cpu.cycles > 1_000);
+    assert_eq!(String::from_utf8(output)?, "?AB\n");
+
+    assert!(Script::parse("delay 99999999999999999ms").is_err());
+    let script = Script::parse_inline(r"wait >;type 1\;2;line \\;type \x1B")?;
+    assert_eq!(script.steps()[1], Step::Type(b"1;2".to_vec()));
+    assert_eq!(script.steps()[2], Step::Type(b"\\\r".to_vec()));
+    assert_eq!(script.steps()[3], Step::Type(b"\x1B".to_vec()));
+
+    let error = Script::parse("wait >\npress a").unwrap_err();
+    assert_eq!(error.to_string(), "line 2: cannot understand `press a`");
+    Ok(())
+}
@@ -0,0 +1,156 @@
+pub mod single_step;
+
+use crate::cpu::Cpu;
+
+use anyhow::{bail, Context, Result};
+use std::{fmt, fs, path::PathBuf};
+
+/// Where test binaries are kept, relative to the crate root.
+pub const FIXTURES: &str = "tests/fixtures";
+
+/// Klaus Dormann's 6502 functional test, assembled with its default settings.
+///
+/// The image covers all 64K and starts at `$0400`. Every test that fails
+/// traps where it failed; all of them passing traps at `$3469`.
+pub const FUNCTIONAL_TEST: Fixture = Fixture {
+    name: "6502_functional_test.bin",
+    load: 0x0000,
+    start: 0x0400,
+    success: Success::TrapAt(0x3469),
+    max_cycles: 200_000_000,
+};
+
+/// Klaus Dormann's decimal mode test, assembled at `$0200`.
+///
+/// It ends at a trap or `BRK`, leaving `ERROR` at `$000B` zero when every
+/// `ADC` and `SBC` result and flag was right.
+pub const DECIMAL_TEST: Fixture = Fixture {
+    name: "6502_decimal_test.bin",
+    load: 0x0200,
+    start: 0x0200,
+    success: Success::ZeroAt(0x000B),
+    max_cycles: 200_000_000,
+};
+
+/// How a test program shows that it passed once it stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Success {
+    /// It traps at this address.
+    TrapAt(u16),
+    /// It leaves zero at this address.
+    ZeroAt(u16),
+}
+
+/// A test program that reports by looping on itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fixture {
+    /// The file name in `FIXTURES`.
+    pub name: &'static str,
+    /// Where the image is loaded.
+    pub load: u16,
+    /// Where execution starts.
+    pub start: u16,
+    pub success: Success,
+    /// The most cycles the program should need.
+    pub max_cycles: u64,
+}
+
+/// Where a test program stopped and whether that means it passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    /// The address of the trap or `BRK` it stopped at.
+    pub address: u16,
+    pub cycles: u64,
+    pub passed: bool,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verdict = if self.passed { "passed" } else { "failed" };
+        write!(
+            f,
+            "{}, stopping at ${:04X} after {} cycles",
+            verdict, self.address, self.cycles
+        )
+    }
+}
+
+impl Fixture {
+    /// The path of the image.
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join(FIXTURES)
+            .join(self.name)
+    }
+
+    /// Read the image, if it has been put in `FIXTURES`.
+    pub fn read(&self) -> Option<Vec<u8>> {
+        fs::read(self.path()).ok()
+    }
+
+    /// Load an image and run it until it stops.
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - The CPU to run on.
+    /// * `image` - The assembled test program.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing where the program stopped, or an error if an
+    /// instruction failed or it never stopped.
+    pub fn run(&self, cpu: &mut Cpu, image: &[u8]) -> Result<Report> {
+        cpu.load(self.load, image);
+        cpu.registers.program_counter = self.start;
+
+        let stop_at_brk = matches!(self.success, Success::ZeroAt(_));
+        let address = run_to_trap(cpu, self.max_cycles, stop_at_brk)
+            .with_context(|| format!("{} did not finish", self.name))?;
+
+        let passed = match self.success {
+            Success::TrapAt(success) => address == success,
+            Success::ZeroAt(flag) => cpu.read_addr(flag)? == 0,
+        };
+        Ok(Report {
+            address,
+            cycles: cpu.cycles,
+            passed,
+        })
+    }
+}
+
+/// Run until an instruction leaves the PC where it was, as `JMP *` or a branch to itself does.
+///
+/// Unlike `Cpu::resume`, `BRK` is executed rather than stopped at, since
+/// test programs check it too.
+///
+/// # Arguments
+///
+/// * `cpu` - The CPU, ready to run.
+/// * `max_cycles` - The cycle count to give up at.
+/// * `stop_at_brk` - Whether a `BRK` counts as a trap, for programs that end with one.
+///
+/// # Returns
+///
+/// A `Result` containing the address of the trap.
+pub fn run_to_trap(cpu: &mut Cpu, max_cycles: u64, stop_at_brk: bool) -> Result<u16> {
+    while cpu.cycles < max_cycles {
+        let address = cpu.registers.program_counter;
+        if stop_at_brk && cpu.read_addr(address)? == crate::cpu::BRK_OPCODE {
+            return Ok(address);
+        }
+
+        cpu.step()
+            .with_context(|| format!("Instruction at ${:04X} failed", address))?;
+
+        if cpu.registers.program_counter == address {
+            return Ok(address);
+        }
+    }
+
+    bail!(
+        "No trap within {} cycles, at ${:04X}",
+        max_cycles,
+        cpu.registers.program_counter
+    )
+}
@@ -0,0 +1,260 @@
+use crate::cpu::{operations::Variant, Cpu, IllegalOpcodePolicy, Registers, StatusFlags};
+use crate::pia::Pia;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::{fmt, fs, path::Path};
+
+/// Where single-step test files for the NMOS 6502 are kept, relative to the crate root.
+pub const FIXTURES: &str = "tests/fixtures/single_step";
+
+/// Where single-step test files for the 65C02 are kept, relative to the crate root.
+pub const CMOS_FIXTURES: &str = "tests/fixtures/single_step/65c02";
+
+/// Status bits with no latch in the processor, which test files disagree on.
+const UNLATCHED: u8 = StatusFlags::BREAK.bits() | StatusFlags::UNUSED.bits();
+
+/// The registers and the memory that matters, before or after a test instruction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct State {
+    pub pc: u16,
+    pub s: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    /// Addresses and the bytes they hold.
+    pub ram: Vec<(u16, u8)>,
+}
+
+/// One instruction to run from a known state, and what it should leave behind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub name: String,
+    pub initial: State,
+    pub expected: State,
+    /// How many cycles the instruction takes, one for each bus access listed.
+    pub cycles: u64,
+}
+
+/// What running a case showed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Passed,
+    /// Each way the result differed from the one expected.
+    Failed(Vec<String>),
+    /// The case could not be run faithfully, and why.
+    Skipped(String),
+}
+
+impl Case {
+    /// Run the instruction on a fresh CPU and compare what it leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `variant` - The processor the case was recorded on.
+    ///
+    /// Stable undocumented opcodes are emulated, since test files cover them
+    /// too; the unstable ones fail.
+    /// The `B` and unused status bits are not compared in the registers,
+    /// though they are in bytes pushed to the stack. Cases touching the PIA
+    /// are skipped, since its registers are not plain memory.
+    pub fn run(&self, variant: Variant) -> Verdict {
+        let mut addresses = self.initial.ram.iter().chain(&self.expected.ram);
+        if let Some((address, _)) = addresses.find(|(address, _)| Pia::contains(*address)) {
+            return Verdict::Skipped(format!("${:04X} is a PIA register", address));
+        }
+
+        let mut cpu = Cpu::new();
+        cpu.variant = variant;
+        cpu.illegal_opcodes = IllegalOpcodePolicy::Emulate;
+        let initial = &self.initial;
+        cpu.registers = Registers {
+            accumulator: initial.a,
+            x: initial.x,
+            y: initial.y,
+            program_counter: initial.pc,
+            status: StatusFlags::from_bits_retain(initial.p),
+            stack_pointer: initial.s,
+        };
+        for (address, value) in &initial.ram {
+            cpu.memory_mut().write(*address, *value);
+        }
+
+        if let Err(error) = cpu.step() {
+            return Verdict::Failed(vec![error.to_string()]);
+        }
+
+        let expected = &self.expected;
+        let registers = &cpu.registers;
+        let mut differences = vec![];
+        let mut compare = |name: &str, actual: u16, expected: u16| {
+            if actual != expected {
+                differences.push(format!(
+                    "{} is ${:02X}, not ${:02X}",
+                    name, actual, expected
+                ));
+            }
+        };
+        compare("PC", registers.program_counter, expected.pc);
+        compare("S", registers.stack_pointer.into(), expected.s.into());
+        compare("A", registers.accumulator.into(), expected.a.into());
+        compare("X", registers.x.into(), expected.x.into());
+        compare("Y", registers.y.into(), expected.y.into());
+        compare(
+            "P",
+            (registers.status.bits() & !UNLATCHED).into(),
+            (expected.p & !UNLATCHED).into(),
+        );
+        for (address, value) in &expected.ram {
+            let actual = cpu.memory().read(*address).unwrap_or_default();
+            compare(&format!("${:04X}", address), actual.into(), (*value).into());
+        }
+        if cpu.cycles != self.cycles {
+            differences.push(format!("took {} cycles, not {}", cpu.cycles, self.cycles));
+        }
+
+        if differences.is_empty() {
+            Verdict::Passed
+        } else {
+            Verdict::Failed(differences)
+        }
+    }
+}
+
+/// Parse a file of cases in the single-step test format: a JSON array of
+/// objects with `name`, `initial` and `final` states and the `cycles` taken.
+///
+/// # Returns
+///
+/// A `Result` containing the cases, or an error saying which one is malformed.
+pub fn parse(text: &str) -> Result<Vec<Case>> {
+    let cases: Value = serde_json::from_str(text).context("Invalid JSON test file")?;
+
+    cases
+        .as_array()
+        .context("A test file must hold an array of cases")?
+        .iter()
+        .enumerate()
+        .map(|(index, case)| {
+            let name = case["name"].as_str().unwrap_or_default().to_string();
+            let parsed = (|| -> Result<Case> {
+                Ok(Case {
+                    initial: state(&case["initial"]).context("Bad initial state")?,
+                    expected: state(&case["final"]).context("Bad final state")?,
+                    cycles: case["cycles"].as_array().context("No cycles")?.len() as u64,
+                    name: name.clone(),
+                })
+            })();
+            parsed.with_context(|| format!("Case {} `{}`", index + 1, name))
+        })
+        .collect()
+}
+
+/// Read the cases in a test file.
+pub fn read(path: &Path) -> Result<Vec<Case>> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    parse(&text).with_context(|| format!("Cannot load {}", path.display()))
+}
+
+/// The results of running many cases.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub passed: usize,
+    pub skipped: usize,
+    /// The name of each case that failed, with how.
+    pub failures: Vec<(String, Vec<String>)>,
+}
+
+impl Summary {
+    /// Run cases recorded on a processor, adding their results to the summary.
+    pub fn run(&mut self, cases: &[Case], variant: Variant) {
+        for case in cases {
+            match case.run(variant) {
+                Verdict::Passed => self.passed += 1,
+                Verdict::Skipped(_) => self.skipped += 1,
+                Verdict::Failed(differences) => {
+                    self.failures.push((case.name.clone(), differences))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped",
+            self.passed,
+            self.failures.len(),
+            self.skipped
+        )?;
+        for (name, differences) in &self.failures {
+            write!(f, "\n{}: {}", name, differences.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every `.json` test file in a directory, in name order.
+///
+/// # Arguments
+///
+/// * `directory` - The directory holding the test files.
+/// * `variant` - The processor the files were recorded on.
+///
+/// # Returns
+///
+/// A `Result` containing the summary, or an error if a file cannot be read.
+pub fn run_directory(directory: &Path, variant: Variant) -> Result<Summary> {
+    let entries =
+        fs::read_dir(directory).with_context(|| format!("Cannot read {}", directory.display()))?;
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "json")
+    });
+    paths.sort();
+
+    let mut summary = Summary::default();
+    for path in paths {
+        summary.run(&read(&path)?, variant);
+    }
+    Ok(summary)
+}
+
+fn state(object: &Value) -> Result<State> {
+    let ram = object["ram"]
+        .as_array()
+        .context("No ram")?
+        .iter()
+        .map(|pair| {
+            Some((
+                u16::try_from(pair.get(0)?.as_u64()?).ok()?,
+                u8::try_from(pair.get(1)?.as_u64()?).ok()?,
+            ))
+        })
+        .collect::<Option<Vec<_>>>()
+        .context("ram must be [address, byte] pairs")?;
+
+    Ok(State {
+        pc: number(object, "pc")?,
+        s: number(object, "s")?,
+        a: number(object, "a")?,
+        x: number(object, "x")?,
+        y: number(object, "y")?,
+        p: number(object, "p")?,
+        ram,
+    })
+}
+
+fn number<T: TryFrom<u64>>(object: &Value, key: &str) -> Result<T> {
+    object[key]
+        .as_u64()
+        .and_then(|value| T::try_from(value).ok())
+        .with_context(|| format!("`{}` is missing or out of range", key))
+}
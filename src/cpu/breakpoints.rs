@@ -0,0 +1,381 @@
+use super::Registers;
+use crate::assembler::{self, diagnostic::SourceError, expression::Expr};
+
+use std::{collections::BTreeMap, fmt, ops::RangeInclusive};
+
+/// The kinds of memory access a watchpoint can stop on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// Either a read or a write.
+    Any,
+}
+
+impl Access {
+    fn covers(self, access: Access) -> bool {
+        self == Access::Any || self == access
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+            Access::Any => write!(f, "access"),
+        }
+    }
+}
+
+/// An expression that must be true for a breakpoint or watchpoint to stop.
+///
+/// Conditions use the operand expression syntax, with the registers `A`, `X`,
+/// `Y`, `SP`, `PC` and `P`, the flags `C`, `Z`, `I`, `D`, `V` and `N` as 0 or 1,
+/// and, for watchpoints, the `ADDRESS` and `VALUE` of the access.
+/// They are parsed once, since watchpoint conditions run on every access.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    text: String,
+    expr: Expr,
+}
+
+/// The names a condition may use.
+const NAMES: [&str; 14] = [
+    "A", "X", "Y", "SP", "PC", "P", "ADDRESS", "VALUE", "C", "Z", "I", "D", "V", "N",
+];
+
+impl Condition {
+    /// Parse a condition, checking it for mistakes such as unknown names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::cpu::breakpoints::Condition;
+    /// let condition = Condition::new("A == $FF && C").unwrap();
+    /// assert!(Condition::new("Q == 1").is_err());
+    /// ```
+    pub fn new(text: &str) -> Result<Self, String> {
+        let text = text.trim().to_string();
+        let expr = assembler::parse(&text)?;
+        check_names(&expr)?;
+        Ok(Condition { text, expr })
+    }
+
+    /// Evaluate the condition, treating any value but zero as true.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing whether it holds, or why it could not be worked
+    /// out, such as a division by zero.
+    pub fn holds(&self, registers: &Registers, access: Option<(u16, u8)>) -> Result<bool, String> {
+        let flag = |letter: char| {
+            let bit = 7 - "NV-BDIZC".find(letter).unwrap_or_default();
+            i64::from(registers.status.bits() >> bit & 1)
+        };
+        let (address, value) = access.unwrap_or_default();
+
+        let lookup = |name: &str, span| {
+            let name = NAMES
+                .iter()
+                .find(|known| known.eq_ignore_ascii_case(name))
+                .ok_or_else(|| SourceError::new(span, format!("`{}` is not a known name", name)))?;
+            Ok(match *name {
+                "A" => i64::from(registers.accumulator),
+                "X" => i64::from(registers.x),
+                "Y" => i64::from(registers.y),
+                "SP" => i64::from(registers.stack_pointer),
+                "PC" => i64::from(registers.program_counter),
+                "P" => i64::from(registers.status.bits()),
+                "ADDRESS" => i64::from(address),
+                "VALUE" => i64::from(value),
+                flag_name => flag(flag_name.chars().next().unwrap_or_default()),
+            })
+        };
+
+        self.expr
+            .evaluate(0, &lookup)
+            .map(|value| value != 0)
+            .map_err(|error| error.message)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Make sure every name in a condition is one it may use.
+fn check_names(expr: &Expr) -> Result<(), String> {
+    match expr {
+        Expr::Symbol { name, .. }
+            if !NAMES.iter().any(|known| known.eq_ignore_ascii_case(name)) =>
+        {
+            Err(format!("`{}` is not a known name", name))
+        }
+        Expr::Current { .. } => Err("`*` has no meaning in a condition".to_string()),
+        Expr::Unary { operand, .. } => check_names(operand),
+        Expr::Binary { left, right, .. } => {
+            check_names(left)?;
+            check_names(right)
+        }
+        Expr::Number { .. } | Expr::Symbol { .. } => Ok(()),
+    }
+}
+
+/// Stops execution before the instruction at an address runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub condition: Option<Condition>,
+    /// How many hits to let pass before stopping.
+    pub ignore: u64,
+    /// How many times the breakpoint has been reached with its condition true.
+    pub hits: u64,
+}
+
+impl Breakpoint {
+    pub fn at(address: u16) -> Self {
+        Breakpoint {
+            address,
+            condition: None,
+            ignore: 0,
+            hits: 0,
+        }
+    }
+}
+
+/// Stops execution after an instruction reads or writes a range of addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub access: Access,
+    pub condition: Option<Condition>,
+    /// How many hits to let pass before stopping.
+    pub ignore: u64,
+    /// How many matching accesses there have been with the condition true.
+    pub hits: u64,
+}
+
+impl Watchpoint {
+    pub fn on(range: RangeInclusive<u16>, access: Access) -> Self {
+        Watchpoint {
+            range,
+            access,
+            condition: None,
+            ignore: 0,
+            hits: 0,
+        }
+    }
+}
+
+/// A breakpoint or watchpoint, as kept by `Breakpoints`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Point {
+    Breakpoint(Breakpoint),
+    Watchpoint(Watchpoint),
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (condition, ignore, hits) = match self {
+            Point::Breakpoint(point) => {
+                write!(f, "break at ${:04X}", point.address)?;
+                (&point.condition, point.ignore, point.hits)
+            }
+            Point::Watchpoint(point) => {
+                write!(f, "watch {} of ${:04X}", point.access, point.range.start())?;
+                if point.range.start() != point.range.end() {
+                    write!(f, "..${:04X}", point.range.end())?;
+                }
+                (&point.condition, point.ignore, point.hits)
+            }
+        };
+
+        if let Some(condition) = condition {
+            write!(f, " if {}", condition)?;
+        }
+        if ignore > 0 {
+            write!(f, ", ignoring {}", ignore)?;
+        }
+        write!(f, ", hit {} times", hits)
+    }
+}
+
+/// Why `Cpu::resume` stopped running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// A breakpoint was reached, before its instruction ran.
+    Breakpoint { id: usize, address: u16 },
+    /// A watched address was accessed, by the instruction that just ran.
+    Watchpoint {
+        id: usize,
+        address: u16,
+        value: u8,
+        access: Access,
+    },
+    /// The condition of a breakpoint or watchpoint could not be evaluated.
+    Condition {
+        id: usize,
+        address: u16,
+        message: String,
+    },
+    /// A `BRK` was reached, before it ran.
+    Brk { address: u16 },
+    /// The address being run to was reached.
+    Reached { address: u16 },
+    /// The given number of instructions ran without anything stopping them.
+    Limit,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Breakpoint { id, address } => write!(f, "Breakpoint {} at ${:04X}", id, address),
+            Stop::Watchpoint {
+                id,
+                address,
+                value,
+                access,
+            } => write!(
+                f,
+                "Watchpoint {}: {} of ${:02X} at ${:04X}",
+                id, access, value, address
+            ),
+            Stop::Condition {
+                id,
+                address,
+                message,
+            } => write!(
+                f,
+                "Condition of {} failed at ${:04X}: {}",
+                id, address, message
+            ),
+            Stop::Brk { address } => write!(f, "BRK at ${:04X}", address),
+            Stop::Reached { address } => write!(f, "Reached ${:04X}", address),
+            Stop::Limit => write!(f, "Instruction limit reached"),
+        }
+    }
+}
+
+/// The breakpoints and watchpoints of a CPU, numbered from 1.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Breakpoints {
+    points: BTreeMap<usize, Point>,
+    next: usize,
+    /// A watchpoint hit during the current instruction, reported once it finishes.
+    hit: Option<Stop>,
+}
+
+impl Breakpoints {
+    /// Add a breakpoint or watchpoint.
+    ///
+    /// # Returns
+    ///
+    /// The number it is known by, for `remove`.
+    pub fn add(&mut self, point: Point) -> usize {
+        self.next += 1;
+        self.points.insert(self.next, point);
+        self.next
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Point> {
+        self.points.remove(&id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Point> {
+        self.points.get_mut(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Iterate over the points in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Point)> {
+        self.points.iter().map(|(id, point)| (*id, point))
+    }
+
+    /// Check for a breakpoint that stops the instruction about to run, counting its hit.
+    pub fn check(&mut self, registers: &Registers) -> Option<Stop> {
+        let address = registers.program_counter;
+
+        for (id, point) in &mut self.points {
+            let Point::Breakpoint(breakpoint) = point else {
+                continue;
+            };
+            if breakpoint.address != address {
+                continue;
+            }
+            match holds(&breakpoint.condition, registers, None) {
+                Ok(true) => (),
+                Ok(false) => continue,
+                Err(message) => {
+                    return Some(Stop::Condition {
+                        id: *id,
+                        address,
+                        message,
+                    })
+                }
+            }
+
+            breakpoint.hits += 1;
+            if breakpoint.hits > breakpoint.ignore {
+                return Some(Stop::Breakpoint { id: *id, address });
+            }
+        }
+
+        None
+    }
+
+    /// Note a memory access by the running instruction, in case it is watched.
+    pub fn access(&mut self, registers: &Registers, address: u16, value: u8, access: Access) {
+        for (id, point) in &mut self.points {
+            let Point::Watchpoint(watchpoint) = point else {
+                continue;
+            };
+            if !watchpoint.range.contains(&address) || !watchpoint.access.covers(access) {
+                continue;
+            }
+            match holds(&watchpoint.condition, registers, Some((address, value))) {
+                Ok(true) => (),
+                Ok(false) => continue,
+                Err(message) => {
+                    self.hit.get_or_insert(Stop::Condition {
+                        id: *id,
+                        address,
+                        message,
+                    });
+                    continue;
+                }
+            }
+
+            watchpoint.hits += 1;
+            if watchpoint.hits > watchpoint.ignore && self.hit.is_none() {
+                self.hit = Some(Stop::Watchpoint {
+                    id: *id,
+                    address,
+                    value,
+                    access,
+                });
+            }
+        }
+    }
+
+    /// Take the watchpoint hit by the last instruction, if any.
+    pub fn take_hit(&mut self) -> Option<Stop> {
+        self.hit.take()
+    }
+}
+
+fn holds(
+    condition: &Option<Condition>,
+    registers: &Registers,
+    access: Option<(u16, u8)>,
+) -> Result<bool, String> {
+    match condition {
+        Some(condition) => condition.holds(registers, access),
+        None => Ok(true),
+    }
+}
@@ -0,0 +1,49 @@
+use super::breakpoints::Stop;
+
+use std::{error, fmt};
+
+/// Why the CPU could not carry on executing.
+///
+/// Unlike the errors elsewhere, these are meant to be matched on, so that a
+/// caller can tell a program that went wrong from one that was stopped.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::{Cpu, CpuError};
+/// let mut cpu = Cpu::new();
+/// cpu.load(0x0800, &[0x02]);
+/// match cpu.step() {
+///     Err(CpuError::IllegalOpcode { pc, opcode }) => assert_eq!((pc, opcode), (0x0800, 0x02)),
+///     other => panic!("{:?}", other),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpuError {
+    /// The byte at the PC is not an instruction.
+    IllegalOpcode { pc: u16, opcode: u8 },
+    /// Nothing answered at an address.
+    BusFault { address: u16 },
+    /// A `BRK` stopped a program run with `Cpu::fde`.
+    Halted { pc: u16 },
+    /// A breakpoint or watchpoint stopped a program run with `Cpu::fde`.
+    Breakpoint(Stop),
+    /// The trace file could not be written.
+    Trace(String),
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::IllegalOpcode { pc, opcode } => {
+                write!(f, "Illegal opcode ${:02X} at ${:04X}", opcode, pc)
+            }
+            CpuError::BusFault { address } => write!(f, "Bus fault at ${:04X}", address),
+            CpuError::Halted { pc } => write!(f, "Halted by BRK at ${:04X}", pc),
+            CpuError::Breakpoint(stop) => write!(f, "{}", stop),
+            CpuError::Trace(error) => write!(f, "Cannot write the trace: {}", error),
+        }
+    }
+}
+
+impl error::Error for CpuError {}
@@ -0,0 +1,100 @@
+use super::Registers;
+use crate::pia::Pia;
+
+use std::collections::VecDeque;
+
+/// What an instruction changed, so that it can be undone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The registers before the instruction ran.
+    pub registers: Registers,
+    /// The cycle count before the instruction ran.
+    pub cycles: u64,
+    /// Each address written, with the value it held before, in order.
+    pub writes: Vec<(u16, u8)>,
+    /// The PIA before the instruction first read or wrote one of its
+    /// registers, since that takes keys and prints characters.
+    pub pia: Option<Pia>,
+}
+
+/// A ring buffer of the most recent instructions executed, for stepping backwards.
+///
+/// Nothing is recorded while the capacity is zero, which it is by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct History {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change how many instructions are kept, forgetting the oldest if there are too many.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The cycle count of the oldest state that can be returned to.
+    pub fn earliest(&self) -> Option<u64> {
+        self.entries.front().map(|entry| entry.cycles)
+    }
+
+    /// Start recording an instruction about to run.
+    pub fn begin(&mut self, registers: Registers, cycles: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            registers,
+            cycles,
+            writes: vec![],
+            pia: None,
+        });
+    }
+
+    /// Note the value an address held before the running instruction wrote to it.
+    pub fn write(&mut self, address: u16, previous: u8) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.writes.push((address, previous));
+        }
+    }
+
+    /// Keep the PIA as it was before the running instruction first uses it.
+    pub fn access_pia(&mut self, pia: &Pia) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.pia.get_or_insert_with(|| pia.clone());
+        }
+    }
+
+    /// Take the most recent instruction, to undo it.
+    pub fn pop(&mut self) -> Option<Entry> {
+        self.entries.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
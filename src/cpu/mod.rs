@@ -1,16 +1,57 @@
+use crate::bus::Bus;
+use crate::disassembler::{decode_with, Instruction};
 use crate::memory::Memory;
-use operations::{get_instruction, AddressingMode, InstructionInfo, OpSize, Opcode};
+use crate::pia::Pia;
+use crate::symbols::SymbolTable;
+use breakpoints::{Access, Breakpoints, Stop};
+pub use error::CpuError;
+use history::History;
+use operations::{
+    get_cmos_nop, get_instruction, get_undocumented, AddressingMode, InstructionInfo, OpSize,
+    Opcode, Variant,
+};
+use trace::Tracer;
 
-use anyhow::{bail, Result};
 use bitflags::bitflags;
-use log::info;
-use std::{thread, time};
+use clap::ValueEnum;
+use log::{debug, error, info};
+use std::fmt;
 
+pub mod breakpoints;
+pub mod error;
+pub mod history;
 pub mod operations;
+pub mod trace;
 
 const _CPU_CLOCK_RATE: u32 = 1_000_000; // 1 MHz
 const _DEFAULT_FLAGS: u8 = 0b0011_0000;
 
+/// The page the stack lives in.
+const STACK_PAGE: u16 = 0x0100;
+
+/// Where the address of the `BRK` and IRQ handler is stored.
+const IRQ_VECTOR: u16 = 0xFFFE;
+
+/// The opcode of `BRK`, which ends programs run with `Cpu::run`.
+pub const BRK_OPCODE: u8 = 0x00;
+
+type Result<T> = std::result::Result<T, CpuError>;
+
+/// What the CPU does on reaching an opcode the 6502 does not document.
+///
+/// The 65C02 runs every opcode it leaves undefined as a `NOP`, so for it
+/// `Nop` and `Emulate` are the same.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum IllegalOpcodePolicy {
+    /// Stop with `CpuError::IllegalOpcode`.
+    #[default]
+    Halt,
+    /// Skip it as a `NOP` as long as the instruction it decodes to.
+    Nop,
+    /// Run the stable undocumented NMOS opcodes as the chip does, halting on the rest.
+    Emulate,
+}
+
 bitflags! {
     #[repr(transparent)]
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -23,10 +64,27 @@ bitflags! {
         const UNUSED     = 0b0010_0000;
         const OVERFLOW   = 0b0100_0000;
         const NEGATIVE   = 0b1000_0000;
-        const DEFAULT    = Self::UNUSED.bits() | Self::BREAK.bits();
+        /// The flags after a reset. `BREAK` only exists in copies pushed to the stack.
+        const DEFAULT    = Self::UNUSED.bits() | Self::INTERRUPT.bits();
+    }
+}
+
+impl fmt::Display for StatusFlags {
+    /// Show each flag as a letter, upper case when set, as in `nv-bdIzc`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (bit, letter) in (0..8).rev().zip("NV-BDIZC".chars()) {
+            let set = self.bits() & (1 << bit) != 0;
+            match letter {
+                '-' => write!(f, "-")?,
+                _ if set => write!(f, "{}", letter)?,
+                _ => write!(f, "{}", letter.to_ascii_lowercase())?,
+            }
+        }
+        Ok(())
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Registers {
     pub accumulator: u8,
     pub x: u8,
@@ -36,6 +94,12 @@ pub struct Registers {
     pub stack_pointer: u8,
 }
 
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Registers {
     pub fn new() -> Self {
         Registers {
@@ -44,21 +108,77 @@ impl Registers {
             y: 0,
             program_counter: 0,
             status: StatusFlags::DEFAULT,
-            stack_pointer: 0,
+            stack_pointer: 0xFD,
         }
     }
 }
 
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PC={:04X} A={:02X} X={:02X} Y={:02X} SP={:02X} P={:02X} {}",
+            self.program_counter,
+            self.accumulator,
+            self.x,
+            self.y,
+            self.stack_pointer,
+            self.status.bits(),
+            self.status
+        )
+    }
+}
+
+/// Where an instruction finds its operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    None,
+    Accumulator,
+    Immediate(u8),
+    Address(u16),
+}
+
 pub struct Cpu {
-    registers: Registers,
-    memory: Memory,
+    pub registers: Registers,
+    /// The memory and PIA the CPU reads and writes.
+    pub bus: Bus,
+    /// Clock cycles run since the CPU was created.
+    pub cycles: u64,
+    /// Names shown in place of raw addresses in logs.
+    pub symbols: SymbolTable,
+    /// Where execution stops, checked by `fde` and `resume`.
+    pub breakpoints: Breakpoints,
+    /// Where executed instructions are traced to, if anywhere.
+    pub tracer: Option<Tracer>,
+    /// The recent instructions executed, for stepping backwards.
+    pub history: History,
+    /// What undocumented opcodes do.
+    pub illegal_opcodes: IllegalOpcodePolicy,
+    /// The processor emulated, which decides the instructions and their quirks.
+    pub variant: Variant,
+    /// Where `fde` last stopped at a breakpoint, before running the instruction there.
+    stopped_at: Option<u16>,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cpu {
     pub fn new() -> Self {
         Cpu {
             registers: Registers::new(),
-            memory: Memory::new(),
+            bus: Bus::new(),
+            cycles: 0,
+            symbols: SymbolTable::new(),
+            breakpoints: Breakpoints::default(),
+            tracer: None,
+            history: History::default(),
+            illegal_opcodes: IllegalOpcodePolicy::default(),
+            variant: Variant::default(),
+            stopped_at: None,
         }
     }
 
@@ -69,121 +189,340 @@ impl Cpu {
         }
     }
 
+    /// Read a byte without side effects, as a debugger looks at memory.
     pub fn read_addr(&self, address: u16) -> Result<u8> {
-        match self.memory.read(address) {
-            Some(value) => Ok(value),
-            None => bail!("No such address!"),
-        }
+        self.bus.peek(address).ok_or(CpuError::BusFault { address })
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.bus.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.bus.memory
     }
 
     pub fn load(&mut self, address: u16, buffer: &[u8]) {
-        self.memory.load(address, buffer);
+        self.bus.load(address, buffer);
         self.registers.program_counter = address;
     }
 
     pub fn load_addr(&mut self, address: u16, value: u8) {
-        self.memory.load(address, &[value]);
+        self.bus.write(address, value);
+    }
+
+    /// Disassemble instructions from memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address of the first instruction.
+    /// * `count` - How many instructions to decode, fewer if memory runs out.
+    ///
+    /// # Returns
+    ///
+    /// The decoded instructions.
+    pub fn disassemble(&self, address: u16, count: usize) -> Vec<Instruction> {
+        let mut instructions = Vec::with_capacity(count);
+        let mut address = address;
+
+        for _ in 0..count {
+            let bytes = instruction_bytes(&self.bus.memory, address);
+            if bytes.is_empty() {
+                break;
+            }
+
+            let instruction = decode_with(&bytes, address, |code| self.lookup(code));
+            address = instruction.next();
+            instructions.push(instruction);
+        }
+
+        instructions
     }
 
     pub fn execute(&mut self, instruction_info: &InstructionInfo) -> Result<()> {
         let args = self.get_args(instruction_info.size)?;
+        let (target, crossed) = self.target(instruction_info.addressing_mode, &args)?;
+
+        // Reads that cross a page take a cycle longer, and branches add their own
+        let mut cycles = instruction_info.cycles;
+        if instruction_info.page_cycle
+            && crossed
+            && instruction_info.addressing_mode != AddressingMode::Relative
+        {
+            cycles += 1;
+        }
 
         match instruction_info.opcode {
-            Opcode::Adc => (),
-            Opcode::And => (),
-            Opcode::Asl => (),
-            Opcode::Bcc => (),
-            Opcode::Bcs => (),
-            Opcode::Beq => (),
-            Opcode::Bit => (),
-            Opcode::Bmi => (),
-            Opcode::Bne => (),
-            Opcode::Bpl => (),
-            Opcode::Brk => (),
-            Opcode::Bvc => (),
-            Opcode::Bvs => (),
-            Opcode::Clc => (),
-            Opcode::Cld => (),
-            Opcode::Cli => (),
-            Opcode::Clv => (),
-            Opcode::Cmp => (),
-            Opcode::Cpx => (),
-            Opcode::Cpy => (),
-            Opcode::Dec => (),
-            Opcode::Dex => (),
-            Opcode::Dey => (),
-            Opcode::Eor => (),
-            Opcode::Inc => (),
-            Opcode::Inx => (),
-            Opcode::Iny => (),
-            Opcode::Jmp => (),
-            Opcode::Jsr => (),
-            Opcode::Ida => (),
-            Opcode::Lda => match instruction_info.addressing_mode {
-                AddressingMode::Immediate => {
-                    self.registers.accumulator = args[0];
-                    self.registers.status.toggle(StatusFlags::ZERO);
-                    self.set_status(
-                        StatusFlags::NEGATIVE,
-                        (self.registers.status & StatusFlags::NEGATIVE).bits() > 0,
-                    );
+            Opcode::Adc => {
+                let value = self.operand(target)?;
+                self.add(value);
+                cycles += self.cmos_decimal();
+            }
+            Opcode::And => {
+                let value = self.registers.accumulator & self.operand(target)?;
+                self.set_accumulator(value);
+            }
+            Opcode::Alr => {
+                let value = self.registers.accumulator & self.operand(target)?;
+                let result = self.shift_right(value);
+                self.set_accumulator(result);
+            }
+            Opcode::Anc => {
+                let value = self.registers.accumulator & self.operand(target)?;
+                self.set_accumulator(value);
+                self.set_status(StatusFlags::CARRY, value & 0x80 != 0);
+            }
+            Opcode::Arr => {
+                let value = self.operand(target)?;
+                self.arr(value);
+            }
+            Opcode::Asl => self.modify(target, Cpu::shift_left)?,
+            Opcode::Bcc => cycles += self.branch(!self.get_status(StatusFlags::CARRY), target),
+            Opcode::Bcs => cycles += self.branch(self.get_status(StatusFlags::CARRY), target),
+            Opcode::Beq => cycles += self.branch(self.get_status(StatusFlags::ZERO), target),
+            Opcode::Bit => {
+                let value = self.operand(target)?;
+                self.set_status(StatusFlags::ZERO, self.registers.accumulator & value == 0);
+                // The 65C02's BIT #imm has no memory to take N and V from
+                if instruction_info.addressing_mode != AddressingMode::Immediate {
+                    self.set_status(StatusFlags::NEGATIVE, value & 0x80 != 0);
+                    self.set_status(StatusFlags::OVERFLOW, value & 0x40 != 0);
                 }
-                _ => (),
-            },
-            Opcode::Ldx => (),
-            Opcode::Ldy => (),
-            Opcode::Lsr => (),
-            Opcode::Nop => (),
-            Opcode::Ora => (),
-            Opcode::Pha => (),
-            Opcode::Php => (),
-            Opcode::Pla => (),
-            Opcode::Plp => (),
-            Opcode::Rol => (),
-            Opcode::Ror => (),
-            Opcode::Rti => (),
-            Opcode::Rts => (),
-            Opcode::Sbc => (),
-            Opcode::Sec => (),
-            Opcode::Sed => (),
-            Opcode::Sei => (),
-            Opcode::Sta => {
-                match instruction_info.addressing_mode {
-                    AddressingMode::Absolute => {
-                        let addr = u16::from(args[1]) << 8 | u16::from(args[0]);
-                        self.load_addr(addr, self.registers.accumulator)
-                    }
-                    _ => (),
+            }
+            Opcode::Bmi => cycles += self.branch(self.get_status(StatusFlags::NEGATIVE), target),
+            Opcode::Bne => cycles += self.branch(!self.get_status(StatusFlags::ZERO), target),
+            Opcode::Bpl => cycles += self.branch(!self.get_status(StatusFlags::NEGATIVE), target),
+            Opcode::Brk => {
+                // BRK skips a padding byte, so RTI returns past it
+                let address = self.registers.program_counter.wrapping_add(1);
+                self.push_word(address)?;
+                let status = self.registers.status | StatusFlags::BREAK | StatusFlags::UNUSED;
+                self.push(status.bits())?;
+                self.set_status(StatusFlags::INTERRUPT, true);
+                if self.variant == Variant::Cmos {
+                    self.set_status(StatusFlags::DECIMAL, false);
                 }
+                self.registers.program_counter = self.read_word(IRQ_VECTOR)?;
+            }
+            Opcode::Bra => cycles += self.branch(true, target),
+            Opcode::Bvc => cycles += self.branch(!self.get_status(StatusFlags::OVERFLOW), target),
+            Opcode::Bvs => cycles += self.branch(self.get_status(StatusFlags::OVERFLOW), target),
+            Opcode::Clc => self.set_status(StatusFlags::CARRY, false),
+            Opcode::Cld => self.set_status(StatusFlags::DECIMAL, false),
+            Opcode::Cli => self.set_status(StatusFlags::INTERRUPT, false),
+            Opcode::Clv => self.set_status(StatusFlags::OVERFLOW, false),
+            Opcode::Cmp => self.compare(self.registers.accumulator, target)?,
+            Opcode::Cpx => self.compare(self.registers.x, target)?,
+            Opcode::Cpy => self.compare(self.registers.y, target)?,
+            Opcode::Dcp => {
+                let value = self.read_modify_write(target, |_, value| value.wrapping_sub(1))?;
+                self.compare_value(self.registers.accumulator, value);
+            }
+            Opcode::Dec => self.modify(target, |_, value| value.wrapping_sub(1))?,
+            Opcode::Dex => {
+                self.registers.x = self.registers.x.wrapping_sub(1);
+                self.set_zero_negative(self.registers.x);
+            }
+            Opcode::Dey => {
+                self.registers.y = self.registers.y.wrapping_sub(1);
+                self.set_zero_negative(self.registers.y);
+            }
+            Opcode::Eor => {
+                let value = self.registers.accumulator ^ self.operand(target)?;
+                self.set_accumulator(value);
+            }
+            Opcode::Inc => self.modify(target, |_, value| value.wrapping_add(1))?,
+            Opcode::Isc => {
+                let value = self.read_modify_write(target, |_, value| value.wrapping_add(1))?;
+                self.subtract(value);
+            }
+            Opcode::Inx => {
+                self.registers.x = self.registers.x.wrapping_add(1);
+                self.set_zero_negative(self.registers.x);
+            }
+            Opcode::Iny => {
+                self.registers.y = self.registers.y.wrapping_add(1);
+                self.set_zero_negative(self.registers.y);
+            }
+            Opcode::Jmp => {
+                if let Target::Address(address) = target {
+                    self.registers.program_counter = address;
+                }
+                // The 65C02 spends a cycle fixing the NMOS page wrap
+                if instruction_info.addressing_mode == AddressingMode::Indirect
+                    && self.variant == Variant::Cmos
+                {
+                    cycles += 1;
+                }
+            }
+            Opcode::Jsr => {
+                // The return address pushed is that of the last byte of the JSR
+                let last = self.registers.program_counter.wrapping_sub(1);
+                self.push_word(last)?;
+                if let Target::Address(address) = target {
+                    self.registers.program_counter = address;
+                }
+            }
+            Opcode::Ida => (),
+            Opcode::Las => {
+                let value = self.operand(target)? & self.registers.stack_pointer;
+                self.registers.stack_pointer = value;
+                self.registers.x = value;
+                self.set_accumulator(value);
+            }
+            Opcode::Lax => {
+                let value = self.operand(target)?;
+                self.registers.x = value;
+                self.set_accumulator(value);
+            }
+            Opcode::Lda => {
+                let value = self.operand(target)?;
+                self.set_accumulator(value);
+            }
+            Opcode::Ldx => {
+                self.registers.x = self.operand(target)?;
+                self.set_zero_negative(self.registers.x);
+            }
+            Opcode::Ldy => {
+                self.registers.y = self.operand(target)?;
+                self.set_zero_negative(self.registers.y);
+            }
+            Opcode::Lsr => self.modify(target, Cpu::shift_right)?,
+            Opcode::Nop => (),
+            Opcode::Ora => {
+                let value = self.registers.accumulator | self.operand(target)?;
+                self.set_accumulator(value);
+            }
+            Opcode::Pha => self.push(self.registers.accumulator)?,
+            Opcode::Phx => self.push(self.registers.x)?,
+            Opcode::Phy => self.push(self.registers.y)?,
+            Opcode::Php => {
+                let status = self.registers.status | StatusFlags::BREAK | StatusFlags::UNUSED;
+                self.push(status.bits())?;
+            }
+            Opcode::Pla => {
+                let value = self.pull()?;
+                self.set_accumulator(value);
+            }
+            Opcode::Plp => self.pull_status()?,
+            Opcode::Plx => {
+                self.registers.x = self.pull()?;
+                self.set_zero_negative(self.registers.x);
+            }
+            Opcode::Ply => {
+                self.registers.y = self.pull()?;
+                self.set_zero_negative(self.registers.y);
+            }
+            Opcode::Rla => {
+                let value = self.read_modify_write(target, Cpu::rotate_left)?;
+                self.set_accumulator(self.registers.accumulator & value);
+            }
+            Opcode::Rol => self.modify(target, Cpu::rotate_left)?,
+            Opcode::Ror => self.modify(target, Cpu::rotate_right)?,
+            Opcode::Rra => {
+                let value = self.read_modify_write(target, Cpu::rotate_right)?;
+                self.add(value);
+            }
+            Opcode::Rti => {
+                self.pull_status()?;
+                self.registers.program_counter = self.pull_word()?;
+            }
+            Opcode::Rts => {
+                self.registers.program_counter = self.pull_word()?.wrapping_add(1);
+            }
+            Opcode::Sax => self.store(target, self.registers.accumulator & self.registers.x),
+            Opcode::Sbc => {
+                let value = self.operand(target)?;
+                self.subtract(value);
+                cycles += self.cmos_decimal();
+            }
+            Opcode::Sbx => {
+                let value = self.operand(target)?;
+                let both = self.registers.accumulator & self.registers.x;
+                self.registers.x = both.wrapping_sub(value);
+                self.set_status(StatusFlags::CARRY, both >= value);
+                self.set_zero_negative(self.registers.x);
+            }
+            Opcode::Sec => self.set_status(StatusFlags::CARRY, true),
+            Opcode::Sed => self.set_status(StatusFlags::DECIMAL, true),
+            Opcode::Sei => self.set_status(StatusFlags::INTERRUPT, true),
+            Opcode::Slo => {
+                let value = self.read_modify_write(target, Cpu::shift_left)?;
+                self.set_accumulator(self.registers.accumulator | value);
+            }
+            Opcode::Sre => {
+                let value = self.read_modify_write(target, Cpu::shift_right)?;
+                self.set_accumulator(self.registers.accumulator ^ value);
+            }
+            Opcode::Sta => self.store(target, self.registers.accumulator),
+            Opcode::Stx => self.store(target, self.registers.x),
+            Opcode::Sty => self.store(target, self.registers.y),
+            Opcode::Stz => self.store(target, 0),
+            Opcode::Tax => {
+                self.registers.x = self.registers.accumulator;
+                self.set_zero_negative(self.registers.x);
+            }
+            Opcode::Tay => {
+                self.registers.y = self.registers.accumulator;
+                self.set_zero_negative(self.registers.y);
+            }
+            Opcode::Trb => {
+                self.read_modify_write(target, |cpu, value| {
+                    let accumulator = cpu.registers.accumulator;
+                    cpu.set_status(StatusFlags::ZERO, accumulator & value == 0);
+                    value & !accumulator
+                })?;
+            }
+            Opcode::Tsb => {
+                self.read_modify_write(target, |cpu, value| {
+                    let accumulator = cpu.registers.accumulator;
+                    cpu.set_status(StatusFlags::ZERO, accumulator & value == 0);
+                    value | accumulator
+                })?;
+            }
+            Opcode::Tsx => {
+                self.registers.x = self.registers.stack_pointer;
+                self.set_zero_negative(self.registers.x);
+            }
+            Opcode::Txa => {
+                let value = self.registers.x;
+                self.set_accumulator(value);
+            }
+            Opcode::Txs => self.registers.stack_pointer = self.registers.x,
+            Opcode::Tya => {
+                let value = self.registers.y;
+                self.set_accumulator(value);
+            }
+            // The unstable opcodes are never decoded, so only a caller passing one gets here
+            Opcode::Ane
+            | Opcode::Jam
+            | Opcode::Lxa
+            | Opcode::Sha
+            | Opcode::Shx
+            | Opcode::Shy
+            | Opcode::Tas => {
+                let size = instruction_info.size as u16;
+                return Err(CpuError::IllegalOpcode {
+                    pc: self.registers.program_counter.wrapping_sub(size),
+                    opcode: instruction_info.opcode_value,
+                });
             }
-            Opcode::Stx => (),
-            Opcode::Sty => (),
-            Opcode::Tax => (),
-            Opcode::Tay => (),
-            Opcode::Tsx => (),
-            Opcode::Txa => (),
-            Opcode::Txs => (),
-            Opcode::Tya => (),
         }
 
-        info!(
-            "Instruction {} executed!",
-            instruction_info.opcode.to_string().to_uppercase()
-        );
+        self.cycles += u64::from(cycles);
 
         Ok(())
     }
 
+    /// Fetch, decode and execute a single instruction, stopping at a `BRK` or breakpoint.
+    ///
+    /// A breakpoint that stopped the previous call is passed over, so that
+    /// calling this again carries on from it.
     pub fn fde(&mut self) -> Result<()> {
-        // Fetch
-        let instruction = self.fetch_byte()?;
+        let address = self.registers.program_counter;
 
-        // Decode
-        let instruction_info = self.decode(instruction)?;
-
-        // Execute
         // For testing, if a BRK, then exit
-        if instruction_info.opcode == Opcode::Brk {
+        if self.read_addr(address)? == BRK_OPCODE {
             info!(
                 "CPU Status On Exit - A: {:#04X}, X: {:#04X}, Y: {:#04X}, SP: {:#04X}, PC: {:#04X}",
                 self.registers.accumulator,
@@ -192,52 +531,252 @@ impl Cpu {
                 self.registers.stack_pointer,
                 self.registers.program_counter
             );
+            info!("BRK at {}", self.symbols.describe(address));
             info!("Current memory: {:#04X}", self.read_addr(0x2000)?);
-            bail!("Testing finished: BRK hit!");
+            return Err(CpuError::Halted { pc: address });
         };
 
-        self.execute(&instruction_info)?;
+        if self.stopped_at.take() != Some(address) {
+            if let Some(stop) = self.breakpoints.check(&self.registers) {
+                self.stopped_at = Some(address);
+                return Err(CpuError::Breakpoint(stop));
+            }
+        }
+
+        self.step()?;
 
-        // Slow down for now
-        thread::sleep(time::Duration::from_millis(100));
+        if let Some(stop) = self.breakpoints.take_hit() {
+            return Err(CpuError::Breakpoint(stop));
+        }
 
         Ok(())
     }
 
-    fn fetch_byte(&mut self) -> Result<u8> {
-        info!(
-            "Current address is: {:#04X}",
-            self.registers.program_counter
-        );
+    /// Fetch, decode and execute a single instruction, at full speed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the instruction that was executed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::Cpu;
+    /// let mut cpu = Cpu::new();
+    /// cpu.load(0x0800, &[0xA9, 0x01]);
+    /// cpu.step().unwrap();
+    /// assert_eq!(cpu.registers.accumulator, 0x01);
+    /// ```
+    pub fn step(&mut self) -> Result<InstructionInfo> {
+        let address = self.registers.program_counter;
 
-        let byte = match self.memory.read(self.registers.program_counter) {
-            Some(byte) => byte,
-            None => bail!("Cannot read next value in memory!"),
+        if self
+            .tracer
+            .as_ref()
+            .is_some_and(|tracer| tracer.covers(address))
+        {
+            let bytes = instruction_bytes(&self.bus.memory, address);
+            let instruction = decode_with(&bytes, address, |code| self.lookup(code));
+            if let Some(tracer) = &mut self.tracer {
+                tracer
+                    .record(&instruction, &self.registers, self.cycles)
+                    .map_err(|error| CpuError::Trace(error.to_string()))?;
+            }
+        }
+
+        let registers = self.registers;
+
+        // Fetch
+        let instruction = self.fetch_byte()?;
+
+        // Decode, leaving the PC on an illegal opcode so that the error can be recovered from
+        let instruction_info = match self.decode(address, instruction) {
+            Ok(instruction_info) => instruction_info,
+            Err(error) => {
+                self.registers.program_counter = address;
+                return Err(error);
+            }
         };
+        debug!("{}: {}", self.symbols.describe(address), instruction_info);
 
-        info!("Byte retrieved: {:#04X}", &byte);
+        self.history.begin(registers, self.cycles);
 
-        self.registers.program_counter += 1;
-        info!(
-            "Program counter incremented: {:#04X}",
-            self.registers.program_counter
-        );
+        // Execute
+        self.execute(&instruction_info)?;
 
-        Ok(byte)
+        Ok(instruction_info)
+    }
+
+    /// The breakpoint or `BRK` that stops `resume` before the instruction at the PC, if any.
+    ///
+    /// Callers running in chunks check this between them, since each call to
+    /// `resume` passes over what is at the PC.
+    pub fn pending_stop(&mut self) -> Result<Option<Stop>> {
+        if let Some(stop) = self.breakpoints.check(&self.registers) {
+            return Ok(Some(stop));
+        }
+        let address = self.registers.program_counter;
+        if self.read_addr(address)? == BRK_OPCODE {
+            return Ok(Some(Stop::Brk { address }));
+        }
+        Ok(None)
+    }
+
+    /// Run at full speed until something stops execution.
+    ///
+    /// A breakpoint or `BRK` at the PC when this is called is passed over, so
+    /// that execution can carry on from the one that stopped it.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The most instructions to execute.
+    /// * `until` - An address to stop at once it is reached.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing why execution stopped, or the error an instruction hit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::cpu::{breakpoints::{Breakpoint, Point, Stop}, Cpu};
+    /// let mut cpu = Cpu::new();
+    /// cpu.load(0x0800, &[0xE8, 0xD0, 0xFD, 0x00]);
+    /// let id = cpu.breakpoints.add(Point::Breakpoint(Breakpoint::at(0x0801)));
+    /// assert_eq!(cpu.resume(u64::MAX, None).unwrap(), Stop::Breakpoint { id, address: 0x0801 });
+    /// ```
+    pub fn resume(&mut self, limit: u64, until: Option<u16>) -> Result<Stop> {
+        for count in 0..limit {
+            let address = self.registers.program_counter;
+
+            if count > 0 {
+                if until == Some(address) {
+                    return Ok(Stop::Reached { address });
+                }
+                if let Some(stop) = self.pending_stop()? {
+                    return Ok(stop);
+                }
+            }
+
+            self.step()?;
+
+            if let Some(stop) = self.breakpoints.take_hit() {
+                return Ok(stop);
+            }
+        }
+
+        Ok(Stop::Limit)
+    }
+
+    /// Undo the most recent instruction recorded in the history.
+    ///
+    /// # Returns
+    ///
+    /// Whether there was an instruction to undo.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::Cpu;
+    /// let mut cpu = Cpu::new();
+    /// cpu.history.set_capacity(100);
+    /// cpu.load(0x0800, &[0xA9, 0x01]);
+    /// cpu.step().unwrap();
+    /// assert!(cpu.step_back());
+    /// assert_eq!(cpu.registers.accumulator, 0x00);
+    /// ```
+    pub fn step_back(&mut self) -> bool {
+        let Some(entry) = self.history.pop() else {
+            return false;
+        };
+
+        // Undo the writes latest first, in case an address was written twice
+        for (address, previous) in entry.writes.into_iter().rev() {
+            self.bus.memory.write(address, previous);
+        }
+        if let Some(pia) = entry.pia {
+            self.bus.pia = pia;
+        }
+        self.registers = entry.registers;
+        self.cycles = entry.cycles;
+        true
+    }
+
+    /// Step backwards until the cycle count is no later than a given one.
+    ///
+    /// # Returns
+    ///
+    /// How many instructions were undone, or `None` if the history does not go
+    /// back far enough, in which case nothing is undone.
+    pub fn rewind(&mut self, cycles: u64) -> Option<usize> {
+        if self.cycles > cycles
+            && self
+                .history
+                .earliest()
+                .is_none_or(|earliest| earliest > cycles)
+        {
+            return None;
+        }
+
+        let mut count = 0;
+        while self.cycles > cycles && self.step_back() {
+            count += 1;
+        }
+        Some(count)
     }
 
-    fn decode(&mut self, instruction: u8) -> Result<InstructionInfo> {
-        let instruction_info = match get_instruction(Some(instruction), None, None) {
-            Some(x) => x,
-            None => bail!("Instruction was invalid!"),
+    fn fetch_byte(&mut self) -> Result<u8> {
+        let address = self.registers.program_counter;
+        let byte = match self.bus.memory.read(address) {
+            Some(byte) => byte,
+            None => return Err(CpuError::BusFault { address }),
         };
 
-        info!("Instruction decoded: {}", &instruction_info);
+        self.registers.program_counter = self.registers.program_counter.wrapping_add(1);
 
-        Ok(instruction_info)
+        Ok(byte)
+    }
+
+    /// The instruction an opcode runs as on this processor, given how it
+    /// treats undocumented opcodes.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the instruction, or `None` if the opcode is illegal.
+    fn lookup(&self, code: u8) -> Option<InstructionInfo> {
+        if let Some(instruction_info) = get_instruction(Some(code), None, None, self.variant) {
+            return Some(instruction_info);
+        }
+
+        let undocumented = match self.variant {
+            Variant::Nmos => get_undocumented(code),
+            Variant::Cmos => Some(get_cmos_nop(code)),
+        };
+        match (self.illegal_opcodes, undocumented) {
+            (IllegalOpcodePolicy::Emulate, Some(info)) if info.opcode.is_stable() => Some(info),
+            (IllegalOpcodePolicy::Nop, Some(info)) => Some(InstructionInfo {
+                opcode: Opcode::Nop,
+                ..info
+            }),
+            _ => None,
+        }
+    }
+
+    fn decode(&mut self, address: u16, instruction: u8) -> Result<InstructionInfo> {
+        self.lookup(instruction).ok_or_else(|| {
+            error!(
+                "Illegal opcode {:#04X} at {}",
+                instruction,
+                self.symbols.describe(address)
+            );
+            CpuError::IllegalOpcode {
+                pc: address,
+                opcode: instruction,
+            }
+        })
     }
 
-    pub fn _get_status(&self, status: StatusFlags) -> bool {
+    pub fn get_status(&self, status: StatusFlags) -> bool {
         self.registers.status.bits() & status.bits() != 0
     }
 
@@ -263,4 +802,386 @@ impl Cpu {
             _ => Ok(vec![]),
         }
     }
+
+    /// Work out where the operand of an instruction is.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The addressing mode of the instruction.
+    /// * `args` - The operand bytes that followed the opcode.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the operand, and whether indexing or a branch crossed a page.
+    fn target(&mut self, mode: AddressingMode, args: &[u8]) -> Result<(Target, bool)> {
+        let byte = args.first().copied().unwrap_or_default();
+        let word = u16::from_le_bytes([byte, args.get(1).copied().unwrap_or_default()]);
+        let indexed = |base: u16, index: u8| {
+            let address = base.wrapping_add(u16::from(index));
+            (Target::Address(address), base & 0xFF00 != address & 0xFF00)
+        };
+
+        Ok(match mode {
+            AddressingMode::Implied => (Target::None, false),
+            AddressingMode::Accumulator => (Target::Accumulator, false),
+            AddressingMode::Immediate => (Target::Immediate(byte), false),
+            AddressingMode::ZeroPage => (Target::Address(u16::from(byte)), false),
+            AddressingMode::ZeroPageX => {
+                let address = byte.wrapping_add(self.registers.x);
+                (Target::Address(u16::from(address)), false)
+            }
+            AddressingMode::ZeroPageY => {
+                let address = byte.wrapping_add(self.registers.y);
+                (Target::Address(u16::from(address)), false)
+            }
+            AddressingMode::Absolute => (Target::Address(word), false),
+            AddressingMode::AbsoluteX => indexed(word, self.registers.x),
+            AddressingMode::AbsoluteY => indexed(word, self.registers.y),
+            AddressingMode::Indirect => {
+                // The NMOS 6502 never carries into the high byte of the pointer
+                let high = match self.variant {
+                    Variant::Nmos => (word & 0xFF00) | (word.wrapping_add(1) & 0x00FF),
+                    Variant::Cmos => word.wrapping_add(1),
+                };
+                let address = u16::from_le_bytes([self.read(word)?, self.read(high)?]);
+                (Target::Address(address), false)
+            }
+            AddressingMode::AbsoluteIndexedIndirect => {
+                let pointer = word.wrapping_add(u16::from(self.registers.x));
+                let address =
+                    u16::from_le_bytes([self.read(pointer)?, self.read(pointer.wrapping_add(1))?]);
+                (Target::Address(address), false)
+            }
+            AddressingMode::IndexedIndirect => {
+                let pointer = byte.wrapping_add(self.registers.x);
+                (Target::Address(self.read_zero_page_word(pointer)?), false)
+            }
+            AddressingMode::IndirectIndexed => {
+                let base = self.read_zero_page_word(byte)?;
+                indexed(base, self.registers.y)
+            }
+            AddressingMode::ZeroPageIndirect => {
+                (Target::Address(self.read_zero_page_word(byte)?), false)
+            }
+            AddressingMode::Relative => {
+                let next = self.registers.program_counter;
+                let address = next.wrapping_add_signed(i16::from(byte as i8));
+                (Target::Address(address), next & 0xFF00 != address & 0xFF00)
+            }
+        })
+    }
+
+    /// Read the value of an operand.
+    fn operand(&mut self, target: Target) -> Result<u8> {
+        match target {
+            Target::None => Ok(0),
+            Target::Accumulator => Ok(self.registers.accumulator),
+            Target::Immediate(value) => Ok(value),
+            Target::Address(address) => self.read(address),
+        }
+    }
+
+    /// Write the result of an instruction to where its operand came from.
+    fn store(&mut self, target: Target, value: u8) {
+        match target {
+            Target::Accumulator => self.registers.accumulator = value,
+            Target::Address(address) => self.write(address, value),
+            Target::None | Target::Immediate(_) => (),
+        }
+    }
+
+    /// Read, change and write back an operand, setting the zero and negative flags.
+    fn modify(&mut self, target: Target, change: impl FnOnce(&mut Cpu, u8) -> u8) -> Result<()> {
+        let result = self.read_modify_write(target, change)?;
+        self.set_zero_negative(result);
+        Ok(())
+    }
+
+    /// Read, change and write back an operand, leaving the flags to the caller.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the value written back.
+    fn read_modify_write(
+        &mut self,
+        target: Target,
+        change: impl FnOnce(&mut Cpu, u8) -> u8,
+    ) -> Result<u8> {
+        let value = self.operand(target)?;
+        let result = change(self, value);
+        self.store(target, result);
+        Ok(result)
+    }
+
+    fn shift_left(&mut self, value: u8) -> u8 {
+        self.set_status(StatusFlags::CARRY, value & 0x80 != 0);
+        value << 1
+    }
+
+    fn shift_right(&mut self, value: u8) -> u8 {
+        self.set_status(StatusFlags::CARRY, value & 0x01 != 0);
+        value >> 1
+    }
+
+    fn rotate_left(&mut self, value: u8) -> u8 {
+        let carry = self.get_status(StatusFlags::CARRY) as u8;
+        self.set_status(StatusFlags::CARRY, value & 0x80 != 0);
+        value << 1 | carry
+    }
+
+    fn rotate_right(&mut self, value: u8) -> u8 {
+        let carry = self.get_status(StatusFlags::CARRY) as u8;
+        self.set_status(StatusFlags::CARRY, value & 0x01 != 0);
+        value >> 1 | carry << 7
+    }
+
+    /// Take a branch if its condition holds.
+    ///
+    /// # Returns
+    ///
+    /// The extra cycles taken: one for a taken branch, two if it also crosses a page.
+    fn branch(&mut self, condition: bool, target: Target) -> u8 {
+        match target {
+            Target::Address(address) if condition => {
+                let next = self.registers.program_counter;
+                self.registers.program_counter = address;
+                if next & 0xFF00 == address & 0xFF00 {
+                    1
+                } else {
+                    2
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    fn compare(&mut self, register: u8, target: Target) -> Result<()> {
+        let value = self.operand(target)?;
+        self.compare_value(register, value);
+        Ok(())
+    }
+
+    fn compare_value(&mut self, register: u8, value: u8) {
+        self.set_status(StatusFlags::CARRY, register >= value);
+        self.set_zero_negative(register.wrapping_sub(value));
+    }
+
+    /// `AND` then rotate right, with the flags the NMOS 6502 leaves, which in
+    /// decimal mode come from an adjustment like that of `ADC`.
+    fn arr(&mut self, value: u8) {
+        let and = self.registers.accumulator & value;
+        let carry = self.get_status(StatusFlags::CARRY) as u8;
+        let mut result = and >> 1 | carry << 7;
+
+        if !self.get_status(StatusFlags::DECIMAL) {
+            self.set_zero_negative(result);
+            self.set_status(StatusFlags::CARRY, result & 0x40 != 0);
+            self.set_status(StatusFlags::OVERFLOW, (result ^ result << 1) & 0x40 != 0);
+            self.registers.accumulator = result;
+            return;
+        }
+
+        self.set_zero_negative(result);
+        self.set_status(StatusFlags::OVERFLOW, (and ^ result) & 0x40 != 0);
+        if (and & 0x0F) + (and & 0x01) > 0x05 {
+            result = (result & 0xF0) | (result.wrapping_add(0x06) & 0x0F);
+        }
+        let high = u16::from(and & 0xF0) + u16::from(and & 0x10) > 0x50;
+        if high {
+            result = result.wrapping_add(0x60);
+        }
+        self.set_status(StatusFlags::CARRY, high);
+        self.registers.accumulator = result;
+    }
+
+    /// Add with carry, in binary or, with the decimal flag set, in BCD.
+    fn add(&mut self, value: u8) {
+        let accumulator = self.registers.accumulator;
+        let carry = self.get_status(StatusFlags::CARRY) as u16;
+        let binary = u16::from(accumulator) + u16::from(value) + carry;
+
+        if !self.get_status(StatusFlags::DECIMAL) {
+            let result = binary as u8;
+            self.set_status(StatusFlags::CARRY, binary > 0xFF);
+            self.set_status(
+                StatusFlags::OVERFLOW,
+                (accumulator ^ result) & (value ^ result) & 0x80 != 0,
+            );
+            self.set_accumulator(result);
+            return;
+        }
+
+        // The NMOS 6502 sets Z from the binary sum, and N and V before the high digit is adjusted
+        let mut low = u16::from(accumulator & 0x0F) + u16::from(value & 0x0F) + carry;
+        if low >= 0x0A {
+            low = ((low + 0x06) & 0x0F) + 0x10;
+        }
+        let mut result = u16::from(accumulator & 0xF0) + u16::from(value & 0xF0) + low;
+        let signed =
+            i16::from((accumulator & 0xF0) as i8) + i16::from((value & 0xF0) as i8) + low as i16;
+
+        self.set_status(StatusFlags::ZERO, binary & 0xFF == 0);
+        self.set_status(StatusFlags::NEGATIVE, result & 0x80 != 0);
+        self.set_status(StatusFlags::OVERFLOW, !(-128..=127).contains(&signed));
+        if result >= 0xA0 {
+            result += 0x60;
+        }
+        self.set_status(StatusFlags::CARRY, result > 0xFF);
+        self.registers.accumulator = result as u8;
+    }
+
+    /// Finish decimal `ADC` or `SBC` on the 65C02, which sets N and Z from the
+    /// BCD result at the cost of a cycle.
+    ///
+    /// # Returns
+    ///
+    /// The extra cycles taken.
+    fn cmos_decimal(&mut self) -> u8 {
+        if self.variant != Variant::Cmos || !self.get_status(StatusFlags::DECIMAL) {
+            return 0;
+        }
+        self.set_zero_negative(self.registers.accumulator);
+        1
+    }
+
+    /// Subtract with borrow, in binary or, with the decimal flag set, in BCD.
+    fn subtract(&mut self, value: u8) {
+        let accumulator = self.registers.accumulator;
+        let borrow = 1 - self.get_status(StatusFlags::CARRY) as i16;
+        let decimal = self.get_status(StatusFlags::DECIMAL);
+
+        // The NMOS 6502 sets every flag from the binary difference, even in decimal mode
+        self.set_status(StatusFlags::DECIMAL, false);
+        self.add(!value);
+        self.set_status(StatusFlags::DECIMAL, decimal);
+
+        if !decimal {
+            return;
+        }
+
+        let low = i16::from(accumulator & 0x0F) - i16::from(value & 0x0F) - borrow;
+        let result = match self.variant {
+            Variant::Nmos => {
+                let low = if low < 0 {
+                    ((low - 0x06) & 0x0F) - 0x10
+                } else {
+                    low
+                };
+                let result = i16::from(accumulator & 0xF0) - i16::from(value & 0xF0) + low;
+                if result < 0 {
+                    result - 0x60
+                } else {
+                    result
+                }
+            }
+            // The 65C02 corrects the whole difference, then the low digit, which
+            // only gives a different answer for digits that are not BCD
+            Variant::Cmos => {
+                let mut result = i16::from(accumulator) - i16::from(value) - borrow;
+                if result < 0 {
+                    result -= 0x60;
+                }
+                if low < 0 {
+                    result -= 0x06;
+                }
+                result
+            }
+        };
+        self.registers.accumulator = result as u8;
+    }
+
+    fn set_accumulator(&mut self, value: u8) {
+        self.registers.accumulator = value;
+        self.set_zero_negative(value);
+    }
+
+    fn set_zero_negative(&mut self, value: u8) {
+        self.set_status(StatusFlags::ZERO, value == 0);
+        self.set_status(StatusFlags::NEGATIVE, value & 0x80 != 0);
+    }
+
+    /// Read a byte for the running instruction, noting it for watchpoints.
+    fn read(&mut self, address: u16) -> Result<u8> {
+        self.note_pia(address);
+        let value = self
+            .bus
+            .read(address)
+            .ok_or(CpuError::BusFault { address })?;
+        if !self.breakpoints.is_empty() {
+            self.breakpoints
+                .access(&self.registers, address, value, Access::Read);
+        }
+        Ok(value)
+    }
+
+    /// Write a byte for the running instruction, noting it for watchpoints.
+    fn write(&mut self, address: u16, value: u8) {
+        if self.history.capacity() > 0 {
+            self.history
+                .write(address, self.bus.memory.ram[address as usize]);
+        }
+        self.note_pia(address);
+        self.load_addr(address, value);
+        if !self.breakpoints.is_empty() {
+            self.breakpoints
+                .access(&self.registers, address, value, Access::Write);
+        }
+    }
+
+    /// Keep the PIA for the history before an access that may change it.
+    fn note_pia(&mut self, address: u16) {
+        if self.history.capacity() > 0 && Pia::contains(address) {
+            self.history.access_pia(&self.bus.pia);
+        }
+    }
+
+    fn read_word(&mut self, address: u16) -> Result<u16> {
+        let low = self.read(address)?;
+        let high = self.read(address.wrapping_add(1))?;
+        Ok(u16::from_le_bytes([low, high]))
+    }
+
+    /// Read a pointer from the zero page, wrapping around within it.
+    fn read_zero_page_word(&mut self, address: u8) -> Result<u16> {
+        let low = self.read(u16::from(address))?;
+        let high = self.read(u16::from(address.wrapping_add(1)))?;
+        Ok(u16::from_le_bytes([low, high]))
+    }
+
+    fn push(&mut self, value: u8) -> Result<()> {
+        self.write(STACK_PAGE | u16::from(self.registers.stack_pointer), value);
+        self.registers.stack_pointer = self.registers.stack_pointer.wrapping_sub(1);
+        Ok(())
+    }
+
+    fn pull(&mut self) -> Result<u8> {
+        self.registers.stack_pointer = self.registers.stack_pointer.wrapping_add(1);
+        self.read(STACK_PAGE | u16::from(self.registers.stack_pointer))
+    }
+
+    fn push_word(&mut self, value: u16) -> Result<()> {
+        let [low, high] = value.to_le_bytes();
+        self.push(high)?;
+        self.push(low)
+    }
+
+    fn pull_word(&mut self) -> Result<u16> {
+        let low = self.pull()?;
+        let high = self.pull()?;
+        Ok(u16::from_le_bytes([low, high]))
+    }
+
+    /// Restore the status register from the stack, where `BREAK` has no meaning.
+    fn pull_status(&mut self) -> Result<()> {
+        let status = StatusFlags::from_bits_retain(self.pull()?);
+        self.registers.status = (status - StatusFlags::BREAK) | StatusFlags::UNUSED;
+        Ok(())
+    }
+}
+
+/// The bytes an instruction at an address could use, fewer at the top of memory.
+fn instruction_bytes(memory: &Memory, address: u16) -> Vec<u8> {
+    (0..3)
+        .map_while(|offset| memory.read(address.wrapping_add(offset)))
+        .collect()
 }
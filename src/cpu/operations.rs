@@ -1,4 +1,5 @@
-use log::{error, info};
+use clap::ValueEnum;
+use log::debug;
 use std::{
     fmt,
     hash::{Hash, Hasher},
@@ -33,11 +34,22 @@ impl Hash for OpSize {
 #[derive(Debug, Clone, Copy, Eq, PartialOrd, Ord)]
 pub enum AddressingMode {
     Absolute,
+    /// A pointer at an absolute address plus X, as in `JMP ($1234,X)` on the 65C02.
+    AbsoluteIndexedIndirect,
+    AbsoluteX,
+    AbsoluteY,
     Accumulator,
     Immediate,
     Implied,
+    IndexedIndirect,
+    Indirect,
+    IndirectIndexed,
     Relative,
     ZeroPage,
+    /// A pointer in the zero page, without indexing, as in `LDA ($10)` on the 65C02.
+    ZeroPageIndirect,
+    ZeroPageX,
+    ZeroPageY,
 }
 
 impl fmt::Display for AddressingMode {
@@ -58,10 +70,36 @@ impl Hash for AddressingMode {
     }
 }
 
+/// Which processor an instruction set is that of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum Variant {
+    /// The original NMOS 6502, as in the Apple I.
+    #[default]
+    #[value(name = "6502")]
+    Nmos,
+    /// The CMOS 65C02, which adds instructions and fixes some NMOS quirks.
+    /// The Rockwell and WDC bit instructions are not included.
+    #[value(name = "65c02")]
+    Cmos,
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Variant::Nmos => write!(f, "6502"),
+            Variant::Cmos => write!(f, "65C02"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialOrd, Ord)]
 pub enum Opcode {
     Adc,
+    Alr,
+    Anc,
     And,
+    Ane,
+    Arr,
     Asl,
     Bcc,
     Bcs,
@@ -70,6 +108,7 @@ pub enum Opcode {
     Bmi,
     Bne,
     Bpl,
+    Bra,
     Brk,
     Bvc,
     Bvs,
@@ -80,6 +119,7 @@ pub enum Opcode {
     Cmp,
     Cpx,
     Cpy,
+    Dcp,
     Dec,
     Dex,
     Dey,
@@ -87,38 +127,79 @@ pub enum Opcode {
     Inc,
     Inx,
     Iny,
+    Isc,
+    Jam,
     Jmp,
     Jsr,
     Ida,
+    Las,
+    Lax,
     Lda,
     Ldx,
     Ldy,
     Lsr,
+    Lxa,
     Nop,
     Ora,
     Pha,
     Php,
+    Phx,
+    Phy,
     Pla,
     Plp,
+    Plx,
+    Ply,
+    Rla,
     Rol,
     Ror,
+    Rra,
     Rti,
     Rts,
+    Sax,
     Sbc,
+    Sbx,
     Sec,
     Sed,
     Sei,
+    Sha,
+    Shx,
+    Shy,
+    Slo,
+    Sre,
     Sta,
     Stx,
     Sty,
+    Stz,
+    Tas,
     Tax,
     Tay,
+    Trb,
+    Tsb,
     Tsx,
     Txa,
     Txs,
     Tya,
 }
 
+impl Opcode {
+    /// Whether an undocumented opcode does the same on every NMOS 6502.
+    ///
+    /// The others depend on the chip and its temperature, or lock it up, so
+    /// they are never emulated.
+    pub fn is_stable(&self) -> bool {
+        !matches!(
+            self,
+            Opcode::Ane
+                | Opcode::Jam
+                | Opcode::Lxa
+                | Opcode::Sha
+                | Opcode::Shx
+                | Opcode::Shy
+                | Opcode::Tas
+        )
+    }
+}
+
 impl fmt::Display for Opcode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", format!("{:?}", self).to_uppercase())
@@ -143,6 +224,12 @@ pub struct InstructionInfo {
     pub opcode_value: u8,
     pub size: OpSize,
     pub addressing_mode: AddressingMode,
+    /// The number of clock cycles the instruction takes at best.
+    pub cycles: u8,
+    /// Whether crossing a page boundary, or taking a branch, costs extra cycles.
+    pub page_cycle: bool,
+    /// The first processor with the instruction. The 65C02 keeps every documented NMOS one.
+    pub variant: Variant,
 }
 
 impl fmt::Display for InstructionInfo {
@@ -169,435 +256,2587 @@ impl Hash for InstructionInfo {
     }
 }
 
-pub static INSTRUCTIONS: [InstructionInfo; 69] = [
-    InstructionInfo {
-        opcode: Opcode::Brk,
-        opcode_value: 0x00,
-        size: OpSize::One,
-        addressing_mode: AddressingMode::Implied,
-    },
+pub static INSTRUCTIONS: [InstructionInfo; 151] = [
     InstructionInfo {
         opcode: Opcode::Adc,
         opcode_value: 0x69,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Adc,
         opcode_value: 0x65,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Adc,
+        opcode_value: 0x75,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Adc,
+        opcode_value: 0x6D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Adc,
+        opcode_value: 0x7D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Adc,
+        opcode_value: 0x79,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Adc,
+        opcode_value: 0x61,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Adc,
+        opcode_value: 0x71,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 5,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::And,
         opcode_value: 0x29,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::And,
         opcode_value: 0x25,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::And,
+        opcode_value: 0x35,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::And,
+        opcode_value: 0x2D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::And,
+        opcode_value: 0x3D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::And,
+        opcode_value: 0x39,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::And,
+        opcode_value: 0x21,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::And,
+        opcode_value: 0x31,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 5,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Asl,
         opcode_value: 0x0A,
         size: OpSize::One,
         addressing_mode: AddressingMode::Accumulator,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Asl,
         opcode_value: 0x06,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Asl,
+        opcode_value: 0x16,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Asl,
+        opcode_value: 0x0E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Asl,
+        opcode_value: 0x1E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bcc,
         opcode_value: 0x90,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bcs,
         opcode_value: 0xB0,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Beq,
         opcode_value: 0xF0,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bit,
         opcode_value: 0x24,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bit,
         opcode_value: 0x2C,
         size: OpSize::Three,
         addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bmi,
         opcode_value: 0x30,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bne,
         opcode_value: 0xD0,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bpl,
         opcode_value: 0x10,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Brk,
+        opcode_value: 0x00,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bvc,
         opcode_value: 0x50,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Bvs,
         opcode_value: 0x70,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Clc,
         opcode_value: 0x18,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Cld,
         opcode_value: 0xD8,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Cli,
         opcode_value: 0x58,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Clv,
         opcode_value: 0xB8,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Cmp,
         opcode_value: 0xC9,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Cmp,
         opcode_value: 0xC5,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cmp,
+        opcode_value: 0xD5,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cmp,
+        opcode_value: 0xCD,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cmp,
+        opcode_value: 0xDD,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cmp,
+        opcode_value: 0xD9,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cmp,
+        opcode_value: 0xC1,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cmp,
+        opcode_value: 0xD1,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 5,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Cpx,
         opcode_value: 0xE0,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Cpx,
         opcode_value: 0xE4,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cpx,
+        opcode_value: 0xEC,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Cpy,
         opcode_value: 0xC0,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Cpy,
         opcode_value: 0xC4,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cpy,
+        opcode_value: 0xCC,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Dec,
         opcode_value: 0xC6,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dec,
+        opcode_value: 0xD6,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dec,
+        opcode_value: 0xCE,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dec,
+        opcode_value: 0xDE,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dex,
+        opcode_value: 0xCA,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dey,
+        opcode_value: 0x88,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Eor,
         opcode_value: 0x49,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Eor,
         opcode_value: 0x45,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Eor,
+        opcode_value: 0x55,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Eor,
+        opcode_value: 0x4D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Eor,
+        opcode_value: 0x5D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Eor,
+        opcode_value: 0x59,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Eor,
+        opcode_value: 0x41,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Eor,
+        opcode_value: 0x51,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 5,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Inc,
         opcode_value: 0xE6,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Inc,
+        opcode_value: 0xF6,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Inc,
+        opcode_value: 0xEE,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Inc,
+        opcode_value: 0xFE,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Inx,
+        opcode_value: 0xE8,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Iny,
+        opcode_value: 0xC8,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Jmp,
         opcode_value: 0x4C,
         size: OpSize::Three,
         addressing_mode: AddressingMode::Absolute,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jmp,
+        opcode_value: 0x6C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Indirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Jsr,
         opcode_value: 0x20,
         size: OpSize::Three,
         addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Lda,
         opcode_value: 0xA9,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Lda,
         opcode_value: 0xA5,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lda,
+        opcode_value: 0xB5,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lda,
+        opcode_value: 0xAD,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lda,
+        opcode_value: 0xBD,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lda,
+        opcode_value: 0xB9,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lda,
+        opcode_value: 0xA1,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lda,
+        opcode_value: 0xB1,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 5,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Ldx,
         opcode_value: 0xA2,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Ldx,
         opcode_value: 0xA6,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ldx,
+        opcode_value: 0xB6,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageY,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ldx,
+        opcode_value: 0xAE,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ldx,
+        opcode_value: 0xBE,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Ldy,
         opcode_value: 0xA0,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Ldy,
         opcode_value: 0xA4,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ldy,
+        opcode_value: 0xB4,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ldy,
+        opcode_value: 0xAC,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ldy,
+        opcode_value: 0xBC,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Lsr,
         opcode_value: 0x4A,
         size: OpSize::One,
         addressing_mode: AddressingMode::Accumulator,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Lsr,
         opcode_value: 0x46,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
-        opcode: Opcode::Nop,
-        opcode_value: 0xEA,
-        size: OpSize::One,
-        addressing_mode: AddressingMode::Implied,
-    },
-    InstructionInfo {
-        opcode: Opcode::Ora,
-        opcode_value: 0x09,
+        opcode: Opcode::Lsr,
+        opcode_value: 0x56,
         size: OpSize::Two,
-        addressing_mode: AddressingMode::Immediate,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
-        opcode: Opcode::Ora,
+        opcode: Opcode::Lsr,
+        opcode_value: 0x4E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lsr,
+        opcode_value: 0x5E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xEA,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
+        opcode_value: 0x09,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
         opcode_value: 0x05,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
+        opcode_value: 0x15,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
+        opcode_value: 0x0D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
+        opcode_value: 0x1D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
+        opcode_value: 0x19,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
+        opcode_value: 0x01,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
+        opcode_value: 0x11,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 5,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Pha,
         opcode_value: 0x48,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Php,
         opcode_value: 0x08,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Pla,
         opcode_value: 0x68,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Plp,
         opcode_value: 0x28,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Rol,
         opcode_value: 0x2A,
         size: OpSize::One,
         addressing_mode: AddressingMode::Accumulator,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Rol,
         opcode_value: 0x26,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rol,
+        opcode_value: 0x36,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rol,
+        opcode_value: 0x2E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rol,
+        opcode_value: 0x3E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Ror,
         opcode_value: 0x6A,
         size: OpSize::One,
         addressing_mode: AddressingMode::Accumulator,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Ror,
         opcode_value: 0x66,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ror,
+        opcode_value: 0x76,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ror,
+        opcode_value: 0x6E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ror,
+        opcode_value: 0x7E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Rti,
         opcode_value: 0x40,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Rts,
         opcode_value: 0x60,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Sbc,
         opcode_value: 0xE9,
         size: OpSize::Two,
         addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Sbc,
         opcode_value: 0xE5,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbc,
+        opcode_value: 0xF5,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbc,
+        opcode_value: 0xED,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbc,
+        opcode_value: 0xFD,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbc,
+        opcode_value: 0xF9,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbc,
+        opcode_value: 0xE1,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbc,
+        opcode_value: 0xF1,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 5,
+        page_cycle: true,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Sec,
         opcode_value: 0x38,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Sed,
         opcode_value: 0xF8,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Sei,
         opcode_value: 0x78,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Sta,
         opcode_value: 0x85,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sta,
+        opcode_value: 0x95,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sta,
+        opcode_value: 0x8D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sta,
+        opcode_value: 0x9D,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sta,
+        opcode_value: 0x99,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sta,
+        opcode_value: 0x81,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sta,
+        opcode_value: 0x91,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Stx,
         opcode_value: 0x86,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Stx,
+        opcode_value: 0x96,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageY,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Stx,
+        opcode_value: 0x8E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Sty,
         opcode_value: 0x84,
         size: OpSize::Two,
         addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sty,
+        opcode_value: 0x94,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sty,
+        opcode_value: 0x8C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Tax,
         opcode_value: 0xAA,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Tay,
         opcode_value: 0xA8,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Tsx,
         opcode_value: 0xBA,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Txa,
         opcode_value: 0x8A,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Txs,
         opcode_value: 0x9A,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
     InstructionInfo {
         opcode: Opcode::Tya,
         opcode_value: 0x98,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
     },
+];
+
+/// The instructions and addressing modes the 65C02 adds to those in `INSTRUCTIONS`.
+pub static CMOS: [InstructionInfo; 27] = [
     InstructionInfo {
-        opcode: Opcode::Inx,
-        opcode_value: 0xE8,
+        opcode: Opcode::Bra,
+        opcode_value: 0x80,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Relative,
+        cycles: 2,
+        page_cycle: true,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Stz,
+        opcode_value: 0x64,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Stz,
+        opcode_value: 0x74,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Stz,
+        opcode_value: 0x9C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Stz,
+        opcode_value: 0x9E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Phx,
+        opcode_value: 0xDA,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Phy,
+        opcode_value: 0x5A,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Plx,
+        opcode_value: 0xFA,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ply,
+        opcode_value: 0x7A,
         size: OpSize::One,
         addressing_mode: AddressingMode::Implied,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Trb,
+        opcode_value: 0x14,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Trb,
+        opcode_value: 0x1C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Tsb,
+        opcode_value: 0x04,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Tsb,
+        opcode_value: 0x0C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ora,
+        opcode_value: 0x12,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageIndirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::And,
+        opcode_value: 0x32,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageIndirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Eor,
+        opcode_value: 0x52,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageIndirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Adc,
+        opcode_value: 0x72,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageIndirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sta,
+        opcode_value: 0x92,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageIndirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lda,
+        opcode_value: 0xB2,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageIndirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Cmp,
+        opcode_value: 0xD2,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageIndirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbc,
+        opcode_value: 0xF2,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageIndirect,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Inc,
+        opcode_value: 0x1A,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Accumulator,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dec,
+        opcode_value: 0x3A,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Accumulator,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Bit,
+        opcode_value: 0x89,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Bit,
+        opcode_value: 0x34,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Bit,
+        opcode_value: 0x3C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Cmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jmp,
+        opcode_value: 0x7C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteIndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Cmos,
     },
 ];
 
-pub fn get_instruction(
-    code: Option<u8>,
-    name: Option<&str>,
-    addressing: Option<AddressingMode>,
-) -> Option<InstructionInfo> {
-    if let Some(code) = code {
-        info!("Searching by code.");
-        let instruction_info = INSTRUCTIONS.iter().find(|o| o.opcode_value == code);
+/// The opcodes the NMOS 6502 runs though they were never documented, with
+/// the `JAM`s that lock it up. Together with `INSTRUCTIONS` they cover every byte.
+pub static UNDOCUMENTED: [InstructionInfo; 105] = [
+    InstructionInfo {
+        opcode: Opcode::Slo,
+        opcode_value: 0x07,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Slo,
+        opcode_value: 0x17,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Slo,
+        opcode_value: 0x0F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Slo,
+        opcode_value: 0x1F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Slo,
+        opcode_value: 0x1B,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Slo,
+        opcode_value: 0x03,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Slo,
+        opcode_value: 0x13,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rla,
+        opcode_value: 0x27,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rla,
+        opcode_value: 0x37,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rla,
+        opcode_value: 0x2F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rla,
+        opcode_value: 0x3F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rla,
+        opcode_value: 0x3B,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rla,
+        opcode_value: 0x23,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rla,
+        opcode_value: 0x33,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sre,
+        opcode_value: 0x47,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sre,
+        opcode_value: 0x57,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sre,
+        opcode_value: 0x4F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sre,
+        opcode_value: 0x5F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sre,
+        opcode_value: 0x5B,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sre,
+        opcode_value: 0x43,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sre,
+        opcode_value: 0x53,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rra,
+        opcode_value: 0x67,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rra,
+        opcode_value: 0x77,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rra,
+        opcode_value: 0x6F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rra,
+        opcode_value: 0x7F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rra,
+        opcode_value: 0x7B,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rra,
+        opcode_value: 0x63,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Rra,
+        opcode_value: 0x73,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dcp,
+        opcode_value: 0xC7,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dcp,
+        opcode_value: 0xD7,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dcp,
+        opcode_value: 0xCF,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dcp,
+        opcode_value: 0xDF,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dcp,
+        opcode_value: 0xDB,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dcp,
+        opcode_value: 0xC3,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Dcp,
+        opcode_value: 0xD3,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Isc,
+        opcode_value: 0xE7,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Isc,
+        opcode_value: 0xF7,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Isc,
+        opcode_value: 0xEF,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Isc,
+        opcode_value: 0xFF,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Isc,
+        opcode_value: 0xFB,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 7,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Isc,
+        opcode_value: 0xE3,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Isc,
+        opcode_value: 0xF3,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 8,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sax,
+        opcode_value: 0x87,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sax,
+        opcode_value: 0x97,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageY,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sax,
+        opcode_value: 0x8F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sax,
+        opcode_value: 0x83,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lax,
+        opcode_value: 0xA7,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lax,
+        opcode_value: 0xB7,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageY,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lax,
+        opcode_value: 0xAF,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lax,
+        opcode_value: 0xBF,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lax,
+        opcode_value: 0xA3,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndexedIndirect,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lax,
+        opcode_value: 0xB3,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 5,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Anc,
+        opcode_value: 0x0B,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Anc,
+        opcode_value: 0x2B,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Alr,
+        opcode_value: 0x4B,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Arr,
+        opcode_value: 0x6B,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbx,
+        opcode_value: 0xCB,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sbc,
+        opcode_value: 0xEB,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Las,
+        opcode_value: 0xBB,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x1A,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x3A,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x5A,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x7A,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xDA,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xFA,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x80,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x82,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x89,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xC2,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xE2,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x04,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x44,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x64,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPage,
+        cycles: 3,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x14,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x34,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x54,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x74,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xD4,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xF4,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::ZeroPageX,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x0C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::Absolute,
+        cycles: 4,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x1C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x3C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x5C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0x7C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xDC,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: 0xFC,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 4,
+        page_cycle: true,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Ane,
+        opcode_value: 0x8B,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Lxa,
+        opcode_value: 0xAB,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::Immediate,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sha,
+        opcode_value: 0x93,
+        size: OpSize::Two,
+        addressing_mode: AddressingMode::IndirectIndexed,
+        cycles: 6,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Sha,
+        opcode_value: 0x9F,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Tas,
+        opcode_value: 0x9B,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Shy,
+        opcode_value: 0x9C,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteX,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Shx,
+        opcode_value: 0x9E,
+        size: OpSize::Three,
+        addressing_mode: AddressingMode::AbsoluteY,
+        cycles: 5,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x02,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x12,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x22,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x32,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x42,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x52,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x62,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x72,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0x92,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0xB2,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0xD2,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+    InstructionInfo {
+        opcode: Opcode::Jam,
+        opcode_value: 0xF2,
+        size: OpSize::One,
+        addressing_mode: AddressingMode::Implied,
+        cycles: 2,
+        page_cycle: false,
+        variant: Variant::Nmos,
+    },
+];
+
+/// Look up a documented instruction of a variant, by opcode or by name and addressing mode.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::{get_instruction, AddressingMode, Variant};
+/// assert!(get_instruction(Some(0x64), None, None, Variant::Nmos).is_none());
+/// let stz = get_instruction(None, Some("stz"), Some(AddressingMode::ZeroPage), Variant::Cmos);
+/// assert_eq!(stz.unwrap().opcode_value, 0x64);
+/// ```
+pub fn get_instruction(
+    code: Option<u8>,
+    name: Option<&str>,
+    addressing: Option<AddressingMode>,
+    variant: Variant,
+) -> Option<InstructionInfo> {
+    if let Some(code) = code {
+        let instruction_info = instructions(variant).find(|o| o.opcode_value == code);
         return match instruction_info {
             Some(instruction_info) => Some(*instruction_info),
             None => {
-                error!("Unknown operation code: {:#04X}", code);
+                debug!("Undocumented operation code: {:#04X}", code);
                 None
             }
         };
@@ -607,15 +2846,94 @@ pub fn get_instruction(
 
     match remaining_args {
         (Some(name), Some(mode)) => {
-            info!("Searching by name and mode.");
             let name = name.to_uppercase();
-            Some(
-                INSTRUCTIONS
-                    .into_iter()
-                    .find(|o| o.opcode.to_string() == name && o.addressing_mode == mode)
-                    .expect("unknown operation name"),
-            )
+            instructions(variant)
+                .find(|o| o.opcode.to_string() == name && o.addressing_mode == mode)
+                .copied()
         }
         _ => None,
     }
 }
+
+/// Every documented instruction of a variant.
+pub fn instructions(variant: Variant) -> impl Iterator<Item = &'static InstructionInfo> {
+    let added: &'static [InstructionInfo] = match variant {
+        Variant::Nmos => &[],
+        Variant::Cmos => &CMOS,
+    };
+    INSTRUCTIONS.iter().chain(added)
+}
+
+/// The `NOP` the 65C02 runs for an opcode it leaves undefined, which may
+/// read an operand and so be longer than one byte.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::{get_cmos_nop, OpSize, Opcode};
+/// let nop = get_cmos_nop(0x5C);
+/// assert_eq!((nop.opcode, nop.size, nop.cycles), (Opcode::Nop, OpSize::Three, 8));
+/// ```
+pub fn get_cmos_nop(code: u8) -> InstructionInfo {
+    let (size, addressing_mode, cycles) = match code {
+        0x44 => (OpSize::Two, AddressingMode::ZeroPage, 3),
+        0x54 | 0xD4 | 0xF4 => (OpSize::Two, AddressingMode::ZeroPageX, 4),
+        0x5C => (OpSize::Three, AddressingMode::Absolute, 8),
+        0xDC | 0xFC => (OpSize::Three, AddressingMode::Absolute, 4),
+        _ if code & 0x0F == 0x02 => (OpSize::Two, AddressingMode::Immediate, 2),
+        _ => (OpSize::One, AddressingMode::Implied, 1),
+    };
+    InstructionInfo {
+        opcode: Opcode::Nop,
+        opcode_value: code,
+        size,
+        addressing_mode,
+        cycles,
+        page_cycle: false,
+        variant: Variant::Cmos,
+    }
+}
+
+/// Look up an opcode the NMOS 6502 runs without it being documented.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::{get_undocumented, AddressingMode, Opcode};
+/// let lax = get_undocumented(0xA7).unwrap();
+/// assert_eq!((lax.opcode, lax.addressing_mode), (Opcode::Lax, AddressingMode::ZeroPage));
+/// assert!(get_undocumented(0xA9).is_none());
+/// ```
+pub fn get_undocumented(code: u8) -> Option<InstructionInfo> {
+    UNDOCUMENTED
+        .iter()
+        .find(|o| o.opcode_value == code)
+        .copied()
+}
+
+/// Find every addressing mode supported by the named instruction.
+///
+/// # Arguments
+///
+/// * `name` - The mnemonic of the instruction, in any case.
+/// * `variant` - The processor whose instructions are searched.
+///
+/// # Returns
+///
+/// A vector of addressing modes, empty if no instruction has that name.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::{get_addressing_modes, AddressingMode, Variant};
+/// let modes = get_addressing_modes("lda", Variant::Nmos);
+/// assert!(modes.contains(&AddressingMode::Immediate));
+/// assert!(get_addressing_modes("LDZ", Variant::Cmos).is_empty());
+/// ```
+pub fn get_addressing_modes(name: &str, variant: Variant) -> Vec<AddressingMode> {
+    let name = name.to_uppercase();
+    instructions(variant)
+        .filter(|o| o.opcode.to_string() == name)
+        .map(|o| o.addressing_mode)
+        .collect()
+}
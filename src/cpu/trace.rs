@@ -0,0 +1,107 @@
+use super::Registers;
+use crate::assembler::parse_address;
+use crate::disassembler::{hex, Instruction};
+
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::RangeInclusive,
+    path::Path,
+};
+
+/// Writes a line for every instruction executed, for comparing against other emulators.
+///
+/// Each line shows the state before the instruction ran, as
+/// `PC  bytes  disassembly  A:XX X:XX Y:XX P:XX SP:XX CYC:n`. Operands are
+/// always written in hex, so that traces do not depend on symbols.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    /// The addresses traced, or every address when empty.
+    ranges: Vec<RangeInclusive<u16>>,
+}
+
+impl Tracer {
+    pub fn new(output: impl Write + 'static, ranges: Vec<RangeInclusive<u16>>) -> Self {
+        Tracer {
+            output: Box::new(output),
+            ranges,
+        }
+    }
+
+    /// Trace to a file, replacing anything already in it.
+    pub fn create(path: &Path, ranges: Vec<RangeInclusive<u16>>) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Cannot create {}", path.display()))?;
+        Ok(Tracer::new(BufWriter::new(file), ranges))
+    }
+
+    /// Whether instructions at an address are traced.
+    pub fn covers(&self, address: u16) -> bool {
+        self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&address))
+    }
+
+    /// Write the line for an instruction about to run.
+    pub fn record(
+        &mut self,
+        instruction: &Instruction,
+        registers: &Registers,
+        cycles: u64,
+    ) -> io::Result<()> {
+        writeln!(self.output, "{}", line(instruction, registers, cycles))
+    }
+}
+
+/// Format the trace line for an instruction and the state before it ran.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::{trace::line, Registers};
+/// # use apple_ie::cpu::operations::Variant;
+/// # use apple_ie::disassembler::decode;
+/// let instruction = decode(&[0xA9, 0x01], 0x0800, Variant::Nmos);
+/// assert_eq!(
+///     line(&instruction, &Registers::new(), 0),
+///     "0800  A9 01     LDA #$01        A:00 X:00 Y:00 P:24 SP:FD CYC:0"
+/// );
+/// ```
+pub fn line(instruction: &Instruction, registers: &Registers, cycles: u64) -> String {
+    let bytes: Vec<String> = instruction
+        .bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+
+    format!(
+        "{:04X}  {:8}  {:14}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+        instruction.address,
+        bytes.join(" "),
+        instruction.format(hex),
+        registers.accumulator,
+        registers.x,
+        registers.y,
+        registers.status.bits(),
+        registers.stack_pointer,
+        cycles
+    )
+}
+
+/// Parse an address range written `START..END`, or a single address.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::trace::parse_range;
+/// assert_eq!(parse_range("$0800..$08FF"), Ok(0x0800..=0x08FF));
+/// assert_eq!(parse_range("$FF00"), Ok(0xFF00..=0xFF00));
+/// ```
+pub fn parse_range(text: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = text.split_once("..").unwrap_or((text, text));
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+
+    if start > end {
+        return Err(format!("{} ends before it starts", text));
+    }
+    Ok(start..=end)
+}
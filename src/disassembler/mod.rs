@@ -0,0 +1,279 @@
+use crate::cpu::operations::{instructions, AddressingMode, InstructionInfo, Variant};
+use crate::symbols::SymbolTable;
+
+use std::fmt::{self, Write};
+
+pub mod source;
+
+/// One decoded instruction, or a byte that does not start one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u16,
+    /// The opcode and operand bytes, or the single byte that could not be decoded.
+    pub bytes: Vec<u8>,
+    /// The decoded instruction, or `None` for an unknown opcode or one cut short.
+    pub info: Option<InstructionInfo>,
+}
+
+impl Instruction {
+    /// The address just past the instruction.
+    pub fn next(&self) -> u16 {
+        self.address.wrapping_add(self.bytes.len() as u16)
+    }
+
+    /// The raw operand: a byte for one-byte operands, a little-endian word for two.
+    pub fn operand(&self) -> Option<u16> {
+        match self.bytes[..] {
+            [_, low] if self.info.is_some() => Some(u16::from(low)),
+            [_, low, high] => Some(u16::from_le_bytes([low, high])),
+            _ => None,
+        }
+    }
+
+    /// The address the operand refers to, with branch offsets resolved.
+    ///
+    /// Immediate operands are values rather than addresses, so they have none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::cpu::operations::Variant;
+    /// # use apple_ie::disassembler::decode;
+    /// let instruction = decode(&[0xD0, 0xFE], 0x0800, Variant::Nmos);
+    /// assert_eq!(instruction.target(), Some(0x0800));
+    /// ```
+    pub fn target(&self) -> Option<u16> {
+        let operand = self.operand()?;
+
+        match self.info?.addressing_mode {
+            AddressingMode::Immediate => None,
+            AddressingMode::Relative => {
+                let offset = operand as u8 as i8;
+                Some(self.next().wrapping_add_signed(i16::from(offset)))
+            }
+            _ => Some(operand),
+        }
+    }
+
+    /// Format the instruction as assembly, such as `LDA ($10),Y` or `.byte $FF`.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbols` - Names shown in place of the addresses they stand for.
+    ///
+    /// # Returns
+    ///
+    /// The mnemonic and operand.
+    pub fn text(&self, symbols: &SymbolTable) -> String {
+        self.format(|address, mode| match symbols.name_of(address) {
+            Some(name) => name.to_string(),
+            None => hex(address, mode),
+        })
+    }
+
+    /// Format the instruction as assembly, choosing how each address is written.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Writes the address the operand refers to, given its addressing mode.
+    ///
+    /// # Returns
+    ///
+    /// The mnemonic and operand.
+    pub fn format(&self, address: impl Fn(u16, AddressingMode) -> String) -> String {
+        let info = match self.info {
+            Some(info) => info,
+            None => return format!(".byte ${:02X}", self.bytes[0]),
+        };
+
+        let mode = info.addressing_mode;
+        let target = || address(self.target().unwrap_or_default(), mode);
+
+        let operand = match mode {
+            AddressingMode::Implied => String::new(),
+            AddressingMode::Accumulator => "A".to_string(),
+            AddressingMode::Immediate => format!("#${:02X}", self.operand().unwrap_or_default()),
+            AddressingMode::ZeroPage | AddressingMode::Absolute | AddressingMode::Relative => {
+                target()
+            }
+            AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => format!("{},X", target()),
+            AddressingMode::ZeroPageY | AddressingMode::AbsoluteY => format!("{},Y", target()),
+            AddressingMode::Indirect | AddressingMode::ZeroPageIndirect => {
+                format!("({})", target())
+            }
+            AddressingMode::IndexedIndirect | AddressingMode::AbsoluteIndexedIndirect => {
+                format!("({},X)", target())
+            }
+            AddressingMode::IndirectIndexed => format!("({}),Y", target()),
+        };
+
+        format!("{} {}", info.opcode, operand)
+            .trim_end()
+            .to_string()
+    }
+}
+
+/// Write an address in hex, with two digits for zero page addressing modes and four otherwise.
+pub fn hex(address: u16, mode: AddressingMode) -> String {
+    match mode {
+        AddressingMode::ZeroPage
+        | AddressingMode::ZeroPageX
+        | AddressingMode::ZeroPageY
+        | AddressingMode::IndexedIndirect
+        | AddressingMode::IndirectIndexed
+        | AddressingMode::ZeroPageIndirect => format!("${:02X}", address),
+        _ => format!("${:04X}", address),
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", line(self, &SymbolTable::new()))
+    }
+}
+
+/// Decode the instruction at the start of a slice of bytes.
+///
+/// A byte that is not a documented opcode of the processor, or an opcode
+/// whose operand runs past the end of the slice, is returned on its own with
+/// no `info`.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to decode, starting with the opcode.
+/// * `address` - The address of the first byte.
+/// * `variant` - The processor whose instructions are decoded.
+///
+/// # Returns
+///
+/// The decoded instruction.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::Variant;
+/// # use apple_ie::disassembler::decode;
+/// let instruction = decode(&[0xA9, 0x01], 0x0800, Variant::Nmos);
+/// assert_eq!(instruction.to_string(), "0800  A9 01     LDA #$01");
+/// assert_eq!(decode(&[0x80, 0xFE], 0x0800, Variant::Cmos).to_string(), "0800  80 FE     BRA $0800");
+/// ```
+pub fn decode(bytes: &[u8], address: u16, variant: Variant) -> Instruction {
+    decode_with(bytes, address, |code| {
+        instructions(variant)
+            .find(|info| info.opcode_value == code)
+            .copied()
+    })
+}
+
+/// Decode the instruction at the start of a slice of bytes, looking opcodes up
+/// with a caller's table, such as a CPU's that runs undocumented opcodes.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to decode, starting with the opcode.
+/// * `address` - The address of the first byte.
+/// * `lookup` - The instruction an opcode runs as, or `None` if it runs as none.
+///
+/// # Returns
+///
+/// The decoded instruction.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::get_undocumented;
+/// # use apple_ie::disassembler::decode_with;
+/// let instruction = decode_with(&[0xA7, 0x10], 0x0800, get_undocumented);
+/// assert_eq!(instruction.to_string(), "0800  A7 10     LAX $10");
+/// ```
+pub fn decode_with(
+    bytes: &[u8],
+    address: u16,
+    lookup: impl Fn(u8) -> Option<InstructionInfo>,
+) -> Instruction {
+    let info = bytes
+        .first()
+        .and_then(|code| lookup(*code))
+        .filter(|info| info.size as usize <= bytes.len());
+
+    let size = info.map_or(1, |info| info.size as usize);
+    Instruction {
+        address,
+        bytes: bytes[..size.min(bytes.len())].to_vec(),
+        info,
+    }
+}
+
+/// Decode every instruction in a block of bytes, one after another.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to decode.
+/// * `origin` - The address of the first byte.
+/// * `variant` - The processor whose instructions are decoded.
+///
+/// # Returns
+///
+/// The instructions, covering every byte.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::Variant;
+/// # use apple_ie::disassemble;
+/// let instructions = disassemble(&[0xA9, 0x01, 0x00], 0x0800, Variant::Nmos);
+/// assert_eq!(instructions.len(), 2);
+/// assert_eq!(instructions[1].address, 0x0802);
+/// ```
+pub fn disassemble(bytes: &[u8], origin: u16, variant: Variant) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+        let instruction = decode(&bytes[offset..], address, variant);
+        offset += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+
+    instructions
+}
+
+/// Render instructions as a listing, one line each with address, bytes and assembly.
+///
+/// # Arguments
+///
+/// * `instructions` - The instructions to list.
+/// * `symbols` - Names shown in place of addresses, and as labels on their own lines.
+///
+/// # Returns
+///
+/// The listing as text.
+pub fn render(instructions: &[Instruction], symbols: &SymbolTable) -> String {
+    let mut output = String::new();
+
+    for instruction in instructions {
+        if let Some(name) = symbols.name_of(instruction.address) {
+            let _ = writeln!(output, "{:16}{}:", "", name);
+        }
+        let _ = writeln!(output, "{}", line(instruction, symbols));
+    }
+
+    output
+}
+
+fn line(instruction: &Instruction, symbols: &SymbolTable) -> String {
+    let bytes = instruction
+        .bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "{:04X}  {:8}  {}",
+        instruction.address,
+        bytes,
+        instruction.text(symbols)
+    )
+}
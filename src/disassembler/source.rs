@@ -0,0 +1,288 @@
+use super::{decode, hex, Instruction};
+use crate::cpu::operations::{AddressingMode, Opcode, Variant};
+use crate::symbols::SymbolTable;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+/// Where the 6502 finds its NMI, reset and IRQ handlers.
+const VECTORS: u16 = 0xFFFA;
+
+/// How many bytes go on each `.byte` line of data.
+const BYTES_PER_LINE: usize = 8;
+
+/// The column trailing address comments start at.
+const COMMENT_COLUMN: usize = 40;
+
+/// Follow the code reachable from the entry points, as the CPU would run it.
+///
+/// Both sides of every branch are followed, as are `JSR`, `JMP` and `BRA` targets.
+/// Paths end at `RTS`, `RTI`, `BRK` and indirect jumps, at bytes that are not
+/// opcodes, and where an instruction would overlap one already found.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to trace through.
+/// * `origin` - The address of the first byte.
+/// * `entries` - The addresses execution can start at.
+/// * `variant` - The processor the code is for.
+///
+/// # Returns
+///
+/// The instructions reached, by address. Every other byte is data.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::Variant;
+/// # use apple_ie::disassembler::source::trace;
+/// // JMP $0805, then a data byte, then RTS
+/// let code = trace(&[0x4C, 0x04, 0x08, 0xFF, 0x60], 0x0800, &[0x0800], Variant::Nmos);
+/// assert_eq!(code.keys().copied().collect::<Vec<_>>(), [0x0800, 0x0804]);
+/// ```
+pub fn trace(
+    bytes: &[u8],
+    origin: u16,
+    entries: &[u16],
+    variant: Variant,
+) -> BTreeMap<u16, Instruction> {
+    let offset = |address: u16| usize::from(address.wrapping_sub(origin));
+
+    let mut code = BTreeMap::new();
+    let mut covered = vec![false; bytes.len()];
+    let mut pending: Vec<u16> = entries.to_vec();
+
+    while let Some(address) = pending.pop() {
+        let start = offset(address);
+        if start >= bytes.len() || covered[start] {
+            continue;
+        }
+
+        let instruction = decode(&bytes[start..], address, variant);
+        let info = match instruction.info {
+            Some(info) => info,
+            None => continue,
+        };
+
+        let end = start + instruction.bytes.len();
+        if covered[start..end].contains(&true) {
+            continue;
+        }
+        covered[start..end].fill(true);
+
+        let next = instruction.next();
+        let target = instruction.target();
+
+        match (info.opcode, info.addressing_mode) {
+            (Opcode::Rts | Opcode::Rti | Opcode::Brk, _) => (),
+            (Opcode::Jmp, AddressingMode::Indirect | AddressingMode::AbsoluteIndexedIndirect) => (),
+            (Opcode::Jmp | Opcode::Bra, _) => pending.extend(target),
+            (Opcode::Jsr, _) | (_, AddressingMode::Relative) => {
+                pending.push(next);
+                pending.extend(target);
+            }
+            _ => pending.push(next),
+        }
+
+        code.insert(address, instruction);
+    }
+
+    code
+}
+
+/// Produce source that the assembler turns back into exactly the same bytes.
+///
+/// Code is traced from the entry points and from any interrupt vectors in
+/// range, and everything else is written as `.byte` data. Addresses in range
+/// that are referred to get labels, taken from `symbols` when named there and
+/// otherwise written `Lxxxx`. Named addresses out of range become constants.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to disassemble.
+/// * `origin` - The address of the first byte.
+/// * `entries` - The addresses execution can start at.
+/// * `symbols` - Names for addresses, used for labels and operands.
+/// * `variant` - The processor the code is for.
+///
+/// # Returns
+///
+/// The source text.
+///
+/// # Examples
+///
+/// ```
+/// # use apple_ie::cpu::operations::Variant;
+/// # use apple_ie::disassembler::source::render;
+/// # use apple_ie::symbols::SymbolTable;
+/// let text = render(&[0xD0, 0xFE], 0x0800, &[0x0800], &SymbolTable::new(), Variant::Nmos);
+/// assert!(text.contains("L0800:"));
+/// assert!(text.contains("BNE L0800"));
+/// ```
+pub fn render(
+    bytes: &[u8],
+    origin: u16,
+    entries: &[u16],
+    symbols: &SymbolTable,
+    variant: Variant,
+) -> String {
+    let in_range = |address: u16| usize::from(address.wrapping_sub(origin)) < bytes.len();
+    let vectors = in_range(VECTORS) && in_range(VECTORS + 5);
+    let vector = |index: u16| {
+        let offset = usize::from(VECTORS + index * 2 - origin);
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    };
+
+    let mut starts = entries.to_vec();
+    if vectors {
+        starts.extend((0..3).map(vector));
+    }
+    let code = trace(bytes, origin, &starts, variant);
+
+    // Every address referred to, from operands and vectors
+    let mut references: BTreeSet<u16> = code
+        .values()
+        .filter_map(|instruction| instruction.target())
+        .collect();
+    if vectors {
+        references.extend((0..3).map(vector));
+    }
+
+    let labels: BTreeMap<u16, String> = references
+        .iter()
+        .filter(|address| in_range(**address))
+        .map(|address| start_of(&code, *address))
+        .map(|address| (address, name(symbols, address)))
+        .collect();
+
+    let constants: BTreeMap<&str, u16> = references
+        .iter()
+        .filter(|address| !in_range(**address))
+        .filter_map(|address| Some((symbols.name_of(*address)?, *address)))
+        .collect();
+
+    // The vectors are written as words unless code or a label falls among them
+    let vectors = vectors
+        && (VECTORS..=0xFFFF).all(|address| {
+            containing(&code, address).is_none()
+                && (address == VECTORS || !labels.contains_key(&address))
+        });
+
+    let refer = |address: u16, mode: AddressingMode| {
+        if in_range(address) {
+            let start = start_of(&code, address);
+            let label = &labels[&start];
+            return match address - start {
+                0 => label.clone(),
+                offset => format!("{}+{}", label, offset),
+            };
+        }
+        match symbols.name_of(address) {
+            Some(name) => name.to_string(),
+            None => hex(address, mode),
+        }
+    };
+
+    let mut output = String::new();
+    let _ = writeln!(
+        output,
+        "; Disassembled from {} bytes at ${:04X}",
+        bytes.len(),
+        origin
+    );
+    if !constants.is_empty() {
+        let _ = writeln!(output);
+    }
+    for (name, address) in &constants {
+        let _ = writeln!(output, "{} = ${:04X}", name, address);
+    }
+    let _ = writeln!(output);
+    let _ = writeln!(output, "        .org ${:04X}", origin);
+
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+
+        if let Some(label) = labels.get(&address) {
+            let _ = writeln!(output, "{}:", label);
+        }
+
+        let (text, size) = match code.get(&address) {
+            Some(instruction) => (
+                instruction.format(|target, mode| {
+                    let text = refer(target, mode);
+                    match mode {
+                        // A small value would be assembled as zero page without the prefix
+                        AddressingMode::Absolute
+                        | AddressingMode::AbsoluteX
+                        | AddressingMode::AbsoluteY
+                            if target <= 0xFF =>
+                        {
+                            format!("a:{}", text)
+                        }
+                        _ => text,
+                    }
+                }),
+                instruction.bytes.len(),
+            ),
+            None if vectors && address == VECTORS => {
+                let names: Vec<String> = (0..3)
+                    .map(|index| refer(vector(index), AddressingMode::Absolute))
+                    .collect();
+                (format!(".word {}", names.join(", ")), 6)
+            }
+            None => {
+                // Data runs until the next label or instruction
+                let size = (offset..bytes.len())
+                    .take(BYTES_PER_LINE)
+                    .take_while(|next| {
+                        let next = origin.wrapping_add(*next as u16);
+                        next == address
+                            || !(labels.contains_key(&next)
+                                || code.contains_key(&next)
+                                || vectors && next == VECTORS)
+                    })
+                    .count();
+                let data: Vec<String> = bytes[offset..offset + size]
+                    .iter()
+                    .map(|byte| format!("${:02X}", byte))
+                    .collect();
+                (format!(".byte {}", data.join(", ")), size)
+            }
+        };
+
+        let line = format!("        {}", text);
+        let _ = writeln!(
+            output,
+            "{:width$}; {:04X}",
+            line,
+            address,
+            width = COMMENT_COLUMN.max(line.len() + 1)
+        );
+        offset += size;
+    }
+
+    output
+}
+
+/// The address of the traced instruction containing an address, if any.
+fn containing(code: &BTreeMap<u16, Instruction>, address: u16) -> Option<u16> {
+    code.range(..=address)
+        .next_back()
+        .filter(|(start, instruction)| instruction.next().wrapping_sub(**start) > address - **start)
+        .map(|(start, _)| *start)
+}
+
+/// The address a label for an address goes on: the start of its instruction, if in one.
+fn start_of(code: &BTreeMap<u16, Instruction>, address: u16) -> u16 {
+    containing(code, address).unwrap_or(address)
+}
+
+fn name(symbols: &SymbolTable, address: u16) -> String {
+    match symbols.name_of(address) {
+        Some(name) => name.to_string(),
+        None => format!("L{:04X}", address),
+    }
+}
@@ -0,0 +1,91 @@
+/// How many characters fit on a line of the Apple I screen.
+pub const COLUMNS: usize = 40;
+
+/// How many lines the Apple I screen shows before scrolling.
+pub const ROWS: usize = 24;
+
+/// The Apple I terminal: characters written to the PIA appear here and scroll off the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    /// The lines on screen, the last holding the cursor.
+    rows: Vec<String>,
+    /// What has been printed since it was last taken, with wrapped lines broken.
+    output: String,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Display {
+            rows: vec![String::new()],
+            output: String::new(),
+        }
+    }
+
+    /// Print a character as the Apple I would.
+    ///
+    /// The high bit is ignored, carriage return starts a new line and other
+    /// control characters are not shown.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::display::Display;
+    /// let mut display = Display::new();
+    /// for byte in b"HI\rTHERE" {
+    ///     display.put(byte | 0x80);
+    /// }
+    /// assert_eq!(display.text(), "HI\nTHERE");
+    /// ```
+    pub fn put(&mut self, byte: u8) {
+        match byte & 0x7F {
+            b'\r' => self.new_line(),
+            character @ 0x20..=0x7E => {
+                if self.rows.last().map_or(0, String::len) == COLUMNS {
+                    self.new_line();
+                }
+                if let Some(row) = self.rows.last_mut() {
+                    row.push(character as char);
+                }
+                self.output.push(character as char);
+            }
+            _ => (),
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.rows.push(String::new());
+        if self.rows.len() > ROWS {
+            self.rows.remove(0);
+        }
+        self.output.push('\n');
+    }
+
+    /// The lines on screen, joined by newlines.
+    pub fn text(&self) -> String {
+        self.rows.join("\n")
+    }
+
+    pub fn rows(&self) -> &[String] {
+        &self.rows
+    }
+
+    /// Put lines back on screen, as from a snapshot, keeping at most a screenful.
+    pub fn set_rows(&mut self, rows: Vec<String>) {
+        let skip = rows.len().saturating_sub(ROWS);
+        self.rows = rows.into_iter().skip(skip).collect();
+        if self.rows.is_empty() {
+            self.rows.push(String::new());
+        }
+    }
+
+    /// Take what has been printed since the last call, for showing or capturing.
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
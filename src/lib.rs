@@ -0,0 +1,43 @@
+//! The Apple Ie, an Apple I emulator: a 6502 or 65C02 with the Apple I's
+//! memory, keyboard and display, an assembler and a disassembler.
+//!
+//! `Machine` is the place to start. `Cpu` and `Bus` give finer control, and
+//! the modules hold the types they are made of, from the PIA to breakpoints.
+//!
+//! # Examples
+//!
+//! ```
+//! use apple_ie::{assemble, cpu::operations::Variant, disassemble, Machine};
+//!
+//! let bytes = assemble("LDA #$01\nBRK").unwrap();
+//! assert_eq!(bytes, vec![0xA9, 0x01, 0x00]);
+//! assert_eq!(disassemble(&bytes, 0x0800, Variant::Nmos).len(), 2);
+//!
+//! let mut machine = Machine::default();
+//! machine.cpu.load(0x0800, &bytes);
+//! machine.run(10).unwrap();
+//! assert_eq!(machine.cpu.registers.accumulator, 0x01);
+//! ```
+
+pub mod assembler;
+pub mod bus;
+pub mod cpu;
+pub mod disassembler;
+pub mod display;
+pub mod machine;
+pub mod memory;
+pub mod pia;
+pub mod symbols;
+
+// The test runners for the CPU's conformance suites
+#[cfg(test)]
+mod conformance;
+
+pub use assembler::assemble;
+pub use bus::Bus;
+pub use cpu::Cpu;
+pub use disassembler::disassemble;
+pub use machine::Machine;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,103 @@
+use crate::assembler::{assemble_with, source::SourceFile, Assembly, Options};
+use crate::bus::Bus;
+use crate::cpu::{breakpoints::Stop, operations::Variant, Cpu, CpuError};
+use crate::symbols::SymbolTable;
+
+use anyhow::Result;
+
+/// An Apple I: a CPU with its memory, keyboard and display.
+///
+/// This is the way in for programs embedding the emulator. It covers
+/// loading, typing, running and reading the display; `cpu` is there for
+/// anything more, such as breakpoints or snapshots.
+///
+/// # Examples
+///
+/// ```
+/// use apple_ie::Machine;
+///
+/// let mut machine = Machine::default();
+/// machine
+///     .assemble(
+///         "      LDX #0
+///          loop: LDA text,X
+///                BEQ done
+///                STA $D012
+///                INX
+///                BNE loop
+///          done: BRK
+///          text: .byte \"HI\", 0",
+///     )
+///     .unwrap();
+/// machine.run(1_000).unwrap();
+/// assert_eq!(machine.take_output(), "HI");
+/// ```
+#[derive(Default)]
+pub struct Machine {
+    pub cpu: Cpu,
+}
+
+impl Machine {
+    /// Create a machine with a processor, its memory cleared.
+    pub fn new(variant: Variant) -> Self {
+        let mut cpu = Cpu::new();
+        cpu.variant = variant;
+        Machine { cpu }
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.cpu.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.cpu.bus
+    }
+
+    /// Load an assembled program, ready to run from its first byte.
+    ///
+    /// Its labels name addresses in logs and the debugger.
+    pub fn load(&mut self, assembly: &Assembly) {
+        self.cpu.load(assembly.origin, &assembly.bytes);
+        self.cpu.symbols = SymbolTable::from_values(&assembly.symbols);
+    }
+
+    /// Assemble a program for this machine's processor and load it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the program, or the assembler's error.
+    pub fn assemble(&mut self, program: &str) -> Result<Assembly> {
+        let options = Options {
+            variant: self.cpu.variant,
+            ..Options::default()
+        };
+        let assembly = assemble_with(SourceFile::new("<input>", program), &options)?;
+        self.load(&assembly);
+        Ok(assembly)
+    }
+
+    /// Press keys on the keyboard, to be read by the program in turn.
+    pub fn type_text(&mut self, text: &str) {
+        for key in text.bytes() {
+            self.cpu.bus.pia.press(key);
+        }
+    }
+
+    /// Run until the program reaches a `BRK` or a breakpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The most instructions to execute.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing why execution stopped, or the error an instruction hit.
+    pub fn run(&mut self, limit: u64) -> Result<Stop, CpuError> {
+        self.cpu.resume(limit, None)
+    }
+
+    /// Take what the display has printed since last asked.
+    pub fn take_output(&mut self) -> String {
+        self.cpu.bus.pia.display.take_output()
+    }
+}
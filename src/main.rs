@@ -1,19 +1,37 @@
-use assembler::assemble;
-use cpu::Cpu;
+use apple_ie::assembler::{
+    assemble_file, assemble_source, dialect::Dialect, listing, parse_address, parse_define,
+    Options, DEFAULT_ORIGIN,
+};
+use apple_ie::cpu::{
+    operations::Variant,
+    trace::{parse_range, Tracer},
+    Cpu, IllegalOpcodePolicy,
+};
+use apple_ie::disassembler::{self, disassemble, source};
+use apple_ie::symbols::{SymbolFormat, SymbolTable};
+use apple_ie::Machine;
+use cli::debugger::Debugger;
+use cli::gdb::{self, Server};
+use cli::headless::{self, Condition};
+use cli::script::Script;
+use cli::snapshot;
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use chrono::Local;
 use clap::Parser;
 use fern::{log_file, Dispatch};
 use log::{info, LevelFilter};
-use std::{fs, io};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{atomic::Ordering, mpsc},
+    thread,
+};
 
-pub mod assembler;
-pub mod cpu;
-pub mod memory;
-
-#[cfg(test)]
-mod tests;
+mod cli;
 
 /// Define a struct to hold command line arguments.
 #[derive(Parser)]
@@ -23,32 +41,155 @@ struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     debug: u8,
 
+    /// Load the program into the interactive debugger instead of running it.
+    #[arg(short = 'g', long)]
+    debugger: bool,
+
     /// Specify an assembly file to run.
     #[arg(short, long)]
     file: Option<String>,
+
+    /// Add a directory to search for included files.
+    #[arg(short = 'I', long = "include")]
+    include_paths: Vec<PathBuf>,
+
+    /// Define a symbol before assembly, as NAME=value or just NAME for 1.
+    #[arg(short = 'D', long = "define", value_parser = parse_define)]
+    defines: Vec<(String, i64)>,
+
+    /// The syntax the assembly file is written in.
+    #[arg(long, value_enum, default_value_t = Dialect::Native)]
+    dialect: Dialect,
+
+    /// Write a listing of the assembled program to a file.
+    #[arg(short, long)]
+    listing: Option<PathBuf>,
+
+    /// Write the symbols of the assembled program to a file.
+    #[arg(long)]
+    export_symbols: Option<PathBuf>,
+
+    /// The format of the exported symbols, guessed from the file extension if not given.
+    #[arg(long, value_enum)]
+    symbol_format: Option<SymbolFormat>,
+
+    /// Load symbol names from a VICE, text or JSON file to show in logs.
+    #[arg(short, long)]
+    symbols: Vec<PathBuf>,
+
+    /// Disassemble a binary file to standard output instead of running anything.
+    #[arg(long)]
+    disassemble: Option<PathBuf>,
+
+    /// The address the disassembled file is loaded at.
+    #[arg(long, value_parser = parse_address, default_value_t = DEFAULT_ORIGIN)]
+    origin: u16,
+
+    /// Disassemble to source the assembler rebuilds the file from, tracing code from the entry points.
+    #[arg(long, requires = "disassemble")]
+    source: bool,
+
+    /// An address execution can start at, for --source. Defaults to the origin.
+    #[arg(long = "entry", value_parser = parse_address)]
+    entries: Vec<u16>,
+
+    /// The processor to assemble for and emulate.
+    #[arg(long, value_enum, default_value_t = Variant::Nmos)]
+    cpu: Variant,
+
+    /// What the CPU does with undocumented opcodes: halt, skip them as NOPs or emulate them.
+    #[arg(long, value_enum, default_value_t = IllegalOpcodePolicy::Halt)]
+    illegal_opcodes: IllegalOpcodePolicy,
+
+    /// Serve the GDB remote protocol on an address instead of running the program.
+    #[arg(long, value_name = "ADDRESS", num_args = 0..=1, default_missing_value = gdb::DEFAULT_ADDRESS)]
+    gdb: Option<String>,
+
+    /// Write a line for every instruction executed to a file, for comparing with other emulators.
+    #[arg(long)]
+    trace: Option<PathBuf>,
+
+    /// Only trace instructions in an address range, written START..END. May be repeated.
+    #[arg(long = "trace-range", value_parser = parse_range, requires = "trace")]
+    trace_ranges: Vec<RangeInclusive<u16>>,
+
+    /// Restore the machine from a snapshot file after loading the program.
+    #[arg(long)]
+    load_state: Option<PathBuf>,
+
+    /// Save a snapshot of the machine to a file when the program stops, or on Ctrl-P and Enter.
+    ///
+    /// Ctrl-P is only read while a program runs interactively, not in the
+    /// debugger, over GDB or headless. The terminal passes keys on a line at a
+    /// time, so it takes effect once Enter is pressed.
+    #[arg(long)]
+    save_state: Option<PathBuf>,
+
+    /// Run at full speed without logging to the terminal or a file, then print the registers.
+    ///
+    /// Exits with 0 when a --until condition is met, or at a BRK when none are
+    /// given; 1 when an instruction fails; 2 at the cycle limit; 3 at a BRK
+    /// before any condition is met.
+    #[arg(long, conflicts_with_all = ["debugger", "gdb"])]
+    headless: bool,
+
+    /// Stop a headless run after this many cycles.
+    #[arg(long, requires = "headless")]
+    max_cycles: Option<u64>,
+
+    /// Stop a headless run when the PC reaches an address or symbol. May be repeated.
+    #[arg(long, value_name = "ADDR", requires = "headless")]
+    until_pc: Vec<String>,
+
+    /// Stop a headless run when memory holds a value, written ADDR=VALUE. May be repeated.
+    #[arg(long, value_name = "ADDR=VALUE", requires = "headless")]
+    until_memory: Vec<String>,
+
+    /// Stop a headless run once the display has printed some text. May be repeated.
+    #[arg(long, value_name = "TEXT", requires = "headless")]
+    until_output: Vec<String>,
+
+    /// Type keys from a script file as the program runs. See `Script` for the steps it may hold.
+    #[arg(long, conflicts_with_all = ["debugger", "gdb"])]
+    script: Option<PathBuf>,
+
+    /// Type keys from a script given on the command line, with steps separated by `;`, writing `\;` to type one.
+    #[arg(long, value_name = "SCRIPT", conflicts_with_all = ["script", "debugger", "gdb"])]
+    script_text: Option<String>,
+
+    /// Write everything the display prints to a file as well as the terminal.
+    #[arg(long, conflicts_with_all = ["debugger", "gdb"])]
+    capture: Option<PathBuf>,
 }
 
+/// The key that saves a snapshot while a program runs, Ctrl-P.
+///
+/// Standard input is read as the terminal passes it on, a line at a time, so
+/// it arrives once Enter is pressed. Only `run` looks for it.
+const SAVE_KEY: u8 = 0x10;
+
 /// The main function of the program.
 ///
 /// # Returns
 ///
-/// A `Result` containing the result of running the program.
-///
-/// # Examples
-///
-/// ```
-/// let result = apple_ie::main();
-/// assert!(result.is_ok());
-/// ```
-fn main() -> Result<()> {
+/// A `Result` containing the exit status.
+fn main() -> Result<ExitCode> {
     // Parse command line arguments using the Cli struct
     let cli = Cli::parse();
 
-    // Create a new CPU instance
-    let mut cpu: Cpu = Cpu::new();
+    // Disassemble a binary and stop there if asked to, before any logging reaches standard output
+    if let Some(path) = &cli.disassemble {
+        print!("{}", disassemble_file(&cli, path)?);
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    // Configure the logger using Fern
-    Dispatch::new()
+    // Create a new machine with the processor asked for
+    let mut machine = Machine::new(cli.cpu);
+    machine.cpu.illegal_opcodes = cli.illegal_opcodes;
+
+    // Configure the logger using Fern, leaving the terminal to the debugger or a headless run
+    let quiet = cli.debugger || cli.headless;
+    let mut dispatch = Dispatch::new()
         .format(|out, message, record| {
             out.finish(format_args!(
                 "{}[{}][{}] {}",
@@ -58,38 +199,264 @@ fn main() -> Result<()> {
                 message
             ))
         })
-        .level(LevelFilter::Info)
-        .chain(io::stdout())
-        .chain(log_file("apple-ie.log").expect("No permission to write to the current directory."))
-        .apply()
-        .expect("Failed to dispatch Fern logger!");
+        .level(if quiet {
+            LevelFilter::Warn
+        } else {
+            LevelFilter::Info
+        });
+    if cli.headless {
+        dispatch = dispatch.chain(io::stderr());
+    } else {
+        if !cli.debugger {
+            dispatch = dispatch.chain(io::stdout());
+        }
+        dispatch = dispatch.chain(
+            log_file("apple-ie.log").expect("No permission to write to the current directory."),
+        );
+    }
+    dispatch.apply().expect("Failed to dispatch Fern logger!");
 
     // Log that the logger has been initialised
     info!("Logging initialised.");
 
-    // Read the program from a file or use a default program
-    let program = match &cli.file {
-        Some(file) => fs::read_to_string(file)?,
-        None => String::from(
+    // Assemble the program from a file or use a default program
+    let options = Options {
+        include_paths: cli.include_paths.clone(),
+        defines: cli.defines.iter().cloned().collect(),
+        dialect: cli.dialect,
+        variant: cli.cpu,
+    };
+    let assembly = match &cli.file {
+        Some(file) => assemble_file(Path::new(file), &options)?,
+        None => assemble_source(
+            "<default>",
             "LDA #$01
              BRK",
-        ),
+        )?,
     };
 
-    // Assemble the program into machine code
-    let bytes = assemble(&program)?;
-
     // Log that the program has been assembled
     info!("Program assembled.");
 
-    // Load the program into memory
-    cpu.load(0x800, &bytes);
+    // Write the listing if one was asked for
+    if let Some(path) = &cli.listing {
+        fs::write(path, listing::render(&assembly))?;
+        info!("Listing written to {}.", path.display());
+    }
+
+    // Load the program into memory, naming addresses after its symbols
+    machine.load(&assembly);
+    let cpu = &mut machine.cpu;
 
     // Log that the program has been loaded into memory
     info!("Program loaded into memory.");
 
-    // Run the program on the CPU
-    cpu.run()?;
+    if let Some(path) = &cli.export_symbols {
+        let format = cli
+            .symbol_format
+            .unwrap_or_else(|| SymbolFormat::from_path(path));
+        fs::write(path, cpu.symbols.export(format))?;
+        info!("Symbols written to {}.", path.display());
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    // Name addresses after any symbol files given too
+    for path in &cli.symbols {
+        cpu.symbols.extend(&SymbolTable::read(path)?);
+    }
+
+    if let Some(path) = &cli.load_state {
+        snapshot::read(cpu, path)?;
+        info!("Snapshot restored from {}.", path.display());
+    }
+
+    if let Some(path) = &cli.trace {
+        cpu.tracer = Some(Tracer::create(path, cli.trace_ranges.clone())?);
+        info!("Tracing instructions to {}.", path.display());
+    }
+
+    let mut script = match (&cli.script, &cli.script_text) {
+        (Some(path), _) => Script::read(path)?,
+        (None, Some(text)) => Script::parse_inline(text)?,
+        (None, None) => Script::default(),
+    };
+    let mut output = Output::create(cli.capture.as_deref())?;
+
+    // Hand the CPU to the debugger or a GDB client, or run the program on it
+    if cli.debugger {
+        let mut debugger = Debugger::new(cpu);
+        let interrupt = debugger.interrupt();
+        ctrlc::set_handler(move || interrupt.store(true, Ordering::Relaxed))
+            .context("Cannot catch Ctrl-C")?;
+        debugger.run(io::stdin().lock(), io::stdout())?;
+    } else if let Some(address) = &cli.gdb {
+        Server::new(cpu).listen(address)?;
+    } else if cli.headless {
+        return run_headless(cpu, &cli, &mut script, &mut output);
+    } else {
+        run(cpu, cli.save_state.as_deref(), &mut script, &mut output)?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Disassemble a binary file as the command line asks, as a listing or as source.
+///
+/// # Returns
+///
+/// A `Result` containing the text, or an error if a file cannot be read.
+fn disassemble_file(cli: &Cli, path: &Path) -> Result<String> {
+    let mut symbols = SymbolTable::new();
+    for path in &cli.symbols {
+        symbols.extend(&SymbolTable::read(path)?);
+    }
+
+    let bytes = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    Ok(if cli.source {
+        let entries = if cli.entries.is_empty() {
+            vec![cli.origin]
+        } else {
+            cli.entries.clone()
+        };
+        source::render(&bytes, cli.origin, &entries, &symbols, cli.cpu)
+    } else {
+        disassembler::render(&disassemble(&bytes, cli.origin, cli.cpu), &symbols)
+    })
+}
+
+/// Run the program headless, printing the display, why it stopped and the registers.
+///
+/// # Returns
+///
+/// A `Result` containing the exit status `Outcome::exit_code` gives.
+fn run_headless(
+    cpu: &mut Cpu,
+    cli: &Cli,
+    script: &mut Script,
+    output: &mut Output,
+) -> Result<ExitCode> {
+    let symbols = &cpu.symbols;
+    let mut conditions = vec![];
+    for text in &cli.until_pc {
+        let condition = Condition::pc(text, symbols);
+        conditions.push(condition.map_err(|error| anyhow!("--until-pc {}: {}", text, error))?);
+    }
+    for text in &cli.until_memory {
+        let condition = Condition::memory(text, symbols);
+        conditions.push(condition.map_err(|error| anyhow!("--until-memory {}: {}", text, error))?);
+    }
+    conditions.extend(cli.until_output.iter().cloned().map(Condition::Output));
+
+    let outcome = headless::run(cpu, &conditions, cli.max_cycles, script, output)?;
+    let mut stdout = io::stdout();
+    if cpu
+        .bus
+        .pia
+        .display
+        .rows()
+        .last()
+        .is_some_and(|row| !row.is_empty())
+    {
+        writeln!(stdout)?;
+    }
+    writeln!(stdout, "{}", outcome)?;
+    writeln!(stdout, "{}  CYC={}", cpu.registers, cpu.cycles)?;
+
+    if let Some(path) = &cli.save_state {
+        snapshot::write(cpu, path)?;
+    }
+    Ok(ExitCode::from(outcome.exit_code(!conditions.is_empty())))
+}
+
+/// Run the program, typing standard input on the keyboard and printing the display.
+///
+/// # Arguments
+///
+/// * `cpu` - The CPU, with the program loaded.
+/// * `save_state` - Where to save a snapshot on `SAVE_KEY` and when the program stops.
+/// * `script` - Keys to type as well as those from standard input.
+/// * `output` - Where the display is printed.
+///
+/// # Returns
+///
+/// A `Result` containing why the program stopped.
+fn run(
+    cpu: &mut Cpu,
+    save_state: Option<&Path>,
+    script: &mut Script,
+    output: &mut Output,
+) -> Result<()> {
+    let (sender, keys) = mpsc::channel();
+    thread::spawn(move || {
+        for key in io::stdin().lock().bytes().map_while(Result::ok) {
+            if sender.send(key).is_err() {
+                break;
+            }
+        }
+    });
+
+    info!("Beginning main F-D-E loop.");
+    let mut printed = String::new();
+    let result = loop {
+        script.advance(cpu, &printed);
+        for key in keys.try_iter() {
+            match (key, save_state) {
+                (SAVE_KEY, Some(path)) => {
+                    snapshot::write(cpu, path)?;
+                    info!("Snapshot saved to {}.", path.display());
+                }
+                (SAVE_KEY, None) => info!("Give --save-state to save snapshots."),
+                _ => cpu.bus.pia.press(key),
+            }
+        }
+
+        let result = cpu.fde();
+        printed = cpu.bus.pia.display.take_output();
+        output.write_all(printed.as_bytes())?;
+        output.flush()?;
+        if result.is_err() {
+            break result;
+        }
+    };
+
+    if let Some(path) = save_state {
+        snapshot::write(cpu, path)?;
+        info!("Snapshot saved to {}.", path.display());
+    }
+    Ok(result?)
+}
+
+/// The terminal, and a file capturing everything printed to it when one is given.
+struct Output {
+    capture: Option<io::BufWriter<fs::File>>,
+}
+
+impl Output {
+    fn create(capture: Option<&Path>) -> Result<Self> {
+        let capture = match capture {
+            Some(path) => Some(io::BufWriter::new(
+                fs::File::create(path)
+                    .with_context(|| format!("Cannot write {}", path.display()))?,
+            )),
+            None => None,
+        };
+        Ok(Output { capture })
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        io::stdout().write_all(bytes)?;
+        if let Some(capture) = &mut self.capture {
+            capture.write_all(bytes)?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()?;
+        if let Some(capture) = &mut self.capture {
+            capture.flush()?;
+        }
+        Ok(())
+    }
+}
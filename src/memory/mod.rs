@@ -1,23 +1,33 @@
+/// The size of the 6502 address space.
+pub const MEMORY_SIZE: usize = 0x10000;
+
 pub struct Memory {
-    pub ram: [u8; 48 * 1024], // 48KB ROM
+    pub ram: Vec<u8>, // 64KB, the whole address space
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Memory {
-    /// Create a new Memory instance with 48KB of RAM.
+    /// Create a new Memory instance covering the full 64KB address space.
     ///
     /// # Returns
     ///
-    /// A new `Memory` instance with 48KB of RAM.
+    /// A new `Memory` instance with 64KB of RAM.
     ///
     /// # Examples
     ///
     /// ```
+    /// # use apple_ie::memory::Memory;
     /// let memory = Memory::new();
-    /// assert_eq!(memory.ram.len(), 48 * 1024);
+    /// assert_eq!(memory.ram.len(), 64 * 1024);
     /// ```
     pub fn new() -> Memory {
         Memory {
-            ram: [0; 48 * 1024],
+            ram: vec![0; MEMORY_SIZE],
         }
     }
 
@@ -34,6 +44,7 @@ impl Memory {
     /// # Examples
     ///
     /// ```
+    /// # use apple_ie::memory::Memory;
     /// let mut memory = Memory::new();
     /// memory.ram[0x1234] = 0xAB;
     /// assert_eq!(memory.read(0x1234), Some(0xAB));
@@ -44,8 +55,29 @@ impl Memory {
         self.ram.get(address as usize).copied()
     }
 
+    /// Write a byte to the specified address in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to write the byte to.
+    /// * `value` - The byte to write.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::memory::Memory;
+    /// let mut memory = Memory::new();
+    /// memory.write(0x1234, 0xAB);
+    /// assert_eq!(memory.ram[0x1234], 0xAB);
+    /// ```
+    pub fn write(&mut self, address: u16, value: u8) {
+        self.ram[address as usize] = value;
+    }
+
     /// Load a buffer of bytes into memory starting at the specified address.
     ///
+    /// Bytes past the top of memory wrap around to address zero.
+    ///
     /// # Arguments
     ///
     /// * `address` - The address to start loading the buffer into.
@@ -54,6 +86,7 @@ impl Memory {
     /// # Examples
     ///
     /// ```
+    /// # use apple_ie::memory::Memory;
     /// let mut memory = Memory::new();
     /// let buffer = vec![0xAB, 0xCD, 0xEF];
     /// memory.load(0x1234, &buffer);
@@ -64,7 +97,7 @@ impl Memory {
     pub fn load(&mut self, address: u16, buffer: &[u8]) {
         // Iterate over the buffer and copy each byte into memory
         for (i, byte) in buffer.iter().enumerate() {
-            self.ram[address as usize + i] = *byte;
+            self.write(address.wrapping_add(i as u16), *byte);
         }
     }
-}
\ No newline at end of file
+}
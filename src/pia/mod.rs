@@ -0,0 +1,95 @@
+use crate::display::Display;
+
+use std::collections::VecDeque;
+
+/// The keyboard data register, holding the last key pressed with its high bit set.
+pub const KBD: u16 = 0xD010;
+
+/// The keyboard control register, whose high bit is set while a key is waiting.
+pub const KBDCR: u16 = 0xD011;
+
+/// The display data register. Writing prints a character; the high bit reads as busy.
+pub const DSP: u16 = 0xD012;
+
+/// The display control register.
+pub const DSPCR: u16 = 0xD013;
+
+/// The 6821 PIA connecting the Apple I keyboard and display to the CPU.
+///
+/// Only the four registers at `KBD` to `DSPCR` are decoded. The display takes
+/// characters immediately, so it never reads as busy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pia {
+    /// Keys pressed that the program has not read yet.
+    pub keys: VecDeque<u8>,
+    /// The key last read, which `KBD` keeps showing.
+    pub key: u8,
+    pub keyboard_control: u8,
+    pub display_control: u8,
+    pub display: Display,
+}
+
+impl Pia {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether an address is one of the PIA's registers rather than memory.
+    pub fn contains(address: u16) -> bool {
+        (KBD..=DSPCR).contains(&address)
+    }
+
+    /// Queue a key press, as the Apple I keyboard sends it: upper case, with the high bit set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::pia::{Pia, KBD, KBDCR};
+    /// let mut pia = Pia::new();
+    /// pia.press(b'a');
+    /// assert_eq!(pia.read(KBDCR) & 0x80, 0x80);
+    /// assert_eq!(pia.read(KBD), 0xC1);
+    /// assert_eq!(pia.read(KBDCR) & 0x80, 0x00);
+    /// ```
+    pub fn press(&mut self, key: u8) {
+        let key = match key {
+            b'\n' => b'\r',
+            key => key.to_ascii_uppercase(),
+        };
+        self.keys.push_back(key | 0x80);
+    }
+
+    /// Read a register without the side effects a CPU read has.
+    pub fn peek(&self, address: u16) -> u8 {
+        match address {
+            KBD => self.keys.front().copied().unwrap_or(self.key),
+            KBDCR => {
+                let ready = if self.keys.is_empty() { 0x00 } else { 0x80 };
+                self.keyboard_control & 0x7F | ready
+            }
+            DSP => 0x00,
+            DSPCR => self.display_control,
+            _ => 0x00,
+        }
+    }
+
+    /// Read a register as the CPU does. Reading `KBD` takes the waiting key.
+    pub fn read(&mut self, address: u16) -> u8 {
+        if address != KBD {
+            return self.peek(address);
+        }
+        if let Some(key) = self.keys.pop_front() {
+            self.key = key;
+        }
+        self.key
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            KBDCR => self.keyboard_control = value,
+            DSP => self.display.put(value),
+            DSPCR => self.display_control = value,
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, fmt::Write, fs, path::Path};
+
+/// The file formats symbol tables can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SymbolFormat {
+    /// A VICE monitor label file, one `al C:0800 .name` command per line.
+    Vice,
+    /// One `NAME = $0800` assignment per line, which the assembler can include.
+    Text,
+    /// A JSON object mapping names to addresses.
+    Json,
+}
+
+impl SymbolFormat {
+    /// Guess the format of a file from its extension, defaulting to `Text`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::symbols::SymbolFormat;
+    /// # use std::path::Path;
+    /// assert_eq!(SymbolFormat::from_path(Path::new("main.json")), SymbolFormat::Json);
+    /// assert_eq!(SymbolFormat::from_path(Path::new("main.vs")), SymbolFormat::Vice);
+    /// ```
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => SymbolFormat::Json,
+            Some("vs" | "lbl" | "labels") => SymbolFormat::Vice,
+            _ => SymbolFormat::Text,
+        }
+    }
+}
+
+/// Names for addresses, exported by the assembler and shown by the emulator.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    symbols: BTreeMap<String, u16>,
+    /// The first name given to each address, for reverse lookups.
+    names: BTreeMap<u16, String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable {
+            symbols: BTreeMap::new(),
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// Build a table from the symbols of an assembled program.
+    ///
+    /// Values that are not 16-bit addresses are left out, as are the labels
+    /// made private to each macro expansion.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - The symbols of the program, such as `Assembly::symbols`.
+    ///
+    /// # Returns
+    ///
+    /// The symbol table.
+    pub fn from_values(values: &BTreeMap<String, i64>) -> Self {
+        let mut table = SymbolTable::new();
+        for (name, value) in values {
+            if let (Ok(address), false) = (u16::try_from(*value), name.contains('#')) {
+                table.insert(name, address);
+            }
+        }
+        table
+    }
+
+    /// Add a symbol, replacing any earlier value for the same name.
+    pub fn insert(&mut self, name: &str, address: u16) {
+        if let Some(old) = self.symbols.insert(name.to_string(), address) {
+            if self.names.get(&old).map(String::as_str) == Some(name) {
+                self.names.remove(&old);
+            }
+        }
+        self.names
+            .entry(address)
+            .or_insert_with(|| name.to_string());
+    }
+
+    /// Add every symbol of another table.
+    pub fn extend(&mut self, other: &SymbolTable) {
+        for (name, address) in other.iter() {
+            self.insert(name, address);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Iterate over the symbols in order of name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        self.symbols
+            .iter()
+            .map(|(name, address)| (name.as_str(), *address))
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).copied()
+    }
+
+    pub fn name_of(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    /// Format an address for display, adding its name when it has one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::symbols::SymbolTable;
+    /// let mut table = SymbolTable::new();
+    /// table.insert("start", 0x0800);
+    /// assert_eq!(table.describe(0x0800), "$0800 <start>");
+    /// assert_eq!(table.describe(0x0801), "$0801");
+    /// ```
+    pub fn describe(&self, address: u16) -> String {
+        match self.name_of(address) {
+            Some(name) => format!("${:04X} <{}>", address, name),
+            None => format!("${:04X}", address),
+        }
+    }
+
+    /// Write the table in the given format.
+    ///
+    /// Local and anonymous labels, such as `main@loop`, are left out of the
+    /// VICE and text formats, since neither VICE nor the assembler reads them.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The format to write.
+    ///
+    /// # Returns
+    ///
+    /// The contents of the symbol file.
+    pub fn export(&self, format: SymbolFormat) -> String {
+        let mut output = String::new();
+
+        match format {
+            SymbolFormat::Vice => {
+                for (name, address) in self.iter().filter(|(name, _)| is_identifier(name)) {
+                    let _ = writeln!(output, "al C:{:04X} .{}", address, name);
+                }
+            }
+            SymbolFormat::Text => {
+                for (name, address) in self.iter().filter(|(name, _)| is_identifier(name)) {
+                    let _ = writeln!(output, "{} = ${:04X}", name, address);
+                }
+            }
+            SymbolFormat::Json => {
+                let object: Map<String, Value> = self
+                    .iter()
+                    .map(|(name, address)| (name.to_string(), Value::from(address)))
+                    .collect();
+                output = serde_json::to_string_pretty(&object).unwrap_or_default();
+                output.push('\n');
+            }
+        }
+
+        output
+    }
+
+    /// Read a symbol file written in any of the supported formats.
+    ///
+    /// The format is recognised from the contents: a JSON object, VICE `al`
+    /// commands, or `NAME = value` lines with `;` comments.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The contents of the symbol file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the table, or an error naming the line that could not be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use apple_ie::symbols::SymbolTable;
+    /// let table = SymbolTable::parse("al C:0800 .start\n").unwrap();
+    /// assert_eq!(table.address_of("start"), Some(0x0800));
+    /// ```
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut table = SymbolTable::new();
+
+        if text.trim_start().starts_with('{') {
+            let object: Map<String, Value> =
+                serde_json::from_str(text).context("Invalid JSON symbol file")?;
+            for (name, value) in &object {
+                match value.as_u64().and_then(|value| u16::try_from(value).ok()) {
+                    Some(address) => table.insert(name, address),
+                    None => bail!("Symbol `{}` is not a 16-bit address: {}", name, value),
+                }
+            }
+            return Ok(table);
+        }
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parsed = match line.strip_prefix("al ") {
+                Some(command) => parse_vice(command),
+                None => parse_assignment(line),
+            };
+
+            match parsed {
+                Some((name, address)) => table.insert(name, address),
+                None => bail!("Line {}: cannot read symbol from `{}`", index + 1, line),
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Read a symbol file from disk.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        SymbolTable::parse(&text).with_context(|| format!("Cannot load {}", path.display()))
+    }
+}
+
+/// Whether a name is written the way the assembler reads a global symbol.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Read the arguments of a VICE `al` command, such as `C:0800 .start`.
+fn parse_vice(command: &str) -> Option<(&str, u16)> {
+    let mut words = command.split_whitespace();
+    let address = words.next()?;
+    let address = address.strip_prefix("C:").unwrap_or(address);
+    let name = words.next()?;
+    let name = name.strip_prefix('.').unwrap_or(name);
+
+    let address = u16::from_str_radix(address, 16).ok()?;
+    Some((name, address))
+}
+
+/// Read a `NAME = value` line, with the value in decimal, `$` hex or `%` binary.
+fn parse_assignment(line: &str) -> Option<(&str, u16)> {
+    let (name, value) = line.split_once('=')?;
+    let name = name.trim();
+    let value = value.trim();
+
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let address = match (value.strip_prefix('$'), value.strip_prefix('%')) {
+        (Some(hex), _) => u16::from_str_radix(hex, 16),
+        (_, Some(binary)) => u16::from_str_radix(binary, 2),
+        _ => value.parse(),
+    };
+
+    Some((name, address.ok()?))
+}
@@ -1,5 +1,27 @@
 use super::*;
-use anyhow::Result;
+use crate::assembler::{
+    assemble_file, assemble_source, assemble_with, diagnostic::Diagnostics, dialect::Dialect,
+    listing, parse_define, source::SourceFile, Options,
+};
+use crate::conformance::{self, single_step, Fixture, Success};
+use crate::cpu::{
+    breakpoints::{Access, Breakpoint, Condition, Point, Stop, Watchpoint},
+    operations::Variant,
+    trace::{parse_range, Tracer},
+    CpuError, IllegalOpcodePolicy, StatusFlags,
+};
+use crate::disassembler::{render, source};
+use crate::symbols::{SymbolFormat, SymbolTable};
+use anyhow::{Context, Result};
+use std::{env, fs, path::PathBuf, process};
+
+/// Create an empty scratch directory for tests that need files on disk.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("apple-ie-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
 
 #[test]
 fn create_cpu() {
@@ -31,3 +53,1334 @@ fn load_cpu() -> Result<()> {
     cpu.load(0x800, &bytes);
     Ok(())
 }
+
+#[test]
+fn assemble_addressing_modes() -> Result<()> {
+    let program: &str = "ASL
+             ASL A
+             STA $10
+             BIT $2000
+             BEQ $0800";
+
+    let bytes: Vec<u8> = assemble(program)?;
+
+    assert_eq!(
+        bytes,
+        [0x0A, 0x0A, 0x85, 0x10, 0x2C, 0x00, 0x20, 0xF0, 0xF7]
+    );
+    Ok(())
+}
+
+#[test]
+fn assemble_reports_every_error() {
+    let program: &str = "LDZ #$01
+LDA #$01
+    LDA #$1234
+LDA $10,Q";
+
+    let error = assemble_source("bad.s", program).unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let positions: Vec<(usize, usize)> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.line, diagnostic.column))
+        .collect();
+
+    assert_eq!(positions, [(1, 1), (3, 10), (4, 9)]);
+    assert_eq!(
+        diagnostics.iter().next().unwrap().to_string(),
+        "error: unknown instruction `LDZ`
+ --> bad.s:1:1
+  |
+1 | LDZ #$01
+  | ^^^"
+    );
+}
+
+#[test]
+fn unsettled_symbols_are_reported_where_they_are_defined() {
+    let program: &str = "start: LDA #x
+x = x+1
+y = 2";
+
+    let error = assemble_source("loop.s", program).unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let found: Vec<(usize, &str)> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.line, diagnostic.message.as_str()))
+        .collect();
+
+    assert_eq!(
+        found,
+        [(
+            2,
+            "the value of `x` did not settle after 16 passes, going from 16 to 17"
+        )]
+    );
+}
+
+#[test]
+fn shift_counts_out_of_range_are_errors() -> Result<()> {
+    assert_eq!(
+        assemble("LDA #1<<7\nLDX #$80>>7")?,
+        [0xA9, 0x80, 0xA2, 0x01]
+    );
+
+    let error = assemble_source("shift.s", "LDA #1<<70\nLDA #4>>-1").unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let messages: Vec<&str> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "cannot shift by 70, only by 0 to 63",
+            "cannot shift by -1, only by 0 to 63"
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn assemble_never_panics_on_bad_input() {
+    for program in [
+        "LDA",
+        "LDA #",
+        "LDA ($10",
+        "JMP ($10),Y",
+        "$$$",
+        "BNE $1000",
+        "LDA #$",
+    ] {
+        assert!(
+            assemble(program).is_err(),
+            "{} should not assemble",
+            program
+        );
+    }
+}
+
+#[test]
+fn assemble_labels_and_data() -> Result<()> {
+    let program: &str = "    .org $0300
+    JMP end
+    .word end, $1234
+    .byte \"AB\", <end, >end
+end: BRK";
+
+    let assembly = assemble_source("data.s", program)?;
+
+    assert_eq!(assembly.origin, 0x0300);
+    assert_eq!(
+        assembly.bytes,
+        [0x4C, 0x0B, 0x03, 0x0B, 0x03, 0x34, 0x12, 0x41, 0x42, 0x0B, 0x03, 0x00]
+    );
+    assert_eq!(assembly.symbols.get("end"), Some(&0x030B));
+    Ok(())
+}
+
+#[test]
+fn assemble_nested_macros() -> Result<()> {
+    let program: &str = "ECHO = $FFEF
+.macro putc char
+    LDA #char
+    JSR ECHO
+.endm
+.macro spin count
+    LDX #0
+loop: INX
+    CPX #count
+    BNE loop
+.endm
+.macro banner
+    putc 'H'
+    putc 'I'
+    spin 3
+.endm
+start: banner
+    spin 2
+    BRK";
+
+    let bytes: Vec<u8> = assemble(program)?;
+
+    assert_eq!(
+        bytes,
+        [
+            0xA9, 0x48, 0x20, 0xEF, 0xFF, 0xA9, 0x49, 0x20, 0xEF, 0xFF, 0xA2, 0x00, 0xE8, 0xE0,
+            0x03, 0xD0, 0xFB, 0xA2, 0x00, 0xE8, 0xE0, 0x02, 0xD0, 0xFB, 0x00
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn macro_errors_point_at_call_and_definition() {
+    let program: &str = ".macro load value
+    LDA #value
+.endm
+    load $1234
+    load 1, 2";
+
+    let error = assemble_source("macro.s", program).unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let lines: Vec<(usize, Vec<usize>)> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let notes = diagnostic.notes.iter().map(|note| note.line).collect();
+            (diagnostic.line, notes)
+        })
+        .collect();
+
+    // The bad value comes from the call site, inside the body on line 2
+    assert_eq!(lines, [(4, vec![4, 1]), (5, vec![1])]);
+}
+
+#[test]
+fn assemble_includes() -> Result<()> {
+    let dir = scratch_dir("includes");
+    fs::create_dir_all(dir.join("lib"))?;
+    fs::write(
+        dir.join("main.s"),
+        ".include \"defs.s\"
+    LDA #VALUE
+    .incbin \"table.bin\", 1, 2
+    .include \"exit.s\"",
+    )?;
+    fs::write(dir.join("defs.s"), "VALUE = $42")?;
+    fs::write(dir.join("table.bin"), [0x01, 0x02, 0x03, 0x04])?;
+    fs::write(dir.join("lib").join("exit.s"), "BRK")?;
+
+    let options = Options {
+        include_paths: vec![dir.join("lib")],
+        ..Options::default()
+    };
+    let assembly = assemble_file(&dir.join("main.s"), &options)?;
+
+    assert_eq!(assembly.bytes, [0xA9, 0x42, 0x02, 0x03, 0x00]);
+    Ok(())
+}
+
+#[test]
+fn include_errors_report_the_chain() -> Result<()> {
+    let dir = scratch_dir("include-chain");
+    fs::write(dir.join("main.s"), "NOP\n.include \"middle.s\"")?;
+    fs::write(
+        dir.join("middle.s"),
+        ".include \"bad.s\"\n.include \"main.s\"",
+    )?;
+    fs::write(dir.join("bad.s"), "LDZ #$01")?;
+
+    let error = assemble_file(&dir.join("main.s"), &Options::default()).unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let found: Vec<(String, Vec<usize>)> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let notes = diagnostic.notes.iter().map(|note| note.line).collect();
+            (diagnostic.message.clone(), notes)
+        })
+        .collect();
+
+    assert_eq!(
+        found[0],
+        ("unknown instruction `LDZ`".to_string(), vec![1, 2])
+    );
+    assert!(found[1].0.starts_with("include cycle: "));
+    assert_eq!(found[1].1, [2]);
+    Ok(())
+}
+
+#[test]
+fn assemble_conditionals() -> Result<()> {
+    let program = "
+        .ifndef RAM_TOP
+        RAM_TOP = $1000
+        .endif
+        .if RAM_TOP >= $8000 && !(RAM_TOP & $FFF)
+        LDA #$03
+        .elseif RAM_TOP = $4000
+        LDA #$02
+        .else
+        LDA #$01
+        .endif
+        .if 0
+        .if 1
+        NOT_ASSEMBLED
+        .else
+        NOT_ASSEMBLED_EITHER
+        .endif
+        .endif
+        .ifdef DEBUG
+        BRK
+        .endif";
+
+    let assemble_for = |defines: &[&str]| -> Result<Vec<u8>> {
+        let options = Options {
+            defines: defines
+                .iter()
+                .map(|define| parse_define(define).unwrap())
+                .collect(),
+            ..Options::default()
+        };
+        Ok(assemble_with(SourceFile::new("<input>", program), &options)?.bytes)
+    };
+
+    assert_eq!(assemble_for(&[])?, [0xA9, 0x01]);
+    assert_eq!(assemble_for(&["RAM_TOP=$4000"])?, [0xA9, 0x02]);
+    assert_eq!(assemble_for(&["RAM_TOP=$C000"])?, [0xA9, 0x03]);
+    assert_eq!(
+        assemble_for(&["RAM_TOP=$C010", "DEBUG"])?,
+        [0xA9, 0x01, 0x00]
+    );
+    assert!(parse_define("1ST=2").is_err());
+    Ok(())
+}
+
+#[test]
+fn unbalanced_conditionals_are_errors() {
+    let error = assemble(".if 1\n.else\n.else\n.endif\n.endif\n.if 0").unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let found: Vec<(usize, &str)> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.line, diagnostic.message.as_str()))
+        .collect();
+
+    assert_eq!(
+        found,
+        [
+            (3, "`.else` after `.else`"),
+            (5, "`.endif` without a matching `.if`"),
+            (6, "`.if` has no matching `.endif`"),
+        ]
+    );
+}
+
+#[test]
+fn listing_shows_addresses_bytes_and_cycles() -> Result<()> {
+    let program =
+        "start: LDA table,X\n       STA $0200\ntable: .byte 1, 2, 3, 4\n.if 0\n       NOP\n.endif";
+    let assembly = assemble_source("main.s", program)?;
+    let listing = listing::render(&assembly);
+    let lines: Vec<&str> = listing.lines().collect();
+
+    assert_eq!(lines[0].trim(), "; main.s");
+    assert_eq!(lines[1], "0800  BD 06 08    4+      1  start: LDA table,X");
+    assert_eq!(lines[2], "0803  8D 00 02    4       2         STA $0200");
+    assert_eq!(
+        lines[3],
+        "0806  01 02 03            3  table: .byte 1, 2, 3, 4"
+    );
+    assert_eq!(lines[4], "0809  04");
+    assert_eq!(lines[6], "                          5         NOP");
+    assert!(listing.ends_with(
+        "Symbols:\n  start                    $0800\n  table                    $0806\n"
+    ));
+    Ok(())
+}
+
+#[test]
+fn symbol_files_round_trip() -> Result<()> {
+    let program =
+        ".macro wait\nspin: DEX\n  BNE spin\n.endm\nstart: wait\nSCREEN = $D012\nOFFSET = -1";
+    let assembly = assemble_source("main.s", program)?;
+    let table = SymbolTable::from_values(&assembly.symbols);
+
+    assert_eq!(table.address_of("start"), Some(0x0800));
+    assert_eq!(table.name_of(0xD012), Some("SCREEN"));
+    assert_eq!(table.len(), 2);
+
+    for format in [SymbolFormat::Vice, SymbolFormat::Text, SymbolFormat::Json] {
+        assert_eq!(SymbolTable::parse(&table.export(format))?, table);
+    }
+
+    assert_eq!(
+        table.export(SymbolFormat::Vice),
+        "al C:D012 .SCREEN\nal C:0800 .start\n"
+    );
+    assert!(SymbolTable::parse("start: $0800").is_err());
+
+    // Exported text assembles, leaving out the local labels it cannot name
+    let program = "main:  LDX #2\n@loop: DEX\n       BNE @loop\n-      BEQ -\nother: RTS";
+    let assembly = assemble_source("main.s", program)?;
+    let table = SymbolTable::from_values(&assembly.symbols);
+    assert_eq!(table.address_of("main@loop"), Some(0x0802));
+    let text = table.export(SymbolFormat::Text);
+    assert_eq!(text, "main = $0800\nother = $0807\n");
+    let included = assemble_source("symbols.s", &format!("{}JMP other", text))?;
+    assert_eq!(included.bytes, [0x4C, 0x07, 0x08]);
+    assert!(!table.export(SymbolFormat::Vice).contains('@'));
+    Ok(())
+}
+
+#[test]
+fn assemble_local_and_anonymous_labels() -> Result<()> {
+    let program = "
+first:  LDX #$02
+@loop:  DEX
+        BNE @loop
+.done:  BEQ +
+second: LDY #$02
+@loop:  DEY
+        BNE @loop
+        JMP .done
+.done:
+-       DEX
+        BNE -
+        BEQ ++
++       NOP
+++      BNE -";
+
+    let assembly = assemble_source("main.s", program)?;
+    assert_eq!(
+        assembly.bytes,
+        [
+            0xA2, 0x02, 0xCA, 0xD0, 0xFD, 0xF0, 0x0D, // first
+            0xA0, 0x02, 0x88, 0xD0, 0xFD, 0x4C, 0x0F, 0x08, // second
+            0xCA, 0xD0, 0xFD, 0xF0, 0x01, 0xEA, 0xD0, 0xF8, // anonymous
+        ]
+    );
+    assert_eq!(assembly.symbols.get("first@loop"), Some(&0x0802));
+    assert_eq!(assembly.symbols.get("second@loop"), Some(&0x0809));
+
+    let error = assemble("BNE -\nBEQ +").unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "no `-` label before this line",
+            "no `+` label after this line"
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn dialects_assemble_to_the_same_bytes() -> Result<()> {
+    let native = "
+        * = $0300
+        ECHO = $FFEF
+.macro print text
+        LDA #<text
+        LDX #>text
+        JSR ECHO
+.endm
+start:  print message
+        LDX #$00
+-       DEX
+        BNE -
+.if DEBUG
+        BRK
+.else
+        RTS
+.endif
+message: .byte \"HI\", 0
+buffer: .fill 4, $EA
+        .word start";
+
+    let ca65 = "
+        .setcpu \"6502\"
+        .segment \"CODE\"
+        .org $0300
+        ECHO := $FFEF
+.macro print text
+        lda #.lobyte(text)
+        ldx #.hibyte(text)
+        jsr ECHO
+.endmacro
+.proc start
+        print strings::message
+        ldx #$00
+:       dex
+        bne :-
+.if DEBUG
+        brk
+.else
+        rts
+.endif
+.endproc
+.scope strings
+message: .asciiz \"HI\"
+buffer: .res 4, $EA
+.endscope
+        .addr start";
+
+    let acme = "
+        !cpu 6502
+        * = $0300
+        ECHO = $FFEF
+!macro print .text {
+        lda #<.text
+        ldx #>.text
+        jsr ECHO
+}
+start   +print message
+        ldx #$00
+-       dex
+        bne -
+!if DEBUG {
+        brk
+} else {
+        rts
+}
+message !text \"HI\", 0
+buffer  !fill 4, $EA
+        !word start";
+
+    let assemble_as = |program: &str, dialect: Dialect| -> Result<Vec<u8>> {
+        let options = Options {
+            defines: [("DEBUG".to_string(), 0)].into(),
+            dialect,
+            ..Options::default()
+        };
+        Ok(assemble_with(SourceFile::new("<input>", program), &options)?.bytes)
+    };
+
+    let expected = assemble_as(native, Dialect::Native)?;
+    assert_eq!(&expected[..4], [0xA9, 0x0D, 0xA2, 0x03]);
+    assert_eq!(assemble_as(ca65, Dialect::Ca65)?, expected);
+    assert_eq!(assemble_as(acme, Dialect::Acme)?, expected);
+
+    // Each ca65 `.proc` keeps its own labels
+    let procs = ".proc first
+loop:   dex
+        bne loop
+.endproc
+.proc second
+loop:   dey
+        bne loop
+        jmp first::loop
+.endproc";
+    let options = Options {
+        dialect: Dialect::Ca65,
+        ..Options::default()
+    };
+    let assembly = assemble_with(SourceFile::new("procs.s", procs), &options)?;
+    assert_eq!(
+        assembly.bytes,
+        [0xCA, 0xD0, 0xFD, 0x88, 0xD0, 0xFD, 0x4C, 0x00, 0x08]
+    );
+    assert_eq!(assembly.symbols.get("second@loop"), Some(&0x0803));
+    let error = assemble_with(
+        SourceFile::new(
+            "open.s",
+            ".proc open
+  rts",
+        ),
+        &options,
+    );
+    assert!(error.is_err());
+    Ok(())
+}
+
+#[test]
+fn unbalanced_acme_blocks_are_errors() {
+    let options = Options {
+        dialect: Dialect::Acme,
+        ..Options::default()
+    };
+    let program = "!if 1 {\n  nop\n}\n}\n!macro twice {\n  nop";
+    let error = assemble_with(SourceFile::new("<input>", program), &options).unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let found: Vec<(usize, &str)> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.line, diagnostic.message.as_str()))
+        .collect();
+
+    assert_eq!(
+        found,
+        [
+            (4, "`}` without a matching `{`"),
+            (5, "`{` has no matching `}`"),
+            (5, "`.macro` has no matching `.endm`"),
+        ]
+    );
+}
+
+#[test]
+fn assemble_picks_zero_page_from_values() -> Result<()> {
+    let program: &str = "PTR = $10
+        LDA PTR
+        STA buffer
+        LDA later,X
+        LDA a:PTR
+        LDA $0012
+buffer: .byte 0
+later = $20";
+
+    assert_eq!(
+        assemble(program)?,
+        [0xA5, 0x10, 0x8D, 0x0C, 0x08, 0xB5, 0x20, 0xAD, 0x10, 0x00, 0xA5, 0x12, 0x00]
+    );
+
+    // The labels move down once the forward references shrink to zero page
+    let shrinking: &str = "* = $00F8
+        LDA target
+        LDA target
+target: .byte 0";
+    let assembly = assemble_source("zp.s", shrinking)?;
+    assert_eq!(assembly.bytes, [0xA5, 0xFC, 0xA5, 0xFC, 0x00]);
+    assert_eq!(assembly.symbols["target"], 0xFC);
+
+    let options = Options {
+        dialect: Dialect::Acme,
+        ..Options::default()
+    };
+    let acme = assemble_with(SourceFile::new("<input>", "lda+2 $12"), &options)?;
+    assert_eq!(acme.bytes, [0xAD, 0x12, 0x00]);
+    Ok(())
+}
+
+#[test]
+fn disassemble_every_addressing_mode() -> Result<()> {
+    let program: &str = "start:  ASL A
+        LDA #$01
+        LDA $10,X
+        LDX $10,Y
+        STA $1234,Y
+        JMP ($1234)
+        LDA ($10,X)
+        LDA ($10),Y
+        BNE start
+        .byte $FF, $4C";
+
+    let assembly = assemble_source("modes.s", program)?;
+    let instructions = disassemble(&assembly.bytes, assembly.origin, Variant::Nmos);
+    let texts: Vec<String> = instructions
+        .iter()
+        .map(|instruction| instruction.text(&SymbolTable::new()))
+        .collect();
+
+    assert_eq!(
+        texts,
+        [
+            "ASL A",
+            "LDA #$01",
+            "LDA $10,X",
+            "LDX $10,Y",
+            "STA $1234,Y",
+            "JMP ($1234)",
+            "LDA ($10,X)",
+            "LDA ($10),Y",
+            "BNE $0800",
+            ".byte $FF",
+            ".byte $4C",
+        ]
+    );
+
+    let symbols = SymbolTable::from_values(&assembly.symbols);
+    let listing = render(&instructions, &symbols);
+    assert!(listing.contains("0811  D0 ED     BNE start"));
+
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+    assert_eq!(cpu.disassemble(0x0800, 2), instructions[..2]);
+    Ok(())
+}
+
+#[test]
+fn disassembled_source_reassembles_to_the_same_bytes() -> Result<()> {
+    let program: &str = "ECHO = $FDED
+        .org $FF00
+reset:  LDX #$00
+loop:   LDA table,X
+        BEQ done
+        JSR print
+        INX
+        BNE loop
+done:   LDA a:$0012
+        JMP (vector)
+print:  JSR ECHO
+        STA $D012
+        RTS
+table:  .byte \"HI\", 0
+vector: .word reset
+irq:    RTI
+        .fill $FFFA - *
+        .word irq, reset, irq";
+
+    let assembly = assemble_source("rom.s", program)?;
+    let mut symbols = SymbolTable::new();
+    symbols.insert("ECHO", 0xFDED);
+
+    let text = source::render(
+        &assembly.bytes,
+        assembly.origin,
+        &[],
+        &symbols,
+        Variant::Nmos,
+    );
+    assert!(text.contains("ECHO = $FDED"));
+    assert!(text.contains("LDA a:$0012"));
+    assert!(text.contains("JMP (LFF1D)"));
+    assert!(text.contains(".word LFF1F, LFF00, LFF1F"));
+
+    let reassembled = assemble_source("disassembled.s", &text)?;
+    assert_eq!(reassembled.origin, assembly.origin);
+    assert_eq!(reassembled.bytes, assembly.bytes);
+    Ok(())
+}
+
+#[test]
+fn cpu_runs_programs() -> Result<()> {
+    let program: &str = "LDX #$05
+        LDA #$00
+        CLC
+loop:   ADC #$03
+        DEX
+        BNE loop
+        STA $10
+        JSR double
+        PHA
+        SED
+        LDA #$19
+        CLC
+        ADC #$28
+        CLD
+        STA $11
+        PLA
+        BRK
+double: ASL A
+        RTS";
+
+    let assembly = assemble_source("program.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+
+    while cpu.read_addr(cpu.registers.program_counter)? != 0x00 {
+        cpu.step()?;
+    }
+
+    assert_eq!(cpu.registers.accumulator, 0x1E);
+    assert_eq!(cpu.read_addr(0x10)?, 0x0F);
+    assert_eq!(cpu.read_addr(0x11)?, 0x47);
+    assert_eq!(cpu.registers.stack_pointer, 0xFD);
+    assert_eq!(cpu.cycles, 77);
+    Ok(())
+}
+
+#[test]
+fn breakpoints_and_watchpoints_stop_execution() -> Result<()> {
+    let program: &str = "start:  LDX #$00
+loop:   INX
+        STX $10
+        LDA table,X
+        CPX #$05
+        BNE loop
+        BRK
+table:  .byte 1, 2, 3, 4, 5, 6";
+
+    let assembly = assemble_source("watch.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+    let loop_address = assembly.symbols["loop"] as u16;
+    let table = assembly.symbols["table"] as u16;
+
+    let breakpoint = cpu.breakpoints.add(Point::Breakpoint(Breakpoint {
+        condition: Some(Condition::new("X == 2").map_err(anyhow::Error::msg)?),
+        ..Breakpoint::at(loop_address)
+    }));
+    assert_eq!(
+        cpu.resume(u64::MAX, None)?,
+        Stop::Breakpoint {
+            id: breakpoint,
+            address: loop_address
+        }
+    );
+    assert_eq!(cpu.registers.x, 0x02);
+
+    cpu.breakpoints.remove(breakpoint);
+    let watchpoint = cpu.breakpoints.add(Point::Watchpoint(Watchpoint {
+        ignore: 1,
+        ..Watchpoint::on(table..=table + 5, Access::Read)
+    }));
+    assert_eq!(
+        cpu.resume(u64::MAX, None)?,
+        Stop::Watchpoint {
+            id: watchpoint,
+            address: table + 4,
+            value: 5,
+            access: Access::Read
+        }
+    );
+
+    assert!(Condition::new("Q == 1").is_err());
+    assert_eq!(cpu.resume(2, None)?, Stop::Limit);
+
+    // A condition that cannot be worked out stops, saying why
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+    let failing = cpu.breakpoints.add(Point::Breakpoint(Breakpoint {
+        condition: Some(Condition::new("A / (X - X)").map_err(anyhow::Error::msg)?),
+        ..Breakpoint::at(loop_address)
+    }));
+    assert_eq!(
+        cpu.resume(u64::MAX, None)?,
+        Stop::Condition {
+            id: failing,
+            address: loop_address,
+            message: "division by zero".to_string()
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn trace_writes_a_line_per_instruction_in_range() -> Result<()> {
+    let program: &str = "start:  LDX #$02
+loop:   DEX
+        BNE loop
+        BRK";
+
+    let assembly = assemble_source("trace.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.load(assembly.origin, &assembly.bytes);
+
+    let path = scratch_dir("trace").join("trace.log");
+    let range = parse_range("$0802..$0804").map_err(anyhow::Error::msg)?;
+    cpu.tracer = Some(Tracer::create(&path, vec![range])?);
+    assert_eq!(cpu.resume(u64::MAX, None)?, Stop::Brk { address: 0x0805 });
+    cpu.tracer = None;
+
+    // Resuming from the BRK runs it, through the vector at $FFFE
+    assert_eq!(cpu.resume(1, None)?, Stop::Limit);
+    assert_eq!(cpu.registers.program_counter, 0x0000);
+
+    let trace = fs::read_to_string(&path)?;
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "0802  CA        DEX             A:00 X:02 Y:00 P:24 SP:FD CYC:2",
+            "0803  D0 FD     BNE $0802       A:00 X:01 Y:00 P:24 SP:FD CYC:4",
+            "0802  CA        DEX             A:00 X:01 Y:00 P:24 SP:FD CYC:7",
+            "0803  D0 FD     BNE $0802       A:00 X:00 Y:00 P:26 SP:FD CYC:9",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn cpu_steps_back_and_rewinds() -> Result<()> {
+    let program: &str = "start:  LDX #$00
+loop:   INX
+        STX $10
+        TXA
+        PHA
+        CPX #$05
+        BNE loop
+        BRK";
+
+    let assembly = assemble_source("rewind.s", program)?;
+    let mut cpu = Cpu::new();
+    cpu.history.set_capacity(8);
+    cpu.load(assembly.origin, &assembly.bytes);
+
+    for _ in 0..13 {
+        cpu.step()?;
+    }
+    let registers = cpu.registers;
+    let cycles = cpu.cycles;
+    assert_eq!(cpu.read_addr(0x10)?, 0x02);
+
+    cpu.step()?;
+    cpu.step()?;
+    assert_eq!(cpu.read_addr(0x10)?, 0x03);
+    assert!(cpu.step_back());
+    assert!(cpu.step_back());
+    assert_eq!(cpu.registers, registers);
+    assert_eq!(cpu.cycles, cycles);
+    assert_eq!(cpu.read_addr(0x10)?, 0x02);
+
+    // Only the last eight instructions are kept
+    assert_eq!(cpu.rewind(0), None);
+    assert_eq!(cpu.rewind(cycles - 10), Some(4));
+    assert!(cpu.cycles <= cycles - 10);
+    assert_eq!(cpu.read_addr(0x01FD)?, 0x01);
+    assert_eq!(cpu.read_addr(0x01FC)?, 0x00);
+    Ok(())
+}
+
+#[test]
+fn rewinding_restores_keyboard_and_display() -> Result<()> {
+    let mut cpu = Cpu::new();
+    cpu.history.set_capacity(8);
+    cpu.load(0x0800, &[0xAD, 0x10, 0xD0, 0x8D, 0x12, 0xD0, 0x00]);
+    cpu.bus.pia.press(b'a');
+    let pia = cpu.bus.pia.clone();
+
+    cpu.step()?;
+    cpu.step()?;
+    assert!(cpu.bus.pia.keys.is_empty());
+    assert_eq!(cpu.bus.pia.display.text(), "A");
+
+    // Undoing the read puts the key back, and undoing the write unprints it
+    assert!(cpu.step_back());
+    assert!(cpu.step_back());
+    assert_eq!(cpu.bus.pia, pia);
+
+    // Replaying reads the same key again
+    cpu.step()?;
+    cpu.step()?;
+    assert_eq!(cpu.registers.accumulator, 0xC1);
+    assert_eq!(cpu.bus.pia.display.text(), "A");
+    Ok(())
+}
+
+#[test]
+fn trap_harness_reports_where_programs_stop() -> Result<()> {
+    let program: &str = "start:  LDX #$03
+loop:   DEX
+        BNE loop
+        CPX #$00
+        BNE fail
+pass:   JMP pass
+fail:   JMP fail";
+
+    let assembly = assemble_source("trap.s", program)?;
+    let fixture = |success| Fixture {
+        name: "trap.bin",
+        load: assembly.origin,
+        start: assembly.origin,
+        success,
+        max_cycles: 1_000,
+    };
+
+    let report = fixture(Success::TrapAt(0x0809)).run(&mut Cpu::new(), &assembly.bytes)?;
+    assert!(report.passed, "{}", report);
+    assert_eq!(report.address, 0x0809);
+
+    let report = fixture(Success::TrapAt(0x080C)).run(&mut Cpu::new(), &assembly.bytes)?;
+    assert!(!report.passed);
+
+    // A BRK ends programs judged by a flag in memory
+    let mut cpu = Cpu::new();
+    cpu.load_addr(0x0010, 0x01);
+    let report = fixture(Success::ZeroAt(0x0010)).run(&mut cpu, &[0xC6, 0x10, 0x00])?;
+    assert_eq!((report.address, report.passed), (0x0802, true));
+
+    let mut cpu = Cpu::new();
+    cpu.load(0x0800, &[0xE8, 0xD0, 0xFD, 0x4C, 0x00, 0x08]);
+    assert!(conformance::run_to_trap(&mut cpu, 100, false).is_err());
+    Ok(())
+}
+
+/// Run one of Klaus Dormann's tests, failing if its binary has not been added.
+fn run_dormann_test(fixture: Fixture) -> Result<()> {
+    let image = fixture.read().with_context(|| {
+        format!(
+            "{} is missing; add it to {} as its README describes",
+            fixture.name,
+            conformance::FIXTURES
+        )
+    })?;
+
+    let report = fixture.run(&mut Cpu::new(), &image)?;
+    assert!(report.passed, "{} {}", fixture.name, report);
+    Ok(())
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/6502_functional_test.bin, see tests/fixtures/README.md"]
+fn dormann_functional_test() -> Result<()> {
+    run_dormann_test(conformance::FUNCTIONAL_TEST)
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/6502_decimal_test.bin, see tests/fixtures/README.md"]
+fn dormann_decimal_test() -> Result<()> {
+    run_dormann_test(conformance::DECIMAL_TEST)
+}
+
+#[test]
+fn decimal_mode_matches_bcd_arithmetic() -> Result<()> {
+    let bcd = |value: u8| ((value / 10) << 4) | (value % 10);
+
+    for variant in [Variant::Nmos, Variant::Cmos] {
+        let mut cpu = Cpu::new();
+        cpu.variant = variant;
+        for (first, second, carry) in (0..100u8)
+            .flat_map(|first| (0..100u8).map(move |second| (first, second)))
+            .flat_map(|(first, second)| [(first, second, false), (first, second, true)])
+        {
+            let sum = u16::from(first) + u16::from(second) + u16::from(carry);
+            let difference = i16::from(first) - i16::from(second) - i16::from(!carry);
+            for (opcode, result, carry_out) in [
+                (0x69, (sum % 100) as u8, sum >= 100),
+                (0xE9, difference.rem_euclid(100) as u8, difference >= 0),
+            ] {
+                // SED, CLC or SEC, LDA #first, ADC or SBC #second
+                let set_carry = if carry { 0x38 } else { 0x18 };
+                cpu.load(
+                    0x0800,
+                    &[0xF8, set_carry, 0xA9, bcd(first), opcode, bcd(second)],
+                );
+                for _ in 0..4 {
+                    cpu.step()?;
+                }
+
+                let case = format!("{} ${:02X} op ${:02X}", variant, first, opcode);
+                assert_eq!(cpu.registers.accumulator, bcd(result), "{}", case);
+                assert_eq!(cpu.get_status(StatusFlags::CARRY), carry_out, "{}", case);
+                // Only the 65C02 sets N and Z from the decimal result
+                if variant == Variant::Cmos {
+                    assert_eq!(cpu.get_status(StatusFlags::ZERO), result == 0, "{}", case);
+                    let negative = bcd(result) & 0x80 != 0;
+                    assert_eq!(cpu.get_status(StatusFlags::NEGATIVE), negative, "{}", case);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn single_step_cases_match_the_cpu() -> Result<()> {
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(single_step::FIXTURES);
+    let summary = single_step::run_directory(&directory, Variant::Nmos)?;
+    assert!(summary.failures.is_empty(), "{}", summary);
+    assert_eq!((summary.passed, summary.skipped), (9, 1));
+
+    // BRA and STZ pass on the 65C02, and are undocumented NOPs on the 6502
+    let cmos = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(single_step::CMOS_FIXTURES);
+    let summary = single_step::run_directory(&cmos, Variant::Cmos)?;
+    assert!(summary.failures.is_empty(), "{}", summary);
+    assert_eq!((summary.passed, summary.skipped), (3, 0));
+    let summary = single_step::run_directory(&cmos, Variant::Nmos)?;
+    assert_eq!(summary.failures.len(), 3);
+
+    // A wrong result is reported against the register it is in
+    let mut cases = single_step::read(&directory.join("a9.json"))?;
+    cases[0].expected.a = 0x01;
+    cases[0].cycles = 3;
+    let mut summary = single_step::Summary::default();
+    summary.run(&cases[..1], Variant::Nmos);
+    assert_eq!(
+        summary.failures,
+        vec![(
+            "a9 00".to_string(),
+            vec![
+                "A is $00, not $01".to_string(),
+                "took 2 cycles, not 3".to_string()
+            ]
+        )]
+    );
+
+    assert!(single_step::parse(r#"[{"name": "ea", "initial": {}}]"#).is_err());
+    Ok(())
+}
+
+#[test]
+fn cpu_errors_can_be_matched_on() {
+    let mut cpu = Cpu::new();
+    cpu.load(0x0800, &[0xEA, 0x02]);
+    cpu.step().unwrap();
+    let error = cpu.step().unwrap_err();
+    assert_eq!(
+        error,
+        CpuError::IllegalOpcode {
+            pc: 0x0801,
+            opcode: 0x02
+        }
+    );
+    assert_eq!(error.to_string(), "Illegal opcode $02 at $0801");
+    // The PC stays on the opcode, so the same error comes back rather than its operand running
+    assert_eq!(cpu.registers.program_counter, 0x0801);
+    assert_eq!(cpu.step().unwrap_err(), error);
+
+    // INX, CPX #$02, BNE back to the INX, BRK
+    let mut cpu = Cpu::new();
+    cpu.load(0x0800, &[0xE8, 0xE0, 0x02, 0xD0, 0xFB, 0x00]);
+    let id = cpu
+        .breakpoints
+        .add(Point::Breakpoint(Breakpoint::at(0x0800)));
+    let stop = CpuError::Breakpoint(Stop::Breakpoint {
+        id,
+        address: 0x0800,
+    });
+    assert_eq!(cpu.fde().unwrap_err(), stop);
+
+    // Calling again carries on past the breakpoint, which still stops the next time round
+    for _ in 0..3 {
+        cpu.fde().unwrap();
+    }
+    assert_eq!(cpu.registers.program_counter, 0x0800);
+    assert_eq!(cpu.fde().unwrap_err(), stop);
+    for _ in 0..3 {
+        cpu.fde().unwrap();
+    }
+    assert_eq!(cpu.registers.x, 2);
+    assert_eq!(cpu.fde().unwrap_err(), CpuError::Halted { pc: 0x0805 });
+
+    // Errors still pass through code using anyhow
+    let result: Result<()> = (|| Ok(Cpu::new().fde()?))();
+    assert!(result
+        .unwrap_err()
+        .downcast_ref::<CpuError>()
+        .is_some_and(|error| matches!(error, CpuError::Halted { .. })));
+}
+
+#[test]
+fn illegal_opcodes_follow_the_policy() {
+    let mut cpu = Cpu::new();
+    cpu.load(0x0800, &[0xA7, 0x10]);
+    assert_eq!(
+        cpu.step().unwrap_err(),
+        CpuError::IllegalOpcode {
+            pc: 0x0800,
+            opcode: 0xA7
+        }
+    );
+
+    // NOPs skip the operands the opcode would have had, even for a JAM
+    let mut cpu = Cpu::new();
+    cpu.illegal_opcodes = IllegalOpcodePolicy::Nop;
+    cpu.load(0x0800, &[0x0C, 0x34, 0x12, 0x02, 0xE8]);
+    assert_eq!(
+        cpu.disassemble(0x0800, 1)[0].to_string(),
+        "0800  0C 34 12  NOP $1234"
+    );
+    cpu.step().unwrap();
+    assert_eq!((cpu.registers.program_counter, cpu.cycles), (0x0803, 4));
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.registers.x, 0x01);
+
+    let mut cpu = Cpu::new();
+    cpu.illegal_opcodes = IllegalOpcodePolicy::Emulate;
+    cpu.load_addr(0x0010, 0x81);
+    cpu.load_addr(0x0011, 0x40);
+    cpu.load(
+        0x0800,
+        &[
+            0xA7, 0x10, // LAX $10
+            0x87, 0x12, // SAX $12
+            0xC7, 0x11, // DCP $11
+            0xE7, 0x11, // ISC $11
+            0x07, 0x10, // SLO $10
+            0x67, 0x12, // RRA $12
+            0xCB, 0x01, // SBX #$01
+            0x6B, 0xFF, // ARR #$FF
+            0x02, // JAM
+        ],
+    );
+
+    // The debugger and trace show undocumented opcodes the way they run
+    let texts: Vec<String> = cpu
+        .disassemble(0x0800, 9)
+        .iter()
+        .map(|instruction| instruction.text(&SymbolTable::new()))
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            "LAX $10",
+            "SAX $12",
+            "DCP $11",
+            "ISC $11",
+            "SLO $10",
+            "RRA $12",
+            "SBX #$01",
+            "ARR #$FF",
+            ".byte $02"
+        ]
+    );
+
+    let mut step = |count| {
+        for _ in 0..count {
+            cpu.step().unwrap();
+        }
+        let registers = cpu.registers;
+        (registers.accumulator, registers.x, registers.status.bits())
+    };
+    assert_eq!(step(1), (0x81, 0x81, 0xA4));
+    assert_eq!(step(2), (0x81, 0x81, 0x25));
+    assert_eq!(step(1), (0x41, 0x81, 0x65));
+    assert_eq!(step(2), (0x04, 0x81, 0x25));
+    assert_eq!(step(1), (0x04, 0xFF, 0xA4));
+    assert_eq!(step(1), (0x02, 0xFF, 0x24));
+
+    assert_eq!(cpu.memory().ram[0x10..0x13], [0x02, 0x40, 0xC0]);
+    assert_eq!(cpu.cycles, 30);
+    assert_eq!(
+        cpu.step().unwrap_err(),
+        CpuError::IllegalOpcode {
+            pc: 0x0810,
+            opcode: 0x02
+        }
+    );
+}
+
+#[test]
+fn cmos_variant_adds_instructions_and_fixes_quirks() -> Result<()> {
+    let program: &str = "        LDA #$F0
+        STA $10
+        LDA #$30
+        TSB $10
+        TRB $10
+        LDX #$11
+        PHX
+        PLY
+        LDA #$10
+        STA $20
+        STZ $21
+        LDA ($20)
+        BRA skip
+        LDA #$FF
+skip:   SED
+        CLC
+        LDA #$99
+        ADC #$01
+        BRK";
+
+    let cmos = Options {
+        variant: Variant::Cmos,
+        ..Options::default()
+    };
+    let assembly = assemble_with(SourceFile::new("cmos.s", program), &cmos)?;
+    let mut cpu = Cpu::new();
+    cpu.variant = Variant::Cmos;
+    cpu.load(assembly.origin, &assembly.bytes);
+    assert!(matches!(cpu.resume(u64::MAX, None)?, Stop::Brk { .. }));
+
+    assert_eq!(cpu.memory().ram[0x10], 0xC0);
+    assert_eq!(cpu.registers.y, 0x11);
+    // The decimal sum is zero, and the 65C02 says so
+    assert_eq!(cpu.registers.accumulator, 0x00);
+    assert!(cpu.get_status(StatusFlags::ZERO) && cpu.get_status(StatusFlags::CARRY));
+
+    // The disassembler reads the program back for the 65C02 only
+    let texts = |variant| -> Vec<String> {
+        disassemble(&assembly.bytes, assembly.origin, variant)
+            .iter()
+            .map(|instruction| instruction.text(&SymbolTable::new()))
+            .collect()
+    };
+    assert!(texts(Variant::Cmos).contains(&"STZ $21".to_string()));
+    assert!(texts(Variant::Cmos).contains(&"LDA ($20)".to_string()));
+    assert!(texts(Variant::Nmos).contains(&".byte $64".to_string()));
+    assert_eq!(
+        cpu.disassemble(assembly.origin + 6, 1)[0].text(&SymbolTable::new()),
+        "TSB $10"
+    );
+    let text = source::render(
+        &assembly.bytes,
+        assembly.origin,
+        &[],
+        &SymbolTable::new(),
+        Variant::Cmos,
+    );
+    let reassembled = assemble_with(SourceFile::new("disassembled.s", &text), &cmos)?;
+    assert_eq!(reassembled.bytes, assembly.bytes);
+
+    // The same program is rejected for the NMOS 6502
+    let error = assemble_source("nmos.s", program).unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    let messages: Vec<&str> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect();
+    assert!(messages.contains(&"`TSB` is only on the 65C02"));
+    assert!(messages.contains(&"`LDA` does not support Indirect addressing"));
+
+    // JMP ($10FF) takes its high byte from $1100 on the 65C02, $1000 on the 6502
+    for (variant, target, cycles) in [(Variant::Nmos, 0x0834, 5), (Variant::Cmos, 0x0934, 6)] {
+        let mut cpu = Cpu::new();
+        cpu.variant = variant;
+        cpu.load_addr(0x10FF, 0x34);
+        cpu.load_addr(0x1000, 0x08);
+        cpu.load_addr(0x1100, 0x09);
+        cpu.load(0x0800, &[0x6C, 0xFF, 0x10]);
+        cpu.step()?;
+        assert_eq!(
+            (cpu.registers.program_counter, cpu.cycles),
+            (target, cycles)
+        );
+    }
+
+    // JMP (table,X) jumps through a table of addresses, on the 65C02 only
+    let program = "        LDX #2
+        JMP (table,X)
+table:  .word first, second
+first:  BRK
+second: NOP
+        BRK";
+    let assembly = assemble_with(SourceFile::new("jump.s", program), &cmos)?;
+    assert_eq!(assembly.bytes[2..5], [0x7C, 0x05, 0x08]);
+    let error = assemble_source("jump.s", program).unwrap_err();
+    let diagnostics = error.downcast_ref::<Diagnostics>().unwrap();
+    assert_eq!(
+        diagnostics.iter().next().unwrap().message,
+        "`JMP` does not support IndexedIndirect addressing"
+    );
+
+    let mut cpu = Cpu::new();
+    cpu.variant = Variant::Cmos;
+    cpu.load(assembly.origin, &assembly.bytes);
+    cpu.step()?;
+    cpu.step()?;
+    assert_eq!((cpu.registers.program_counter, cpu.cycles), (0x080A, 8));
+    assert_eq!(
+        cpu.disassemble(0x0802, 1)[0].to_string(),
+        "0802  7C 05 08  JMP ($0805,X)"
+    );
+
+    // Decimal SBC: $00 - $0B leaves $9F on the 6502 and $8F on the 65C02, a cycle later
+    for (variant, difference, cycles) in [(Variant::Nmos, 0x9F, 8), (Variant::Cmos, 0x8F, 9)] {
+        let mut cpu = Cpu::new();
+        cpu.variant = variant;
+        // SED, SEC, LDA #$00, SBC #$0B
+        cpu.load(0x0800, &[0xF8, 0x38, 0xA9, 0x00, 0xE9, 0x0B]);
+        for _ in 0..4 {
+            cpu.step()?;
+        }
+        assert_eq!(
+            (cpu.registers.accumulator, cpu.cycles),
+            (difference, cycles)
+        );
+        assert!(!cpu.get_status(StatusFlags::CARRY));
+        assert!(cpu.get_status(StatusFlags::NEGATIVE));
+    }
+    Ok(())
+}
+
+#[test]
+fn machine_runs_programs_through_the_library_api() -> Result<()> {
+    use crate::{Bus, Machine};
+
+    // Echo one key, upper case, then stop
+    let mut machine = Machine::new(Variant::Cmos);
+    let assembly = machine.assemble(
+        "wait: BIT $D011
+               BPL wait
+               LDA $D010
+               STA $D012
+               STZ $10
+               BRK",
+    )?;
+    assert_eq!(
+        machine.cpu.symbols.describe(assembly.origin),
+        "$0800 <wait>"
+    );
+
+    machine.type_text("q");
+    assert_eq!(
+        machine.run(1_000)?,
+        Stop::Brk {
+            address: assembly.origin + 13
+        }
+    );
+    assert_eq!(machine.take_output(), "Q");
+    assert_eq!(machine.bus().peek(0x0010), Some(0x00));
+
+    // Reads through the bus take keys the way the CPU does, peeks leave them
+    let mut bus = Bus::new();
+    bus.pia.press(b'x');
+    assert_eq!(bus.peek(0xD010), Some(0xD8));
+    assert_eq!(bus.read(0xD011).map(|control| control & 0x80), Some(0x80));
+    assert_eq!(bus.read(0xD010), Some(0xD8));
+    assert_eq!(bus.read(0xD011).map(|control| control & 0x80), Some(0x00));
+
+    machine.bus_mut().write(0xD012, b'!' | 0x80);
+    assert_eq!(machine.take_output(), "!");
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use apple_ie::assemble;
+
+use anyhow::Result;
+use std::{env, fs, process::Command};
+
+#[test]
+fn disassembled_source_from_the_command_line_reassembles() -> Result<()> {
+    let dir = env::temp_dir().join(format!("apple-ie-cli-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir)?;
+
+    let bytes = assemble(
+        "      LDX #$00
+         loop: LDA text,X
+               BEQ done
+               STA $D012
+               INX
+               BNE loop
+         done: BRK
+         text: .byte \"HI\", 0",
+    )?;
+    fs::write(dir.join("hi.bin"), &bytes)?;
+
+    // Run from the scratch directory, so a stray log file would show up there
+    let output = Command::new(env!("CARGO_BIN_EXE_apple-ie"))
+        .current_dir(&dir)
+        .args(["--disassemble", "hi.bin", "--source"])
+        .output()?;
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout)?;
+    assert!(!text.contains("Logging initialised"), "{}", text);
+    assert!(!dir.join("apple-ie.log").exists());
+    assert_eq!(assemble(&text)?, bytes);
+    Ok(())
+}